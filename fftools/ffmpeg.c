@@ -1007,6 +1007,7 @@ static int ffmpeg_main_internal(int argc, char **argv)
 
     fftools_ctx->current_time = ti = get_benchmark_time_stamps();
     ret = transcode(sch);
+    fftools_ctx->transcode_ret = ret;
     if (ret >= 0 && fftools_ctx->do_benchmark) {
         int64_t utime, stime, rtime;
         fftools_ctx->current_time = get_benchmark_time_stamps();
@@ -1046,6 +1047,7 @@ int ffmpeg_run(FftoolsContext *ctx, int argc, char **argv)
     /* reset run-scoped state */
     fftools_ctx->received_sigterm = 0;
     fftools_ctx->received_nb_signals = 0;
+    fftools_ctx->transcode_ret = 0;
     atomic_store(&fftools_ctx->transcode_init_done, 0);
     atomic_store(&fftools_ctx->nb_output_dumped, 0);
     fftools_ctx->current_time.real_usec = 0;
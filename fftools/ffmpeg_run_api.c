@@ -32,6 +32,22 @@ void ffmpeg_ctx_request_exit(FftoolsContext *ctx)
     ctx->received_nb_signals = 2;
 }
 
+void ffmpeg_ctx_request_stop(FftoolsContext *ctx)
+{
+    if (!ctx)
+        return;
+    /* A single signal stops the transcode loop without interrupting I/O, so
+     * muxers still flush and write their trailers. */
+    ctx->received_sigterm = SIGINT;
+    if (!ctx->received_nb_signals)
+        ctx->received_nb_signals = 1;
+}
+
+int ffmpeg_ctx_transcode_result(FftoolsContext *ctx)
+{
+    return ctx ? ctx->transcode_ret : 0;
+}
+
 int ffmpeg_run_with_ctx(FftoolsContext *ctx, int argc, char **argv)
 {
     return ffmpeg_run(ctx, argc, argv);
@@ -7,6 +7,10 @@ FftoolsContext *ffmpeg_ctx_create(int install_signal_handlers,
                                   int stdin_interaction);
 void ffmpeg_ctx_free(FftoolsContext *ctx);
 void ffmpeg_ctx_request_exit(FftoolsContext *ctx);
+void ffmpeg_ctx_request_stop(FftoolsContext *ctx);
+/* Result of the last run's transcode before a stop request turned the exit
+ * code into 255; negative if muxing failed. */
+int ffmpeg_ctx_transcode_result(FftoolsContext *ctx);
 
 int ffmpeg_run_with_ctx(FftoolsContext *ctx, int argc, char **argv);
 int ffmpeg_run_with_options(int argc, char **argv, int install_signal_handlers,
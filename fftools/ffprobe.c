@@ -3651,6 +3651,9 @@ int ffprobe_run_with_ctx(FFProbeContext *ctx, int argc, char **argv,
     prev_tools = fftools_set_context(&tools_ctx);
 
     ffprobe_ctx = ctx;
+    /* Free what the previous reset or run allocated so a context can be
+     * reused for several runs. */
+    ffprobe_ctx_cleanup(ffprobe_ctx);
     ffprobe_ctx_reset(ffprobe_ctx);
 
     ret = ffprobe_main_internal(argc, argv);
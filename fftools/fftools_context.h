@@ -73,6 +73,8 @@ typedef struct FftoolsContext {
     atomic_int transcode_init_done;
     volatile sig_atomic_t received_sigterm;
     volatile sig_atomic_t received_nb_signals;
+    /* result of transcode() before a signal masks the exit code */
+    int transcode_ret;
     int install_signal_handlers;
 #ifdef HAVE_TERMIOS_H
     int restore_tty;
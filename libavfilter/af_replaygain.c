@@ -315,6 +315,7 @@ typedef struct ReplayGainContext {
     uint32_t histogram[HISTOGRAM_SLOTS];
     float peak;
     float gain;
+    int metadata;
     int yule_hist_i, butter_hist_i;
     const double *yule_coeff_a;
     const double *yule_coeff_b;
@@ -593,6 +594,15 @@ static int filter_frame(AVFilterLink *inlink, AVFrame *in)
 
     s->histogram[level]++;
 
+    if (s->metadata) {
+        char metabuf[32];
+
+        snprintf(metabuf, sizeof(metabuf), "%+.2f", calc_replaygain(s->histogram));
+        av_dict_set(&in->metadata, "lavfi.replaygain.track_gain", metabuf, 0);
+        snprintf(metabuf, sizeof(metabuf), "%.6f", s->peak);
+        av_dict_set(&in->metadata, "lavfi.replaygain.track_peak", metabuf, 0);
+    }
+
     av_frame_free(&out);
     return ff_filter_frame(outlink, in);
 }
@@ -638,6 +648,7 @@ static const AVFilterPad replaygain_outputs[] = {
 static const AVOption replaygain_options[] = {
     { "track_gain", "track gain (dB)", OFFSET(gain), AV_OPT_TYPE_FLOAT,{.dbl=0}, -FLT_MAX, FLT_MAX, FLAGS },
     { "track_peak", "track peak",      OFFSET(peak), AV_OPT_TYPE_FLOAT,{.dbl=0}, -FLT_MAX, FLT_MAX, FLAGS },
+    { "metadata", "inject the running track gain and peak in the frames", OFFSET(metadata), AV_OPT_TYPE_BOOL, {.i64=0}, 0, 1, AV_OPT_FLAG_AUDIO_PARAM|AV_OPT_FLAG_FILTERING_PARAM },
     { NULL }
 };
 
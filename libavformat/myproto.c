@@ -29,11 +29,15 @@ static int myproto_open(URLContext *h, const char *uri, int flags)
 static int myproto_read(URLContext *h, unsigned char *buf, int size)
 {
     MyProtoContext *c = h->priv_data;
+    int ret;
     if (!c || !c->rctx)
         return AVERROR(EIO);
     if (ff_check_interrupt(&h->interrupt_callback))
         return AVERROR_EXIT;
-    return rsproto_read(c->rctx, buf, size);
+    ret = rsproto_read(c->rctx, buf, size);
+    // A source returns 0 once it runs out of data, e.g. at the end of the
+    // downloaded part of a torrent. avio keeps retrying on 0.
+    return ret == 0 ? AVERROR_EOF : ret;
 }
 
 static int64_t myproto_seek(URLContext *h, int64_t pos, int whence)
@@ -14,10 +14,10 @@ fn add_link_arg(token: &str) {
         println!("cargo:rustc-link-lib=bz2");
     } else if token == "-liconv" {
         println!("cargo:rustc-link-lib=iconv");
-    } else if token.starts_with("-l") {
-        println!("cargo:rustc-link-lib={}", &token[2..]);
-    } else if token.starts_with("-L") {
-        println!("cargo:rustc-link-search=native={}", &token[2..]);
+    } else if let Some(lib) = token.strip_prefix("-l") {
+        println!("cargo:rustc-link-lib={}", lib);
+    } else if let Some(dir) = token.strip_prefix("-L") {
+        println!("cargo:rustc-link-search=native={}", dir);
     }
 }
 
@@ -82,6 +82,8 @@ fn main() {
         "fftools/ffmpeg_run_api.h",
         "fftools/fftools_context.c",
         "fftools/fftools_context.h",
+        "libavformat/myproto.c",
+        "libavfilter/af_replaygain.c",
     ] {
         let path = root.join(rel);
         if path.exists() {
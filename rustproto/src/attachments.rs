@@ -0,0 +1,174 @@
+use std::fs;
+
+use crate::{run_ffmpeg, AttachmentStream, Chapter, FfprobeOutput, Source, Stream};
+
+impl AttachmentStream {
+    pub fn filename(&self) -> Option<&str> {
+        self.common.tag("filename")
+    }
+
+    pub fn mimetype(&self) -> Option<&str> {
+        self.common.tag("mimetype")
+    }
+
+    /// Size of the attached file in bytes.
+    pub fn size(&self) -> Option<u64> {
+        self.extradata_size.and_then(|s| u64::try_from(s).ok())
+    }
+
+    /// Font attachments, which ASS subtitles need for rendering.
+    pub fn is_font(&self) -> bool {
+        let by_mime = self.mimetype().is_some_and(|m| {
+            let m = m.to_ascii_lowercase();
+            m.starts_with("font/") || m.contains("truetype") || m.contains("opentype")
+        });
+        let by_name = self.filename().is_some_and(|f| {
+            let f = f.to_ascii_lowercase();
+            f.ends_with(".ttf") || f.ends_with(".otf") || f.ends_with(".ttc")
+        });
+        by_mime || by_name || matches!(self.common.codec_name.as_str(), "ttf" | "otf")
+    }
+}
+
+impl Chapter {
+    pub fn title(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("title"))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl FfprobeOutput {
+    pub fn attachments(&self) -> impl Iterator<Item = &AttachmentStream> {
+        self.streams.iter().filter_map(|s| match s {
+            Stream::Attachment(a) => Some(a),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// An attachment stream (fonts and other files in Matroska).
+    Attachment,
+    /// A video stream with the `attached_pic` disposition, i.e. cover art.
+    AttachedPicture,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractedAttachment {
+    /// Input stream index.
+    pub index: i64,
+    pub kind: AttachmentKind,
+    pub filename: Option<String>,
+    pub mimetype: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Extract every attachment and attached picture listed in `probe` from
+/// `source` in a single ffmpeg run. `probe` must describe the same source.
+pub fn extract_attachments<S: Source + 'static>(
+    source: S,
+    probe: &FfprobeOutput,
+) -> Result<Vec<ExtractedAttachment>, String> {
+    let attachments: Vec<&AttachmentStream> = probe.attachments().collect();
+    let pictures: Vec<&crate::VideoStream> = probe
+        .streams
+        .iter()
+        .filter_map(|s| match s {
+            Stream::Video(v) if v.common.disposition_flags().attached_pic => Some(v),
+            _ => None,
+        })
+        .collect();
+    if attachments.is_empty() && pictures.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut args: Vec<String> = ["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    for a in &attachments {
+        args.push(format!("-dump_attachment:{}", a.common.index));
+        args.push(format!("{{outdir}}/attachment_{}", a.common.index));
+    }
+    args.extend(["-i".to_string(), "{input}".to_string()]);
+    if pictures.is_empty() {
+        // Attachments are written while the input is opened, but ffmpeg
+        // still needs an output. A single null frame keeps the run short.
+        args.extend(
+            [
+                "-f",
+                "lavfi",
+                "-i",
+                "nullsrc=s=16x16:d=0.04",
+                "-map",
+                "1:v",
+                "-frames:v",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ]
+            .iter()
+            .map(|s| s.to_string()),
+        );
+    }
+    for p in &pictures {
+        args.extend([
+            "-map".to_string(),
+            format!("0:{}", p.common.index),
+            "-c".to_string(),
+            "copy".to_string(),
+            "-frames:v".to_string(),
+            "1".to_string(),
+            "-update".to_string(),
+            "1".to_string(),
+            "-f".to_string(),
+            "image2".to_string(),
+            format!("{{outdir}}/picture_{}", p.common.index),
+        ]);
+    }
+
+    let output = run_ffmpeg(source, &args)?.wait()?;
+    let read = |name: String| {
+        fs::read(output.path().join(&name)).map_err(|e| format!("read {}: {}", name, e))
+    };
+    let mut extracted = Vec::with_capacity(attachments.len() + pictures.len());
+    for a in attachments {
+        extracted.push(ExtractedAttachment {
+            index: a.common.index,
+            kind: AttachmentKind::Attachment,
+            filename: a.filename().map(str::to_string),
+            mimetype: a.mimetype().map(str::to_string),
+            data: read(format!("attachment_{}", a.common.index))?,
+        });
+    }
+    for p in pictures {
+        extracted.push(ExtractedAttachment {
+            index: p.common.index,
+            kind: AttachmentKind::AttachedPicture,
+            filename: p.common.tag("filename").map(str::to_string),
+            mimetype: p
+                .common
+                .tag("mimetype")
+                .map(str::to_string)
+                .or_else(|| picture_mimetype(&p.common.codec_name).map(str::to_string)),
+            data: read(format!("picture_{}", p.common.index))?,
+        });
+    }
+    extracted.sort_by_key(|e| e.index);
+    Ok(extracted)
+}
+
+fn picture_mimetype(codec_name: &str) -> Option<&'static str> {
+    match codec_name {
+        "mjpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "bmp" => Some("image/bmp"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
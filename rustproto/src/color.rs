@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{Rational, VideoStream};
+
+/// Display matrix side data (`Display Matrix`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisplayMatrix {
+    /// Counter-clockwise rotation in degrees, as printed by ffprobe.
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displaymatrix: Option<String>,
+}
+
+/// SMPTE ST 2086 mastering display colour volume. Fields are absent when the
+/// stream only carries primaries or only luminance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MasteringDisplayMetadata {
+    #[serde(flatten)]
+    pub primaries: Option<DisplayPrimaries>,
+    #[serde(flatten)]
+    pub luminance: Option<Luminance>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DisplayPrimaries {
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub red_x: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub red_y: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub green_x: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub green_y: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub blue_x: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub blue_y: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub white_point_x: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub white_point_y: Rational,
+}
+
+/// Mastering display luminance in cd/m².
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Luminance {
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub min_luminance: Rational,
+    #[serde(
+        deserialize_with = "crate::deserialize_rational",
+        serialize_with = "crate::serialize_rational"
+    )]
+    pub max_luminance: Rational,
+}
+
+/// MaxCLL / MaxFALL in cd/m² (`Content light level metadata`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentLightLevel {
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub max_content: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub max_average: i64,
+}
+
+/// Dolby Vision decoder configuration record (`DOVI configuration record`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoviConfiguration {
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub dv_version_major: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub dv_version_minor: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub dv_profile: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub dv_level: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub rpu_present_flag: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub el_present_flag: i64,
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub bl_present_flag: i64,
+    /// Base layer compatibility: 1 HDR10, 2 SDR, 4 HLG, 0 none.
+    #[serde(deserialize_with = "crate::deserialize_i64")]
+    pub dv_bl_signal_compatibility_id: i64,
+    #[serde(flatten)]
+    pub unknown: HashMap<String, Value>,
+}
+
+/// One entry of a stream's `side_data_list`, tagged by `side_data_type`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SideData {
+    DisplayMatrix(DisplayMatrix),
+    MasteringDisplay(MasteringDisplayMetadata),
+    ContentLightLevel(ContentLightLevel),
+    DolbyVision(DoviConfiguration),
+    /// Any other side data, with all of its fields including `side_data_type`.
+    Other(HashMap<String, Value>),
+}
+
+const DISPLAY_MATRIX: &str = "Display Matrix";
+const MASTERING_DISPLAY: &str = "Mastering display metadata";
+const CONTENT_LIGHT_LEVEL: &str = "Content light level metadata";
+const DOVI_CONFIGURATION: &str = "DOVI configuration record";
+const HDR10_PLUS: &str = "HDR10+ Dynamic Metadata (SMPTE 2094-40)";
+
+impl SideData {
+    pub fn side_data_type(&self) -> &str {
+        match self {
+            SideData::DisplayMatrix(_) => DISPLAY_MATRIX,
+            SideData::MasteringDisplay(_) => MASTERING_DISPLAY,
+            SideData::ContentLightLevel(_) => CONTENT_LIGHT_LEVEL,
+            SideData::DolbyVision(_) => DOVI_CONFIGURATION,
+            SideData::Other(map) => map
+                .get("side_data_type")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SideData {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let side_data_type = value
+            .get("side_data_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+        // Typed variants get their tag back from `side_data_type()`.
+        let mut untagged = value.clone();
+        if let Some(obj) = untagged.as_object_mut() {
+            obj.remove("side_data_type");
+        }
+        let typed = match side_data_type.as_str() {
+            DISPLAY_MATRIX => serde_json::from_value(untagged).map(SideData::DisplayMatrix),
+            MASTERING_DISPLAY => serde_json::from_value(untagged).map(SideData::MasteringDisplay),
+            CONTENT_LIGHT_LEVEL => {
+                serde_json::from_value(untagged).map(SideData::ContentLightLevel)
+            }
+            DOVI_CONFIGURATION => serde_json::from_value(untagged).map(SideData::DolbyVision),
+            _ => serde_json::from_value(value.clone()).map(SideData::Other),
+        };
+        // A known type whose fields don't match the model, say from a newer
+        // ffprobe, is kept as it is rather than failing the whole probe.
+        typed
+            .or_else(|e| {
+                serde_json::from_value(value)
+                    .map(SideData::Other)
+                    .map_err(|_| e)
+            })
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for SideData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let value = match self {
+            SideData::DisplayMatrix(v) => serde_json::to_value(v),
+            SideData::MasteringDisplay(v) => serde_json::to_value(v),
+            SideData::ContentLightLevel(v) => serde_json::to_value(v),
+            SideData::DolbyVision(v) => serde_json::to_value(v),
+            SideData::Other(map) => return map.serialize(serializer),
+        };
+        let mut value = value.map_err(serde::ser::Error::custom)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert(
+                "side_data_type".to_string(),
+                Value::String(self.side_data_type().to_string()),
+            );
+        }
+        value.serialize(serializer)
+    }
+}
+
+/// Dynamic range of a video stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+    /// PQ transfer (SMPTE ST 2084) with static metadata.
+    Hdr10,
+    /// PQ with SMPTE 2094-40 dynamic metadata declared on the stream.
+    Hdr10Plus,
+    /// Hybrid log-gamma (ARIB STD-B67).
+    Hlg,
+    /// Dolby Vision. `compatibility_id` tells what the base layer decodes
+    /// as without Dolby Vision support: 1 HDR10, 2 SDR, 4 HLG, 0 nothing.
+    DolbyVision { profile: i64, compatibility_id: i64 },
+}
+
+impl VideoStream {
+    pub fn display_matrix(&self) -> Option<&DisplayMatrix> {
+        self.side_data_list.iter().find_map(|sd| match sd {
+            SideData::DisplayMatrix(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    pub fn mastering_display(&self) -> Option<&MasteringDisplayMetadata> {
+        self.side_data_list.iter().find_map(|sd| match sd {
+            SideData::MasteringDisplay(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    pub fn content_light_level(&self) -> Option<&ContentLightLevel> {
+        self.side_data_list.iter().find_map(|sd| match sd {
+            SideData::ContentLightLevel(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    pub fn dolby_vision(&self) -> Option<&DoviConfiguration> {
+        self.side_data_list.iter().find_map(|sd| match sd {
+            SideData::DolbyVision(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// HDR flavour from the Dolby Vision record and the transfer
+    /// characteristics; `None` for SDR or unknown transfer.
+    pub fn hdr_format(&self) -> Option<HdrFormat> {
+        if let Some(dovi) = self.dolby_vision() {
+            return Some(HdrFormat::DolbyVision {
+                profile: dovi.dv_profile,
+                compatibility_id: dovi.dv_bl_signal_compatibility_id,
+            });
+        }
+        match self.color_transfer.as_deref() {
+            Some("smpte2084") => {
+                let dynamic = self
+                    .side_data_list
+                    .iter()
+                    .any(|sd| sd.side_data_type() == HDR10_PLUS);
+                Some(if dynamic {
+                    HdrFormat::Hdr10Plus
+                } else {
+                    HdrFormat::Hdr10
+                })
+            }
+            Some("arib-std-b67") => Some(HdrFormat::Hlg),
+            _ => None,
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        self.hdr_format().is_some()
+    }
+
+    /// Whether showing the stream on an SDR display needs tone mapping.
+    /// Dolby Vision with an SDR compatible base layer does not.
+    pub fn needs_tone_mapping(&self) -> bool {
+        match self.hdr_format() {
+            Some(HdrFormat::DolbyVision {
+                compatibility_id, ..
+            }) => compatibility_id != 2,
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use std::ffi::CString;
+
+use once_cell::sync::Lazy;
+
+use crate::{AudioStream, FfprobeOutput, Stream, SubtitleStream, VideoStream};
+
+/// Client profile to check playback support against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaybackTarget {
+    /// Safari / AVPlayer playing HLS with fMP4 segments.
+    SafariHls,
+    /// Chromecast default media receiver.
+    Chromecast,
+    /// Android ExoPlayer (Media3) with platform decoders.
+    ExoPlayer,
+    /// Desktop browsers through Media Source Extensions (fMP4 or WebM).
+    Mse,
+}
+
+/// What has to happen to a stream before the target can play it. Ordered
+/// from cheapest to most expensive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PlaybackAction {
+    /// Codec and container are supported as they are.
+    DirectPlay,
+    /// The codec is supported but has to be copied into another container
+    /// or retagged.
+    Remux,
+    /// The stream has to be re-encoded.
+    Transcode,
+    /// The stream cannot be delivered to the target and is left out, e.g.
+    /// bitmap subtitles or cover art.
+    Skip,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamDecision {
+    /// Input stream index.
+    pub index: i64,
+    pub codec_type: String,
+    pub codec_name: String,
+    pub action: PlaybackAction,
+    /// Why the stream cannot be direct played.
+    pub reasons: Vec<String>,
+    /// `run_ffmpeg` arguments for this stream, including its `-map`.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compatibility {
+    pub target: PlaybackTarget,
+    /// Whether the target can play the input container as is.
+    pub container_supported: bool,
+    pub streams: Vec<StreamDecision>,
+}
+
+impl Compatibility {
+    /// The most expensive action any delivered stream needs.
+    pub fn action(&self) -> PlaybackAction {
+        let action = self
+            .streams
+            .iter()
+            .map(|s| s.action)
+            .filter(|a| *a != PlaybackAction::Skip)
+            .max()
+            .unwrap_or(PlaybackAction::DirectPlay);
+        if action == PlaybackAction::DirectPlay && !self.container_supported {
+            PlaybackAction::Remux
+        } else {
+            action
+        }
+    }
+
+    pub fn can_direct_play(&self) -> bool {
+        self.action() == PlaybackAction::DirectPlay
+    }
+
+    /// Stream arguments for `run_ffmpeg`, to be placed between `-i {input}`
+    /// and the muxer options.
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        self.streams
+            .iter()
+            .filter(|s| s.action != PlaybackAction::Skip)
+            .flat_map(|s| s.args.iter().cloned())
+            .collect()
+    }
+}
+
+impl FfprobeOutput {
+    /// Decide per stream whether `target` can play it directly, after a
+    /// remux, or only after a transcode.
+    pub fn compatibility(&self, target: PlaybackTarget) -> Compatibility {
+        let container_supported = container_supported(target, &self.format.format_name, self);
+        let mut outputs = OutputCounter::default();
+        let streams = self
+            .streams
+            .iter()
+            .map(|stream| {
+                let mut decision = match stream {
+                    Stream::Video(v) => check_video(target, v),
+                    Stream::Audio(a) => check_audio(target, a),
+                    Stream::Subtitle(s) => check_subtitle(target, s),
+                    Stream::Attachment(_) | Stream::Other(_) => Decision::skip(format!(
+                        "{} streams are not delivered",
+                        stream.common().codec_type
+                    )),
+                };
+                if decision.action == PlaybackAction::DirectPlay && !container_supported {
+                    decision.action = PlaybackAction::Remux;
+                }
+                let common = stream.common();
+                let args = match decision.action {
+                    PlaybackAction::Skip => Vec::new(),
+                    _ => outputs.args(common.index, &common.codec_type, &decision.codec_args),
+                };
+                StreamDecision {
+                    index: common.index,
+                    codec_type: common.codec_type.clone(),
+                    codec_name: common.codec_name.clone(),
+                    action: decision.action,
+                    reasons: decision.reasons,
+                    args,
+                }
+            })
+            .collect();
+        Compatibility {
+            target,
+            container_supported,
+            streams,
+        }
+    }
+}
+
+impl VideoStream {
+    /// Bits per component, from the pixel format.
+    pub fn bit_depth(&self) -> u32 {
+        pix_fmt_bit_depth(&self.pix_fmt)
+    }
+}
+
+fn pix_fmt_bit_depth(pix_fmt: &str) -> u32 {
+    let name = pix_fmt
+        .strip_suffix("le")
+        .or_else(|| pix_fmt.strip_suffix("be"))
+        .unwrap_or(pix_fmt);
+    // p010 / p016 semi-planar formats.
+    if let Some(depth) = name.strip_prefix("p0") {
+        return depth.parse().unwrap_or(8);
+    }
+    let digits = name
+        .chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    let (head, depth) = name.split_at(name.len() - digits);
+    match depth.parse::<u32>() {
+        Ok(depth) if head.ends_with('p') && (9..=16).contains(&depth) => depth,
+        _ => 8,
+    }
+}
+
+struct Decision {
+    action: PlaybackAction,
+    reasons: Vec<String>,
+    /// Codec options, with `{spec}` standing for the output stream specifier.
+    codec_args: Vec<String>,
+}
+
+impl Decision {
+    fn copy() -> Self {
+        Self {
+            action: PlaybackAction::DirectPlay,
+            reasons: Vec::new(),
+            codec_args: vec!["-c:{spec}".to_string(), "copy".to_string()],
+        }
+    }
+
+    fn skip(reason: String) -> Self {
+        Self {
+            action: PlaybackAction::Skip,
+            reasons: vec![reason],
+            codec_args: Vec::new(),
+        }
+    }
+
+    fn transcode(reasons: Vec<String>, codec_args: &[&str]) -> Self {
+        Self {
+            action: PlaybackAction::Transcode,
+            reasons,
+            codec_args: codec_args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// Numbers output streams per type so the codec options can use stream
+/// specifiers such as `-c:v:0`.
+#[derive(Default)]
+struct OutputCounter {
+    video: usize,
+    audio: usize,
+    subtitle: usize,
+}
+
+impl OutputCounter {
+    fn args(&mut self, index: i64, codec_type: &str, codec_args: &[String]) -> Vec<String> {
+        let (kind, counter) = match codec_type {
+            "video" => ("v", &mut self.video),
+            "audio" => ("a", &mut self.audio),
+            _ => ("s", &mut self.subtitle),
+        };
+        let spec = format!("{}:{}", kind, counter);
+        *counter += 1;
+        let mut args = vec!["-map".to_string(), format!("0:{}", index)];
+        args.extend(codec_args.iter().map(|a| a.replace("{spec}", &spec)));
+        args
+    }
+}
+
+fn container_supported(target: PlaybackTarget, format_name: &str, probe: &FfprobeOutput) -> bool {
+    let names: Vec<&str> = format_name.split(',').collect();
+    let has = |name: &str| names.contains(&name);
+    let is_mp4 = has("mp4") || has("mov");
+    // The matroska demuxer reports "matroska,webm" for both.
+    let is_webm = has("webm")
+        && probe.streams.iter().all(|s| match s {
+            Stream::Video(v) => matches!(v.common.codec_name.as_str(), "vp8" | "vp9" | "av1"),
+            Stream::Audio(a) => matches!(a.common.codec_name.as_str(), "opus" | "vorbis"),
+            Stream::Subtitle(s) => s.common.codec_name == "webvtt",
+            Stream::Attachment(_) | Stream::Other(_) => false,
+        });
+    match target {
+        PlaybackTarget::SafariHls => is_mp4 || has("hls") || has("mpegts"),
+        PlaybackTarget::Chromecast => is_mp4 || is_webm || has("hls") || has("mpegts"),
+        PlaybackTarget::ExoPlayer => {
+            is_mp4
+                || has("matroska")
+                || has("mpegts")
+                || has("hls")
+                || has("ogg")
+                || has("flv")
+                || has("mp3")
+                || has("flac")
+                || has("wav")
+        }
+        PlaybackTarget::Mse => is_mp4 || is_webm,
+    }
+}
+
+/// Whether this build of libavcodec has an encoder called `name`, such as
+/// `libx264`.
+pub fn encoder_available(name: &str) -> bool {
+    let Ok(name) = CString::new(name) else {
+        return false;
+    };
+    !unsafe { crate::avcodec_find_encoder_by_name(name.as_ptr()) }.is_null()
+}
+
+/// H.264 encoders in order of preference. Hardware encoders come last since
+/// they also need a device at run time.
+const H264_ENCODERS: &[&str] = &[
+    "libx264",
+    "libopenh264",
+    "h264_videotoolbox",
+    "h264_mf",
+    "h264_nvenc",
+    "h264_qsv",
+    "h264_amf",
+    "h264_v4l2m2m",
+];
+
+static H264_ENCODER: Lazy<Option<&'static str>> =
+    Lazy::new(|| H264_ENCODERS.iter().copied().find(|e| encoder_available(e)));
+
+fn h264_transcode(mut reasons: Vec<String>) -> Decision {
+    let args: &[&str] = match *H264_ENCODER {
+        Some("libx264") => &[
+            "-c:{spec}",
+            "libx264",
+            "-profile:{spec}",
+            "high",
+            "-level:{spec}",
+            "4.1",
+            "-pix_fmt:{spec}",
+            "yuv420p",
+        ],
+        Some(encoder) => &["-c:{spec}", encoder, "-pix_fmt:{spec}", "yuv420p"],
+        None => {
+            // ffmpeg reports the missing encoder when the run starts.
+            reasons.push("no H.264 encoder in this build".to_string());
+            &["-c:{spec}", "h264", "-pix_fmt:{spec}", "yuv420p"]
+        }
+    };
+    Decision::transcode(reasons, args)
+}
+
+fn check_video(target: PlaybackTarget, v: &VideoStream) -> Decision {
+    if v.common.disposition_flags().attached_pic {
+        return Decision::skip("attached picture".to_string());
+    }
+    let codec = v.common.codec_name.as_str();
+    let profile = v.common.profile.to_ascii_lowercase();
+    let depth = v.bit_depth();
+    let chroma_420 = ["yuv420p", "yuvj420p", "nv12", "p010"]
+        .iter()
+        .any(|p| v.pix_fmt.starts_with(p));
+    let mut reasons = Vec::new();
+
+    let supported = match (target, codec) {
+        (_, "h264") => {
+            let max_level = match target {
+                PlaybackTarget::Chromecast => 42,
+                _ => 52,
+            };
+            if !matches!(
+                profile.as_str(),
+                "baseline" | "constrained baseline" | "main" | "high"
+            ) {
+                reasons.push(format!(
+                    "h264 profile {} is not supported",
+                    v.common.profile
+                ));
+            }
+            if v.level > max_level {
+                reasons.push(format!("h264 level {} exceeds {}", v.level, max_level));
+            }
+            if depth != 8 || !chroma_420 {
+                reasons.push(format!("h264 pixel format {} is not supported", v.pix_fmt));
+            }
+            reasons.is_empty()
+        }
+        (
+            PlaybackTarget::SafariHls | PlaybackTarget::Chromecast | PlaybackTarget::ExoPlayer,
+            "hevc",
+        ) => {
+            if !matches!(profile.as_str(), "main" | "main 10") {
+                reasons.push(format!(
+                    "hevc profile {} is not supported",
+                    v.common.profile
+                ));
+            }
+            // HEVC levels are stored as level * 30.
+            if v.level > 153 {
+                reasons.push(format!("hevc level {} exceeds 5.1", v.level as f64 / 30.0));
+            }
+            if depth > 10 || !chroma_420 {
+                reasons.push(format!("hevc pixel format {} is not supported", v.pix_fmt));
+            }
+            reasons.is_empty()
+        }
+        (PlaybackTarget::Chromecast | PlaybackTarget::ExoPlayer | PlaybackTarget::Mse, "vp8") => {
+            true
+        }
+        (PlaybackTarget::Chromecast | PlaybackTarget::ExoPlayer | PlaybackTarget::Mse, "vp9") => {
+            if depth > 10 {
+                reasons.push(format!("vp9 pixel format {} is not supported", v.pix_fmt));
+            }
+            reasons.is_empty()
+        }
+        (PlaybackTarget::ExoPlayer | PlaybackTarget::Mse, "av1") => {
+            if depth > 10 {
+                reasons.push(format!("av1 pixel format {} is not supported", v.pix_fmt));
+            }
+            reasons.is_empty()
+        }
+        (PlaybackTarget::ExoPlayer, "mpeg4") => true,
+        _ => {
+            reasons.push(format!("video codec {} is not supported", codec));
+            false
+        }
+    };
+    if !supported {
+        if v.needs_tone_mapping() {
+            reasons.push("HDR source needs tone mapping to SDR".to_string());
+        }
+        return h264_transcode(reasons);
+    }
+
+    let mut decision = Decision::copy();
+    if target == PlaybackTarget::SafariHls && codec == "hevc" && v.common.codec_tag_string != "hvc1"
+    {
+        // Apple players only accept the hvc1 sample entry.
+        decision.action = PlaybackAction::Remux;
+        decision.reasons.push(format!(
+            "hevc tag {} has to be hvc1",
+            v.common.codec_tag_string
+        ));
+        decision
+            .codec_args
+            .extend(["-tag:{spec}".to_string(), "hvc1".to_string()]);
+    }
+    decision
+}
+
+fn check_audio(target: PlaybackTarget, a: &AudioStream) -> Decision {
+    let codec = a.common.codec_name.as_str();
+    let supported = match target {
+        PlaybackTarget::SafariHls => matches!(
+            codec,
+            "aac" | "ac3" | "eac3" | "mp3" | "alac" | "flac" | "opus"
+        ),
+        PlaybackTarget::Chromecast => matches!(
+            codec,
+            "aac" | "mp3" | "opus" | "vorbis" | "flac" | "ac3" | "eac3"
+        ),
+        PlaybackTarget::ExoPlayer => matches!(
+            codec,
+            "aac"
+                | "mp3"
+                | "opus"
+                | "vorbis"
+                | "flac"
+                | "alac"
+                | "ac3"
+                | "eac3"
+                | "pcm_s16le"
+                | "pcm_s24le"
+        ),
+        PlaybackTarget::Mse => matches!(codec, "aac" | "mp3" | "opus" | "vorbis" | "flac"),
+    };
+    let max_channels = match (target, codec) {
+        (PlaybackTarget::Mse, _)
+        | (PlaybackTarget::Chromecast, "aac" | "mp3" | "opus" | "vorbis") => 2,
+        _ => 8,
+    };
+
+    let mut reasons = Vec::new();
+    if !supported {
+        reasons.push(format!("audio codec {} is not supported", codec));
+    }
+    if a.channels > max_channels {
+        reasons.push(format!("{} channels exceed {}", a.channels, max_channels));
+    }
+    if reasons.is_empty() {
+        return Decision::copy();
+    }
+    let channels = a.channels.clamp(1, 2).to_string();
+    Decision::transcode(
+        reasons,
+        &[
+            "-c:{spec}",
+            "aac",
+            "-b:{spec}",
+            "192k",
+            "-ac:{spec}",
+            channels.as_str(),
+        ],
+    )
+}
+
+fn check_subtitle(target: PlaybackTarget, s: &SubtitleStream) -> Decision {
+    let codec = s.common.codec_name.as_str();
+    let supported = match target {
+        PlaybackTarget::ExoPlayer => matches!(
+            codec,
+            "subrip"
+                | "srt"
+                | "ass"
+                | "ssa"
+                | "webvtt"
+                | "mov_text"
+                | "hdmv_pgs_subtitle"
+                | "dvb_subtitle"
+                | "dvd_subtitle"
+        ),
+        _ => codec == "webvtt",
+    };
+    if supported {
+        Decision::copy()
+    } else if s.can_convert_to_webvtt() {
+        Decision::transcode(
+            vec![format!("subtitle codec {} is not supported", codec)],
+            &["-c:{spec}", "webvtt"],
+        )
+    } else if s.requires_burn_in() {
+        Decision::skip(format!("bitmap subtitle codec {} is not supported", codec))
+    } else {
+        Decision::skip(format!("subtitle codec {} cannot be decoded", codec))
+    }
+}
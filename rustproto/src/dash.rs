@@ -0,0 +1,912 @@
+//! DASH manifest (MPD) model with parsing and serialization.
+//!
+//! Models the parts of the MPD schema ffmpeg's DASH muxer writes: Periods,
+//! AdaptationSets, Representations and `SegmentTemplate` with an optional
+//! `SegmentTimeline`. Attributes and child elements that are not modelled are
+//! kept so a parse/serialize round trip does not drop them.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fmt;
+use std::time::Duration;
+
+/// Generic XML element, used for children that are not modelled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: Option<String>,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mpd {
+    /// `static` or `dynamic`.
+    pub mpd_type: Option<String>,
+    pub profiles: Option<String>,
+    pub media_presentation_duration: Option<Duration>,
+    pub min_buffer_time: Option<Duration>,
+    pub minimum_update_period: Option<Duration>,
+    pub availability_start_time: Option<String>,
+    pub publish_time: Option<String>,
+    pub base_urls: Vec<String>,
+    pub periods: Vec<Period>,
+    /// Unmodelled attributes, including namespace declarations.
+    pub other_attributes: Vec<(String, String)>,
+    /// Unmodelled children that precede the first Period.
+    pub other_elements: Vec<Element>,
+    /// Unmodelled children that follow the Periods (e.g. `UTCTiming`).
+    pub trailing_elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Period {
+    pub id: Option<String>,
+    pub start: Option<Duration>,
+    pub duration: Option<Duration>,
+    pub base_urls: Vec<String>,
+    pub adaptation_sets: Vec<AdaptationSet>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdaptationSet {
+    pub id: Option<String>,
+    pub content_type: Option<String>,
+    pub mime_type: Option<String>,
+    pub codecs: Option<String>,
+    pub lang: Option<String>,
+    pub segment_template: Option<SegmentTemplate>,
+    pub representations: Vec<Representation>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Representation {
+    pub id: String,
+    pub bandwidth: Option<u64>,
+    pub mime_type: Option<String>,
+    pub codecs: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// As written, e.g. `24000/1001`.
+    pub frame_rate: Option<String>,
+    pub audio_sampling_rate: Option<u32>,
+    pub base_urls: Vec<String>,
+    pub segment_template: Option<SegmentTemplate>,
+    pub other_attributes: Vec<(String, String)>,
+    pub other_elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SegmentTemplate {
+    pub timescale: Option<u64>,
+    pub duration: Option<u64>,
+    pub start_number: Option<u64>,
+    pub presentation_time_offset: Option<u64>,
+    pub initialization: Option<String>,
+    pub media: Option<String>,
+    pub timeline: Option<Vec<TimelineEntry>>,
+    pub other_attributes: Vec<(String, String)>,
+}
+
+impl SegmentTemplate {
+    /// Fill unset fields from `parent`, the AdaptationSet-level template.
+    fn inherit(&self, parent: &SegmentTemplate) -> SegmentTemplate {
+        SegmentTemplate {
+            timescale: self.timescale.or(parent.timescale),
+            duration: self.duration.or(parent.duration),
+            start_number: self.start_number.or(parent.start_number),
+            presentation_time_offset: self
+                .presentation_time_offset
+                .or(parent.presentation_time_offset),
+            initialization: self
+                .initialization
+                .clone()
+                .or_else(|| parent.initialization.clone()),
+            media: self.media.clone().or_else(|| parent.media.clone()),
+            timeline: self.timeline.clone().or_else(|| parent.timeline.clone()),
+            other_attributes: self.other_attributes.clone(),
+        }
+    }
+}
+
+/// `S` element of a `SegmentTimeline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineEntry {
+    pub t: Option<u64>,
+    pub d: u64,
+    /// Repeat count; `-1` repeats until the next entry or the period end.
+    pub r: i64,
+}
+
+/// Values substituted into `SegmentTemplate` identifiers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateVars<'a> {
+    pub representation_id: &'a str,
+    pub number: Option<u64>,
+    pub time: Option<u64>,
+    pub bandwidth: Option<u64>,
+}
+
+/// Expand `$RepresentationID$`, `$Number$`, `$Time$` and `$Bandwidth$`
+/// (with optional `%0Nd` width) and `$$` in a template.
+pub fn expand_template(template: &str, vars: &TemplateVars<'_>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('$') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let ident = &after[..end];
+        let (name, width) = match ident.split_once('%') {
+            Some((name, fmt)) => (name, parse_width(fmt)),
+            None => (ident, None),
+        };
+        let value = match name {
+            "" => Some("$".to_string()),
+            "RepresentationID" => Some(vars.representation_id.to_string()),
+            "Number" => vars.number.map(|v| pad(v, width)),
+            "Time" => vars.time.map(|v| pad(v, width)),
+            "Bandwidth" => vars.bandwidth.map(|v| pad(v, width)),
+            _ => None,
+        };
+        match value {
+            Some(v) => out.push_str(&v),
+            None => {
+                out.push('$');
+                out.push_str(ident);
+                out.push('$');
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn parse_width(fmt: &str) -> Option<usize> {
+    fmt.strip_suffix('d')?.trim_start_matches('0').parse().ok()
+}
+
+fn pad(v: u64, width: Option<usize>) -> String {
+    match width {
+        Some(w) => format!("{:0w$}", v, w = w),
+        None => v.to_string(),
+    }
+}
+
+/// A segment resolved from the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentRef {
+    pub period_index: usize,
+    pub representation_id: String,
+    pub url: String,
+    /// `None` for initialization segments.
+    pub number: Option<u64>,
+    /// Start and end in seconds, relative to the period start.
+    pub start: f64,
+    pub end: f64,
+}
+
+impl SegmentRef {
+    pub fn is_init(&self) -> bool {
+        self.number.is_none()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+impl Mpd {
+    pub fn parse(text: &str) -> Result<Self, DashError> {
+        let root = parse_document(text)?;
+        if root.name != "MPD" {
+            return Err(DashError::new(format!(
+                "expected <MPD> root element, got <{}>",
+                root.name
+            )));
+        }
+        mpd_from_element(root)
+    }
+
+    /// Every initialization and media segment URL with its time range.
+    ///
+    /// Segments of a `dynamic` MPD whose timeline ends in `r="-1"` are only
+    /// enumerated up to the period duration, if one is known. Fails if the
+    /// templates describe more than `MAX_SEGMENTS` segments or times
+    /// overflow.
+    pub fn segments(&self) -> Result<Vec<SegmentRef>, DashError> {
+        let mut out = Vec::new();
+        for (period_index, period) in self.periods.iter().enumerate() {
+            let period_duration = match period.duration {
+                Some(d) => Some(d),
+                None if self.periods.len() == 1 => self.media_presentation_duration,
+                None => None,
+            };
+            for set in &period.adaptation_sets {
+                for rep in &set.representations {
+                    let template = match (&rep.segment_template, &set.segment_template) {
+                        (Some(own), Some(parent)) => own.inherit(parent),
+                        (Some(own), None) => own.clone(),
+                        (None, Some(parent)) => parent.clone(),
+                        (None, None) => continue,
+                    };
+                    let base = rep
+                        .base_urls
+                        .first()
+                        .or(period.base_urls.first())
+                        .or(self.base_urls.first())
+                        .map(|s| s.as_str())
+                        .unwrap_or("");
+                    push_template_segments(
+                        &mut out,
+                        period_index,
+                        rep,
+                        &template,
+                        base,
+                        period_duration,
+                    )?;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn representation(&self, id: &str) -> Option<&Representation> {
+        self.periods
+            .iter()
+            .flat_map(|p| &p.adaptation_sets)
+            .flat_map(|a| &a.representations)
+            .find(|r| r.id == id)
+    }
+}
+
+/// Upper bound on the segments `Mpd::segments` enumerates, so a hostile or
+/// broken timeline (a huge `r`, or `r="-1"` with a long period) can't
+/// exhaust memory.
+pub const MAX_SEGMENTS: usize = 1_000_000;
+
+fn push_template_segments(
+    out: &mut Vec<SegmentRef>,
+    period_index: usize,
+    rep: &Representation,
+    template: &SegmentTemplate,
+    base: &str,
+    period_duration: Option<Duration>,
+) -> Result<(), DashError> {
+    let timescale = template.timescale.unwrap_or(1).max(1) as f64;
+    let offset = template.presentation_time_offset.unwrap_or(0);
+    let mut vars = TemplateVars {
+        representation_id: &rep.id,
+        number: None,
+        time: None,
+        bandwidth: rep.bandwidth,
+    };
+
+    if let Some(init) = &template.initialization {
+        out.push(SegmentRef {
+            period_index,
+            representation_id: rep.id.clone(),
+            url: join_url(base, &expand_template(init, &vars)),
+            number: None,
+            start: 0.0,
+            end: 0.0,
+        });
+    }
+
+    let Some(media) = &template.media else {
+        return Ok(());
+    };
+    let mut number = template.start_number.unwrap_or(1);
+    let period_end = period_duration.map(|d| d.as_secs_f64() * timescale + offset as f64);
+    let overflow = || {
+        DashError::new(format!(
+            "segment times of representation {} overflow",
+            rep.id
+        ))
+    };
+    let too_many = || DashError::new(format!("more than {} segments", MAX_SEGMENTS));
+
+    // Returns the time and number of the next segment.
+    let mut push = |time: u64, duration: u64, number: u64| -> Result<(u64, u64), DashError> {
+        if out.len() >= MAX_SEGMENTS {
+            return Err(too_many());
+        }
+        vars.number = Some(number);
+        vars.time = Some(time);
+        let start = time.saturating_sub(offset) as f64 / timescale;
+        out.push(SegmentRef {
+            period_index,
+            representation_id: rep.id.clone(),
+            url: join_url(base, &expand_template(media, &vars)),
+            number: Some(number),
+            start,
+            end: start + duration as f64 / timescale,
+        });
+        let next_time = time.checked_add(duration).ok_or_else(overflow)?;
+        let next_number = number.checked_add(1).ok_or_else(overflow)?;
+        Ok((next_time, next_number))
+    };
+
+    if let Some(timeline) = &template.timeline {
+        let mut time = offset;
+        for (i, entry) in timeline.iter().enumerate() {
+            if let Some(t) = entry.t {
+                time = t;
+            }
+            let repeats = if entry.r >= 0 {
+                entry.r as u64
+            } else {
+                // Repeat until the next explicit start time or the period end.
+                let until = timeline
+                    .get(i + 1)
+                    .and_then(|next| next.t.map(|t| t as f64))
+                    .or(period_end);
+                match until {
+                    Some(until) if entry.d > 0 => {
+                        (((until - time as f64) / entry.d as f64).ceil() as u64).saturating_sub(1)
+                    }
+                    _ => 0,
+                }
+            };
+            if repeats >= MAX_SEGMENTS as u64 {
+                return Err(too_many());
+            }
+            for _ in 0..=repeats {
+                (time, number) = push(time, entry.d, number)?;
+            }
+        }
+    } else if let (Some(duration), Some(end)) = (template.duration, period_end) {
+        if duration == 0 {
+            return Ok(());
+        }
+        let mut time = offset;
+        while (time as f64) < end {
+            (time, number) = push(time, duration, number)?;
+        }
+    }
+    Ok(())
+}
+
+fn join_url(base: &str, url: &str) -> String {
+    if base.is_empty() || url.contains("://") || url.starts_with('/') {
+        return url.to_string();
+    }
+    if base.ends_with('/') {
+        format!("{}{}", base, url)
+    } else {
+        // A base without a trailing slash names a document; resolve against its directory.
+        match base.rfind('/') {
+            Some(pos) => format!("{}{}", &base[..=pos], url),
+            None => url.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DashError {
+    pub message: String,
+}
+
+impl DashError {
+    fn new<M: Into<String>>(message: M) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DashError {}
+
+/// Parse an `xs:duration` such as `PT1M34.5S` or `P0Y0M0DT0H3M30.000S`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let rest = s.trim().strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((d, t)) => (d, t),
+        None => (rest, ""),
+    };
+    let mut secs = 0.0f64;
+    let mut take = |part: &str, units: &[(char, f64)]| -> Option<()> {
+        let mut num = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                num.push(c);
+                continue;
+            }
+            let factor = units.iter().find(|(u, _)| *u == c)?.1;
+            secs += num.parse::<f64>().ok()? * factor;
+            num.clear();
+        }
+        num.is_empty().then_some(())
+    };
+    take(
+        date,
+        &[
+            ('Y', 365.0 * 86400.0),
+            ('M', 30.0 * 86400.0),
+            ('D', 86400.0),
+        ],
+    )?;
+    take(time, &[('H', 3600.0), ('M', 60.0), ('S', 1.0)])?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// Format an `xs:duration` the way ffmpeg's DASH muxer does (`PT0H1M34.500S`).
+pub fn format_duration(d: Duration) -> String {
+    let total = d.as_secs_f64();
+    let hours = (total / 3600.0).floor();
+    let minutes = ((total - hours * 3600.0) / 60.0).floor();
+    let seconds = total - hours * 3600.0 - minutes * 60.0;
+    format!("PT{}H{}M{:.3}S", hours as u64, minutes as u64, seconds)
+}
+
+fn parse_document(text: &str) -> Result<Element, DashError> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            DashError::new(format!("xml error at {}: {e}", reader.buffer_position()))
+        })?;
+        match event {
+            Event::Start(e) => stack.push(element_from_start(&e)?),
+            Event::Empty(e) => {
+                let element = element_from_start(&e)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| DashError::new("unbalanced closing tag"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(t) => {
+                let text = t
+                    .unescape()
+                    .map_err(|e| DashError::new(format!("xml text: {e}")))?;
+                if let Some(current) = stack.last_mut() {
+                    current.text = Some(text.into_owned());
+                }
+            }
+            Event::CData(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text = Some(String::from_utf8_lossy(&t).into_owned());
+                }
+            }
+            Event::Eof => return Err(DashError::new("unexpected end of document")),
+            _ => {}
+        }
+    }
+}
+
+fn element_from_start(e: &quick_xml::events::BytesStart<'_>) -> Result<Element, DashError> {
+    let mut element = Element {
+        name: String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+        ..Element::default()
+    };
+    for attr in e.attributes() {
+        let attr = attr.map_err(|e| DashError::new(format!("xml attribute: {e}")))?;
+        let value = attr
+            .unescape_value()
+            .map_err(|e| DashError::new(format!("xml attribute: {e}")))?;
+        element.attributes.push((
+            String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+            value.into_owned(),
+        ));
+    }
+    Ok(element)
+}
+
+/// Removes known attributes from an element as they are consumed, leaving
+/// the rest for `other_attributes`.
+struct Attrs(Vec<(String, String)>);
+
+impl Attrs {
+    fn take(&mut self, name: &str) -> Option<String> {
+        let pos = self.0.iter().position(|(k, _)| k == name)?;
+        Some(self.0.remove(pos).1)
+    }
+
+    fn take_num<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, DashError> {
+        match self.take(name) {
+            Some(v) => v
+                .trim()
+                .parse::<T>()
+                .map(Some)
+                .map_err(|_| DashError::new(format!("invalid {name}: {v}"))),
+            None => Ok(None),
+        }
+    }
+
+    fn take_duration(&mut self, name: &str) -> Result<Option<Duration>, DashError> {
+        match self.take(name) {
+            Some(v) => parse_duration(&v)
+                .map(Some)
+                .ok_or_else(|| DashError::new(format!("invalid {name}: {v}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+fn mpd_from_element(el: Element) -> Result<Mpd, DashError> {
+    let mut attrs = Attrs(el.attributes);
+    let mut mpd = Mpd {
+        mpd_type: attrs.take("type"),
+        profiles: attrs.take("profiles"),
+        media_presentation_duration: attrs.take_duration("mediaPresentationDuration")?,
+        min_buffer_time: attrs.take_duration("minBufferTime")?,
+        minimum_update_period: attrs.take_duration("minimumUpdatePeriod")?,
+        availability_start_time: attrs.take("availabilityStartTime"),
+        publish_time: attrs.take("publishTime"),
+        ..Mpd::default()
+    };
+    mpd.other_attributes = attrs.0;
+    for child in el.children {
+        match child.name.as_str() {
+            "BaseURL" => mpd.base_urls.push(child.text.unwrap_or_default()),
+            "Period" => mpd.periods.push(period_from_element(child)?),
+            _ if mpd.periods.is_empty() => mpd.other_elements.push(child),
+            _ => mpd.trailing_elements.push(child),
+        }
+    }
+    Ok(mpd)
+}
+
+fn period_from_element(el: Element) -> Result<Period, DashError> {
+    let mut attrs = Attrs(el.attributes);
+    let mut period = Period {
+        id: attrs.take("id"),
+        start: attrs.take_duration("start")?,
+        duration: attrs.take_duration("duration")?,
+        ..Period::default()
+    };
+    period.other_attributes = attrs.0;
+    for child in el.children {
+        match child.name.as_str() {
+            "BaseURL" => period.base_urls.push(child.text.unwrap_or_default()),
+            "AdaptationSet" => period
+                .adaptation_sets
+                .push(adaptation_set_from_element(child)?),
+            _ => period.other_elements.push(child),
+        }
+    }
+    Ok(period)
+}
+
+fn adaptation_set_from_element(el: Element) -> Result<AdaptationSet, DashError> {
+    let mut attrs = Attrs(el.attributes);
+    let mut set = AdaptationSet {
+        id: attrs.take("id"),
+        content_type: attrs.take("contentType"),
+        mime_type: attrs.take("mimeType"),
+        codecs: attrs.take("codecs"),
+        lang: attrs.take("lang"),
+        ..AdaptationSet::default()
+    };
+    set.other_attributes = attrs.0;
+    for child in el.children {
+        match child.name.as_str() {
+            "SegmentTemplate" => set.segment_template = Some(template_from_element(child)?),
+            "Representation" => set
+                .representations
+                .push(representation_from_element(child)?),
+            _ => set.other_elements.push(child),
+        }
+    }
+    Ok(set)
+}
+
+fn representation_from_element(el: Element) -> Result<Representation, DashError> {
+    let mut attrs = Attrs(el.attributes);
+    let mut rep = Representation {
+        id: attrs
+            .take("id")
+            .ok_or_else(|| DashError::new("Representation without id"))?,
+        bandwidth: attrs.take_num("bandwidth")?,
+        mime_type: attrs.take("mimeType"),
+        codecs: attrs.take("codecs"),
+        width: attrs.take_num("width")?,
+        height: attrs.take_num("height")?,
+        frame_rate: attrs.take("frameRate"),
+        audio_sampling_rate: attrs.take_num("audioSamplingRate")?,
+        ..Representation::default()
+    };
+    rep.other_attributes = attrs.0;
+    for child in el.children {
+        match child.name.as_str() {
+            "BaseURL" => rep.base_urls.push(child.text.unwrap_or_default()),
+            "SegmentTemplate" => rep.segment_template = Some(template_from_element(child)?),
+            _ => rep.other_elements.push(child),
+        }
+    }
+    Ok(rep)
+}
+
+fn template_from_element(el: Element) -> Result<SegmentTemplate, DashError> {
+    let mut attrs = Attrs(el.attributes);
+    let mut template = SegmentTemplate {
+        timescale: attrs.take_num("timescale")?,
+        duration: attrs.take_num("duration")?,
+        start_number: attrs.take_num("startNumber")?,
+        presentation_time_offset: attrs.take_num("presentationTimeOffset")?,
+        initialization: attrs.take("initialization"),
+        media: attrs.take("media"),
+        ..SegmentTemplate::default()
+    };
+    template.other_attributes = attrs.0;
+    for child in el.children {
+        if child.name == "SegmentTimeline" {
+            let mut entries = Vec::new();
+            for s in child.children.into_iter().filter(|c| c.name == "S") {
+                let mut attrs = Attrs(s.attributes);
+                entries.push(TimelineEntry {
+                    t: attrs.take_num("t")?,
+                    d: attrs
+                        .take_num("d")?
+                        .ok_or_else(|| DashError::new("SegmentTimeline S without d"))?,
+                    r: attrs.take_num("r")?.unwrap_or(0),
+                });
+            }
+            template.timeline = Some(entries);
+        }
+    }
+    Ok(template)
+}
+
+fn push_attr<V: ToString>(attrs: &mut Vec<(String, String)>, name: &str, value: Option<V>) {
+    if let Some(v) = value {
+        attrs.push((name.to_string(), v.to_string()));
+    }
+}
+
+fn text_element(name: &str, text: &str) -> Element {
+    Element {
+        name: name.to_string(),
+        text: Some(text.to_string()),
+        ..Element::default()
+    }
+}
+
+impl From<&Mpd> for Element {
+    fn from(mpd: &Mpd) -> Self {
+        let mut attributes = Vec::new();
+        // Namespace declarations conventionally come first.
+        attributes.extend(
+            mpd.other_attributes
+                .iter()
+                .filter(|(k, _)| k.starts_with("xmlns"))
+                .cloned(),
+        );
+        push_attr(&mut attributes, "profiles", mpd.profiles.as_ref());
+        push_attr(&mut attributes, "type", mpd.mpd_type.as_ref());
+        push_attr(
+            &mut attributes,
+            "mediaPresentationDuration",
+            mpd.media_presentation_duration.map(format_duration),
+        );
+        push_attr(
+            &mut attributes,
+            "minimumUpdatePeriod",
+            mpd.minimum_update_period.map(format_duration),
+        );
+        push_attr(
+            &mut attributes,
+            "availabilityStartTime",
+            mpd.availability_start_time.as_ref(),
+        );
+        push_attr(&mut attributes, "publishTime", mpd.publish_time.as_ref());
+        push_attr(
+            &mut attributes,
+            "minBufferTime",
+            mpd.min_buffer_time.map(format_duration),
+        );
+        attributes.extend(
+            mpd.other_attributes
+                .iter()
+                .filter(|(k, _)| !k.starts_with("xmlns"))
+                .cloned(),
+        );
+        let mut children = mpd.other_elements.clone();
+        children.extend(mpd.base_urls.iter().map(|u| text_element("BaseURL", u)));
+        children.extend(mpd.periods.iter().map(Element::from));
+        children.extend(mpd.trailing_elements.iter().cloned());
+        Element {
+            name: "MPD".to_string(),
+            attributes,
+            children,
+            text: None,
+        }
+    }
+}
+
+impl From<&Period> for Element {
+    fn from(period: &Period) -> Self {
+        let mut attributes = Vec::new();
+        push_attr(&mut attributes, "id", period.id.as_ref());
+        push_attr(&mut attributes, "start", period.start.map(format_duration));
+        push_attr(
+            &mut attributes,
+            "duration",
+            period.duration.map(format_duration),
+        );
+        attributes.extend(period.other_attributes.iter().cloned());
+        let mut children: Vec<Element> = period
+            .base_urls
+            .iter()
+            .map(|u| text_element("BaseURL", u))
+            .collect();
+        children.extend(period.other_elements.iter().cloned());
+        children.extend(period.adaptation_sets.iter().map(Element::from));
+        Element {
+            name: "Period".to_string(),
+            attributes,
+            children,
+            text: None,
+        }
+    }
+}
+
+impl From<&AdaptationSet> for Element {
+    fn from(set: &AdaptationSet) -> Self {
+        let mut attributes = Vec::new();
+        push_attr(&mut attributes, "id", set.id.as_ref());
+        push_attr(&mut attributes, "contentType", set.content_type.as_ref());
+        push_attr(&mut attributes, "mimeType", set.mime_type.as_ref());
+        push_attr(&mut attributes, "codecs", set.codecs.as_ref());
+        push_attr(&mut attributes, "lang", set.lang.as_ref());
+        attributes.extend(set.other_attributes.iter().cloned());
+        let mut children = set.other_elements.clone();
+        children.extend(set.segment_template.iter().map(Element::from));
+        children.extend(set.representations.iter().map(Element::from));
+        Element {
+            name: "AdaptationSet".to_string(),
+            attributes,
+            children,
+            text: None,
+        }
+    }
+}
+
+impl From<&Representation> for Element {
+    fn from(rep: &Representation) -> Self {
+        let mut attributes = vec![("id".to_string(), rep.id.clone())];
+        push_attr(&mut attributes, "mimeType", rep.mime_type.as_ref());
+        push_attr(&mut attributes, "codecs", rep.codecs.as_ref());
+        push_attr(&mut attributes, "bandwidth", rep.bandwidth);
+        push_attr(&mut attributes, "width", rep.width);
+        push_attr(&mut attributes, "height", rep.height);
+        push_attr(&mut attributes, "frameRate", rep.frame_rate.as_ref());
+        push_attr(
+            &mut attributes,
+            "audioSamplingRate",
+            rep.audio_sampling_rate,
+        );
+        attributes.extend(rep.other_attributes.iter().cloned());
+        let mut children = rep.other_elements.clone();
+        children.extend(rep.base_urls.iter().map(|u| text_element("BaseURL", u)));
+        children.extend(rep.segment_template.iter().map(Element::from));
+        Element {
+            name: "Representation".to_string(),
+            attributes,
+            children,
+            text: None,
+        }
+    }
+}
+
+impl From<&SegmentTemplate> for Element {
+    fn from(template: &SegmentTemplate) -> Self {
+        let mut attributes = Vec::new();
+        push_attr(&mut attributes, "timescale", template.timescale);
+        push_attr(&mut attributes, "duration", template.duration);
+        push_attr(
+            &mut attributes,
+            "initialization",
+            template.initialization.as_ref(),
+        );
+        push_attr(&mut attributes, "media", template.media.as_ref());
+        push_attr(&mut attributes, "startNumber", template.start_number);
+        push_attr(
+            &mut attributes,
+            "presentationTimeOffset",
+            template.presentation_time_offset,
+        );
+        attributes.extend(template.other_attributes.iter().cloned());
+        let children = match &template.timeline {
+            Some(entries) => vec![Element {
+                name: "SegmentTimeline".to_string(),
+                attributes: Vec::new(),
+                children: entries
+                    .iter()
+                    .map(|s| {
+                        let mut attributes = Vec::new();
+                        push_attr(&mut attributes, "t", s.t);
+                        attributes.push(("d".to_string(), s.d.to_string()));
+                        if s.r != 0 {
+                            attributes.push(("r".to_string(), s.r.to_string()));
+                        }
+                        Element {
+                            name: "S".to_string(),
+                            attributes,
+                            children: Vec::new(),
+                            text: None,
+                        }
+                    })
+                    .collect(),
+                text: None,
+            }],
+            None => Vec::new(),
+        };
+        Element {
+            name: "SegmentTemplate".to_string(),
+            attributes,
+            children,
+            text: None,
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_element(f, self, 0)
+    }
+}
+
+fn write_element(f: &mut fmt::Formatter<'_>, el: &Element, depth: usize) -> fmt::Result {
+    let indent = "\t".repeat(depth);
+    write!(f, "{}<{}", indent, el.name)?;
+    for (k, v) in &el.attributes {
+        write!(f, " {}=\"{}\"", k, quick_xml::escape::escape(v.as_str()))?;
+    }
+    match (&el.text, el.children.is_empty()) {
+        (None, true) => writeln!(f, "/>"),
+        (Some(text), true) => writeln!(
+            f,
+            ">{}</{}>",
+            quick_xml::escape::escape(text.as_str()),
+            el.name
+        ),
+        (_, false) => {
+            writeln!(f, ">")?;
+            for child in &el.children {
+                write_element(f, child, depth + 1)?;
+            }
+            writeln!(f, "{}</{}>", indent, el.name)
+        }
+    }
+}
+
+impl fmt::Display for Mpd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+        write_element(f, &Element::from(self), 0)
+    }
+}
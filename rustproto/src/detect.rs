@@ -0,0 +1,366 @@
+use std::path::Path;
+
+use crate::filter_metadata::{read_metadata_print, FrameMetadata};
+use crate::{run_ffmpeg, CancelHandle, RunHandle, Source};
+
+/// A span of the input in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn overlaps(&self, other: &TimeRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// A frame `scdet` scored at or above the threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneChange {
+    /// Time of the first frame of the new scene, in seconds.
+    pub time: f64,
+    /// Scene change score, from 0 to 100.
+    pub score: f64,
+}
+
+/// `blackdetect` settings. The defaults are the filter's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlackOptions {
+    /// Video stream, as in `0:v:<n>`.
+    pub stream: usize,
+    /// Shortest black range reported, in seconds.
+    pub min_duration: f64,
+    /// Share of black pixels for a picture to count as black (`pic_th`).
+    pub picture_threshold: f64,
+    /// Luminance up to which a pixel counts as black, from 0 to 1 (`pix_th`).
+    pub pixel_threshold: f64,
+}
+
+impl Default for BlackOptions {
+    fn default() -> Self {
+        Self {
+            stream: 0,
+            min_duration: 2.0,
+            picture_threshold: 0.98,
+            pixel_threshold: 0.10,
+        }
+    }
+}
+
+/// `silencedetect` settings. The defaults are the filter's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SilenceOptions {
+    /// Audio stream, as in `0:a:<n>`.
+    pub stream: usize,
+    /// Level below which audio counts as silent, in dBFS.
+    pub noise_db: f64,
+    /// Shortest silence reported, in seconds.
+    pub min_duration: f64,
+}
+
+impl Default for SilenceOptions {
+    fn default() -> Self {
+        Self {
+            stream: 0,
+            noise_db: -60.0,
+            min_duration: 2.0,
+        }
+    }
+}
+
+/// `scdet` settings. The defaults are the filter's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneOptions {
+    /// Video stream, as in `0:v:<n>`.
+    pub stream: usize,
+    /// Lowest score reported as a scene change, from 0 to 100.
+    pub threshold: f64,
+}
+
+impl Default for SceneOptions {
+    fn default() -> Self {
+        Self {
+            stream: 0,
+            threshold: 10.0,
+        }
+    }
+}
+
+/// Everything a combined detection run found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaAnalysis {
+    pub black: Vec<TimeRange>,
+    pub silence: Vec<TimeRange>,
+    pub scenes: Vec<SceneChange>,
+    /// Time of the last frame ffmpeg processed, in seconds.
+    pub duration: Option<f64>,
+}
+
+/// A running detection. Results are read once ffmpeg is done.
+///
+/// Cancelling through `cancel_handle` (in either mode) makes `wait` return
+/// an error; dropping the detection cancels it.
+pub struct Detection<T> {
+    run: RunHandle,
+    finish: Box<dyn FnOnce(MediaAnalysis) -> T + Send>,
+    detectors: Detectors,
+}
+
+impl<T> Detection<T> {
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.run.cancel_handle()
+    }
+
+    pub fn wait(self) -> Result<T, String> {
+        let Detection {
+            run,
+            finish,
+            detectors,
+        } = self;
+        let output = run.wait()?;
+        if let Some(c) = &output.cancelled {
+            return Err(format!("detection cancelled: {}", c.reason));
+        }
+        let analysis = detectors.collect(output.path())?;
+        Ok(finish(analysis))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn wait_async(self) -> Result<T, String>
+    where
+        T: Send + 'static,
+    {
+        tokio::task::spawn_blocking(move || self.wait())
+            .await
+            .map_err(|_| "detection async join failed".to_string())?
+    }
+}
+
+/// Find black video ranges of at least `options.min_duration`.
+pub fn detect_black<S: Source + 'static>(
+    source: S,
+    options: &BlackOptions,
+) -> Result<Detection<Vec<TimeRange>>, String> {
+    let detectors = Detectors {
+        black: Some(options.clone()),
+        ..Default::default()
+    };
+    detectors.start(source, |a| a.black)
+}
+
+/// Find silent audio ranges of at least `options.min_duration`.
+pub fn detect_silence<S: Source + 'static>(
+    source: S,
+    options: &SilenceOptions,
+) -> Result<Detection<Vec<TimeRange>>, String> {
+    let detectors = Detectors {
+        silence: Some(options.clone()),
+        ..Default::default()
+    };
+    detectors.start(source, |a| a.silence)
+}
+
+/// Score every video frame against the previous one and report the scene
+/// changes at or above `options.threshold`.
+pub fn detect_scenes<S: Source + 'static>(
+    source: S,
+    options: &SceneOptions,
+) -> Result<Detection<Vec<SceneChange>>, String> {
+    let detectors = Detectors {
+        scenes: Some(options.clone()),
+        ..Default::default()
+    };
+    detectors.start(source, |a| a.scenes)
+}
+
+/// Run any combination of the detectors in one pass over the input.
+pub fn analyze_media<S: Source + 'static>(
+    source: S,
+    black: Option<&BlackOptions>,
+    silence: Option<&SilenceOptions>,
+    scenes: Option<&SceneOptions>,
+) -> Result<Detection<MediaAnalysis>, String> {
+    let detectors = Detectors {
+        black: black.cloned(),
+        silence: silence.cloned(),
+        scenes: scenes.cloned(),
+    };
+    if detectors.black.is_none() && detectors.silence.is_none() && detectors.scenes.is_none() {
+        return Err("analyze_media: no detector selected".to_string());
+    }
+    detectors.start(source, |a| a)
+}
+
+#[derive(Default)]
+pub(crate) struct Detectors {
+    pub black: Option<BlackOptions>,
+    pub silence: Option<SilenceOptions>,
+    pub scenes: Option<SceneOptions>,
+}
+
+impl Detectors {
+    pub(crate) fn start<S, T, F>(self, source: S, finish: F) -> Result<Detection<T>, String>
+    where
+        S: Source + 'static,
+        F: FnOnce(MediaAnalysis) -> T + Send + 'static,
+    {
+        let mut branches = Vec::new();
+        if let Some(o) = &self.black {
+            branches.push(format!(
+                "[0:v:{}]blackdetect=d={}:pic_th={}:pix_th={},\
+                 metadata=mode=print:file={{outdir}}/black.txt[black]",
+                o.stream, o.min_duration, o.picture_threshold, o.pixel_threshold
+            ));
+        }
+        if let Some(o) = &self.silence {
+            branches.push(format!(
+                "[0:a:{}]silencedetect=n={}dB:d={},\
+                 ametadata=mode=print:file={{outdir}}/silence.txt[silence]",
+                o.stream, o.noise_db, o.min_duration
+            ));
+        }
+        if let Some(o) = &self.scenes {
+            // scdet scores every frame; only the scores at or above the
+            // threshold are printed, compared explicitly since the
+            // `greater` function's tolerance isn't documented. Frames are
+            // not dropped (`sc_pass`) since the progress time follows the
+            // slowest output.
+            branches.push(format!(
+                "[0:v:{0}]scdet=threshold={1},metadata=mode=print:key=lavfi.scd.score:\
+                 value={1}:function=expr:expr=gte(VALUE1\\,VALUE2):\
+                 file={{outdir}}/scenes.txt[scenes]",
+                o.stream, o.threshold
+            ));
+        }
+
+        let mut args: Vec<String> = [
+            "ffmpeg",
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-nostats",
+            "-progress",
+            "{outdir}/progress.txt",
+            "-i",
+            "{input}",
+            "-filter_complex",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        args.push(branches.join(";"));
+        for (enabled, label) in [
+            (self.black.is_some(), "[black]"),
+            (self.silence.is_some(), "[silence]"),
+            (self.scenes.is_some(), "[scenes]"),
+        ] {
+            if enabled {
+                args.extend(["-map".to_string(), label.to_string()]);
+            }
+        }
+        args.extend(["-f", "null", "-"].iter().map(|s| s.to_string()));
+
+        let run = run_ffmpeg(source, &args)?;
+        Ok(Detection {
+            run,
+            finish: Box::new(finish),
+            detectors: self,
+        })
+    }
+
+    fn collect(&self, dir: &Path) -> Result<MediaAnalysis, String> {
+        let duration = read_progress_time(&dir.join("progress.txt"));
+        let mut analysis = MediaAnalysis {
+            duration,
+            ..Default::default()
+        };
+        if let Some(o) = &self.black {
+            let frames = read_metadata_print(&dir.join("black.txt"))?;
+            analysis.black = ranges(&frames, "lavfi.black_start", "lavfi.black_end", duration)
+                .into_iter()
+                .filter(|r| r.duration() >= o.min_duration)
+                .collect();
+        }
+        if let Some(o) = &self.silence {
+            let frames = read_metadata_print(&dir.join("silence.txt"))?;
+            analysis.silence = ranges(
+                &frames,
+                "lavfi.silence_start",
+                "lavfi.silence_end",
+                duration,
+            )
+            .into_iter()
+            .filter(|r| r.duration() >= o.min_duration)
+            .collect();
+        }
+        if self.scenes.is_some() {
+            let frames = read_metadata_print(&dir.join("scenes.txt"))?;
+            analysis.scenes = frames
+                .iter()
+                .filter_map(|f| {
+                    Some(SceneChange {
+                        time: f.pts_time?,
+                        score: f.f64("lavfi.scd.score")?,
+                    })
+                })
+                .collect();
+        }
+        Ok(analysis)
+    }
+}
+
+/// Pairs up start and end markers. A range still open at the end of the
+/// input ends at `end_of_input`, or is dropped when that is unknown.
+fn ranges(
+    frames: &[FrameMetadata],
+    start_key: &str,
+    end_key: &str,
+    end_of_input: Option<f64>,
+) -> Vec<TimeRange> {
+    let mut out = Vec::new();
+    let mut open: Option<f64> = None;
+    for frame in frames {
+        // One frame can end a range and start the next; apply them in time
+        // order.
+        let mut events: Vec<(f64, bool)> = Vec::with_capacity(2);
+        if let Some(t) = frame.f64(end_key) {
+            events.push((t, false));
+        }
+        if let Some(t) = frame.f64(start_key) {
+            events.push((t, true));
+        }
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (t, is_start) in events {
+            match (is_start, open) {
+                (true, None) => open = Some(t),
+                (false, Some(start)) => {
+                    out.push(TimeRange { start, end: t });
+                    open = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (open, end_of_input) {
+        if end > start {
+            out.push(TimeRange { start, end });
+        }
+    }
+    out
+}
+
+/// The last `out_time_us` of an ffmpeg `-progress` file.
+fn read_progress_time(path: &Path) -> Option<f64> {
+    let text = std::fs::read_to_string(path).ok()?;
+    text.lines()
+        .rev()
+        .find_map(|l| l.strip_prefix("out_time_us="))
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .map(|us| us as f64 / 1_000_000.0)
+}
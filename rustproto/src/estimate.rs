@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use crate::{run_ffprobe_capture, FfprobeError, FfprobeOutput, Source, Stream, StreamCommon};
+
+/// Where a derived duration or bitrate came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provenance {
+    /// `format.duration` / `format.bit_rate` from the container header.
+    Format,
+    /// The stream's own `duration` / `bit_rate`.
+    Stream,
+    /// Matroska statistics tags (`DURATION`, `BPS`, `NUMBER_OF_BYTES`).
+    Tag,
+    /// Measured from packet timestamps and sizes, see `scan_packets`.
+    PacketScan,
+    /// Computed from other values, e.g. the container bitrate minus the
+    /// bitrates of the other streams.
+    Estimated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Derived<T> {
+    pub value: T,
+    pub provenance: Provenance,
+}
+
+impl<T> Derived<T> {
+    fn new(value: T, provenance: Provenance) -> Self {
+        Derived { value, provenance }
+    }
+
+    /// Whether the value was computed rather than reported by the file.
+    pub fn is_estimate(&self) -> bool {
+        matches!(
+            self.provenance,
+            Provenance::PacketScan | Provenance::Estimated
+        )
+    }
+}
+
+impl StreamCommon {
+    /// Duration in seconds from the stream itself or its `DURATION` tag.
+    pub fn best_duration(&self) -> Option<Derived<f64>> {
+        if let Some(d) = self.duration.filter(|d| *d > 0.0) {
+            return Some(Derived::new(d, Provenance::Stream));
+        }
+        self.statistics_tag("DURATION")
+            .and_then(parse_tag_duration)
+            .map(|d| Derived::new(d, Provenance::Tag))
+    }
+
+    /// Bitrate in bits per second from the stream itself or its `BPS` /
+    /// `NUMBER_OF_BYTES` tags.
+    pub fn best_bit_rate(&self) -> Option<Derived<i64>> {
+        if let Some(b) = self.bit_rate.filter(|b| *b > 0) {
+            return Some(Derived::new(b, Provenance::Stream));
+        }
+        if let Some(b) = self
+            .statistics_tag("BPS")
+            .and_then(|v| v.trim().parse::<i64>().ok())
+        {
+            return Some(Derived::new(b, Provenance::Tag));
+        }
+        let bytes = self
+            .statistics_tag("NUMBER_OF_BYTES")
+            .and_then(|v| v.trim().parse::<i64>().ok())?;
+        let duration = self.best_duration()?;
+        let provenance = match duration.provenance {
+            Provenance::Stream | Provenance::Tag => Provenance::Tag,
+            other => other,
+        };
+        Some(Derived::new(
+            (bytes as f64 * 8.0 / duration.value).round() as i64,
+            provenance,
+        ))
+    }
+
+    /// mkvmerge writes its statistics tags with a language suffix, e.g.
+    /// `BPS-eng`.
+    fn statistics_tag(&self, key: &str) -> Option<&str> {
+        self.tag(key).or_else(|| {
+            self.tags
+                .iter()
+                .find(|(k, _)| {
+                    k.len() > key.len()
+                        && k[..key.len()].eq_ignore_ascii_case(key)
+                        && k.as_bytes()[key.len()] == b'-'
+                })
+                .map(|(_, v)| v.as_str())
+        })
+    }
+}
+
+impl FfprobeOutput {
+    /// Best available duration of the whole input in seconds: the container
+    /// duration, else the longest stream duration.
+    ///
+    /// Returns `None` when neither is known; `scan_packets` can measure it.
+    pub fn best_duration(&self) -> Option<Derived<f64>> {
+        if let Some(d) = self.format.duration.filter(|d| *d > 0.0) {
+            return Some(Derived::new(d, Provenance::Format));
+        }
+        self.streams
+            .iter()
+            .filter_map(|s| s.common().best_duration())
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+    }
+
+    /// Best available duration of stream `index`, falling back to the
+    /// container duration.
+    pub fn stream_duration(&self, index: i64) -> Option<Derived<f64>> {
+        let stream = self.stream(index)?;
+        stream.best_duration().or_else(|| {
+            self.format
+                .duration
+                .filter(|d| *d > 0.0)
+                .map(|d| Derived::new(d, Provenance::Estimated))
+        })
+    }
+
+    /// Best available bitrate of stream `index`.
+    ///
+    /// When only one audio or video stream has no known bitrate, it gets the
+    /// container bitrate minus the bitrates of the other streams. Subtitle
+    /// and data streams are subtracted if their bitrate is known and
+    /// otherwise ignored, as they are small next to the media.
+    pub fn stream_bit_rate(&self, index: i64) -> Option<Derived<i64>> {
+        let stream = self.streams.iter().find(|s| s.common().index == index)?;
+        if let Some(b) = stream.common().best_bit_rate() {
+            return Some(b);
+        }
+        if !matches!(stream, Stream::Video(_) | Stream::Audio(_)) {
+            return None;
+        }
+        let total = self.container_bit_rate()?;
+        let mut others = 0;
+        for s in &self.streams {
+            let common = s.common();
+            if common.index == index || matches!(s, Stream::Attachment(_)) {
+                continue;
+            }
+            others += match (s, common.best_bit_rate()) {
+                (_, Some(b)) => b.value,
+                (Stream::Video(_) | Stream::Audio(_), None) => return None,
+                (_, None) => 0,
+            };
+        }
+        Some(Derived::new(total - others, Provenance::Estimated)).filter(|b| b.value > 0)
+    }
+
+    fn stream(&self, index: i64) -> Option<&StreamCommon> {
+        self.streams
+            .iter()
+            .map(Stream::common)
+            .find(|s| s.index == index)
+    }
+
+    fn container_bit_rate(&self) -> Option<i64> {
+        self.format.bit_rate.filter(|b| *b > 0).or_else(|| {
+            let size = self.format.size?;
+            let duration = self.format.duration.filter(|d| *d > 0.0)?;
+            Some((size as f64 * 8.0 / duration).round() as i64)
+        })
+    }
+}
+
+/// Per-stream totals from reading every packet of an input.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PacketScan {
+    pub streams: BTreeMap<i64, StreamScan>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamScan {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Earliest packet timestamp in seconds.
+    pub start: f64,
+    /// Latest packet timestamp plus its duration, in seconds.
+    pub end: f64,
+}
+
+impl StreamScan {
+    pub fn duration(&self) -> f64 {
+        (self.end - self.start).max(0.0)
+    }
+}
+
+impl PacketScan {
+    /// Span from the earliest to the latest packet over all streams.
+    pub fn duration(&self) -> Option<Derived<f64>> {
+        let start = self
+            .streams
+            .values()
+            .map(|s| s.start)
+            .min_by(f64::total_cmp)?;
+        let end = self
+            .streams
+            .values()
+            .map(|s| s.end)
+            .max_by(f64::total_cmp)?;
+        Some(Derived::new((end - start).max(0.0), Provenance::PacketScan))
+    }
+
+    pub fn stream_duration(&self, index: i64) -> Option<Derived<f64>> {
+        let s = self.streams.get(&index)?;
+        Some(Derived::new(s.duration(), Provenance::PacketScan))
+    }
+
+    pub fn stream_bit_rate(&self, index: i64) -> Option<Derived<i64>> {
+        let s = self.streams.get(&index)?;
+        let duration = s.duration();
+        if duration <= 0.0 {
+            return None;
+        }
+        Some(Derived::new(
+            (s.bytes as f64 * 8.0 / duration).round() as i64,
+            Provenance::PacketScan,
+        ))
+    }
+}
+
+const PACKET_SCAN_ARGS: &[&str] = &[
+    "ffprobe",
+    "-hide_banner",
+    "-loglevel",
+    "error",
+    "-of",
+    "compact=p=0",
+    "-show_entries",
+    "packet=stream_index,pts_time,dts_time,duration_time,size",
+    "-i",
+    "{input}",
+];
+
+/// Read every packet of `source` to measure stream durations and sizes.
+/// This demuxes the whole input, so it is only worth it when
+/// `FfprobeOutput::best_duration` or `stream_bit_rate` come back empty.
+pub async fn scan_packets<S: Source + 'static>(source: S) -> Result<PacketScan, FfprobeError> {
+    let args: Vec<String> = PACKET_SCAN_ARGS.iter().map(|a| a.to_string()).collect();
+    match run_ffprobe_capture(source, &args) {
+        Ok(capture) => Ok(parse_packet_scan(&String::from_utf8_lossy(&capture.stdout))),
+        Err((message, capture)) => Err(FfprobeError {
+            message,
+            stderr: capture.stderr,
+            args,
+        }),
+    }
+}
+
+fn parse_packet_scan(out: &str) -> PacketScan {
+    let mut scan = PacketScan::default();
+    for line in out.lines() {
+        let mut index = None;
+        let (mut pts, mut dts, mut duration, mut size) = (None, None, 0.0, 0);
+        for field in line.split('|') {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            match key {
+                "stream_index" => index = value.parse::<i64>().ok(),
+                "pts_time" => pts = value.parse::<f64>().ok(),
+                "dts_time" => dts = value.parse::<f64>().ok(),
+                "duration_time" => duration = value.parse::<f64>().unwrap_or(0.0),
+                "size" => size = value.parse::<u64>().unwrap_or(0),
+                _ => {}
+            }
+        }
+        let Some(index) = index else {
+            continue;
+        };
+        let entry = scan.streams.entry(index).or_insert(StreamScan {
+            packets: 0,
+            bytes: 0,
+            start: f64::INFINITY,
+            end: f64::NEG_INFINITY,
+        });
+        entry.packets += 1;
+        entry.bytes += size;
+        if let Some(ts) = pts.or(dts) {
+            entry.start = entry.start.min(ts);
+            entry.end = entry.end.max(ts + duration);
+        }
+    }
+    for s in scan.streams.values_mut() {
+        if !s.start.is_finite() {
+            s.start = 0.0;
+            s.end = 0.0;
+        }
+    }
+    scan
+}
+
+/// Parses Matroska's `HH:MM:SS.nnnnnnnnn` duration tags.
+fn parse_tag_duration(value: &str) -> Option<f64> {
+    let mut parts = value.trim().rsplitn(3, ':');
+    let secs = parts.next()?.parse::<f64>().ok()?;
+    let mins = parts.next().map_or(Ok(0), str::parse::<u64>).ok()?;
+    let hours = parts.next().map_or(Ok(0), str::parse::<u64>).ok()?;
+    let total = hours as f64 * 3600.0 + mins as f64 * 60.0 + secs;
+    (total > 0.0).then_some(total)
+}
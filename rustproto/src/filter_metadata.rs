@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The metadata of one frame as written by `ametadata=mode=print:file=...`
+/// (or `metadata` for video).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameMetadata {
+    pub pts_time: Option<f64>,
+    pub values: HashMap<String, String>,
+}
+
+impl FrameMetadata {
+    pub fn f64(&self, key: &str) -> Option<f64> {
+        self.values.get(key)?.trim().parse().ok()
+    }
+}
+
+pub(crate) fn read_metadata_print(path: &Path) -> Result<Vec<FrameMetadata>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("read {}: {}", path.display(), e))?;
+    Ok(parse_metadata_print(&text))
+}
+
+/// Parses blocks of
+///
+/// ```text
+/// frame:0    pts:0       pts_time:0
+/// lavfi.r128.M=-120.691
+/// ```
+pub(crate) fn parse_metadata_print(text: &str) -> Vec<FrameMetadata> {
+    let mut frames: Vec<FrameMetadata> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with("frame:") {
+            let pts_time = line
+                .split_whitespace()
+                .find_map(|f| f.strip_prefix("pts_time:"))
+                .and_then(|v| v.parse().ok());
+            frames.push(FrameMetadata {
+                pts_time,
+                values: HashMap::new(),
+            });
+        } else if let (Some(frame), Some((key, value))) = (frames.last_mut(), line.split_once('='))
+        {
+            frame.values.insert(key.to_string(), value.to_string());
+        }
+    }
+    frames
+}
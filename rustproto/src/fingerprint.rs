@@ -0,0 +1,372 @@
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{run_ffmpeg, Source};
+
+/// Audio is analysed at this rate; chroma only needs pitches up to ~3.5 kHz.
+const AUDIO_RATE: u32 = 8000;
+/// 256 ms analysis frames, hashed every 128 ms.
+const AUDIO_FRAME: usize = 2048;
+const AUDIO_HOP: usize = 1024;
+const CHROMA_MIN_FREQ: f64 = 28.0;
+const CHROMA_MAX_FREQ: f64 = 3520.0;
+
+/// dHash input size: 9 columns so each of the 8 rows yields 8 comparisons.
+const HASH_W: usize = 9;
+const HASH_H: usize = 8;
+/// Frames are point-sampled to this many pixels per hash cell and averaged
+/// here rather than by an area scale, whose output varies from run to run.
+const CELL: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintOptions {
+    /// Video stream, as in `0:v:<n>`; `None` to skip video.
+    pub video_stream: Option<usize>,
+    /// Audio stream, as in `0:a:<n>`; `None` to skip audio.
+    pub audio_stream: Option<usize>,
+    /// Video frames hashed per second.
+    pub frames_per_second: f64,
+    /// Only fingerprint the first `duration` seconds.
+    pub duration: Option<f64>,
+}
+
+impl Default for FingerprintOptions {
+    fn default() -> Self {
+        Self {
+            video_stream: Some(0),
+            audio_stream: Some(0),
+            frames_per_second: 1.0,
+            duration: None,
+        }
+    }
+}
+
+/// Perceptual fingerprint of a title: a dHash per sampled video frame and a
+/// chroma hash per audio window. Survives re-encoding, rescaling and
+/// bitrate changes, but not cropping or different cuts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaFingerprint {
+    /// Seconds between consecutive `video` hashes.
+    pub video_interval: f64,
+    pub video: Vec<u64>,
+    /// Seconds between consecutive `audio` hashes.
+    pub audio_interval: f64,
+    pub audio: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FingerprintMatch {
+    /// From 0 for unrelated content to 1 for identical hashes, averaged over
+    /// video and audio.
+    pub similarity: f64,
+    pub video_similarity: Option<f64>,
+    pub audio_similarity: Option<f64>,
+    /// Seconds to add to a time in the other fingerprint to get the
+    /// matching time in this one.
+    pub offset: f64,
+}
+
+/// Decode `source` once and fingerprint its video and audio.
+pub fn media_fingerprint<S: Source + 'static>(
+    source: S,
+    options: &FingerprintOptions,
+) -> Result<MediaFingerprint, String> {
+    if options.video_stream.is_none() && options.audio_stream.is_none() {
+        return Err("media_fingerprint: no stream selected".to_string());
+    }
+    if options.frames_per_second.is_nan() || options.frames_per_second <= 0.0 {
+        return Err("media_fingerprint: frames_per_second must be positive".to_string());
+    }
+
+    let mut args: Vec<String> = ["ffmpeg", "-hide_banner", "-loglevel", "error"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if let Some(d) = options.duration {
+        args.extend(["-t".to_string(), d.to_string()]);
+    }
+    args.extend(["-i".to_string(), "{input}".to_string()]);
+    if let Some(stream) = options.video_stream {
+        args.extend([
+            "-map".to_string(),
+            format!("0:v:{}", stream),
+            "-vf".to_string(),
+            format!(
+                "fps={},format=gray,scale={}:{}:flags=neighbor",
+                options.frames_per_second,
+                HASH_W * CELL,
+                HASH_H * CELL
+            ),
+            "-f".to_string(),
+            "rawvideo".to_string(),
+            "{outdir}/video.gray".to_string(),
+        ]);
+    }
+    if let Some(stream) = options.audio_stream {
+        args.extend([
+            "-map".to_string(),
+            format!("0:a:{}", stream),
+            "-ac".to_string(),
+            "1".to_string(),
+            "-ar".to_string(),
+            AUDIO_RATE.to_string(),
+            "-f".to_string(),
+            "s16le".to_string(),
+            "{outdir}/audio.pcm".to_string(),
+        ]);
+    }
+
+    let output = run_ffmpeg(source, &args)?.wait()?;
+    let video = match options.video_stream {
+        Some(_) => video_hashes(&output.path().join("video.gray"))?,
+        None => Vec::new(),
+    };
+    let audio = match options.audio_stream {
+        Some(_) => audio_hashes(&output.path().join("audio.pcm"))?,
+        None => Vec::new(),
+    };
+    Ok(MediaFingerprint {
+        video_interval: 1.0 / options.frames_per_second,
+        video,
+        audio_interval: AUDIO_HOP as f64 / AUDIO_RATE as f64,
+        audio,
+    })
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("open {}: {}", path.display(), e))
+}
+
+fn video_hashes(path: &Path) -> Result<Vec<u64>, String> {
+    let mut reader = open(path)?;
+    let mut frame = vec![0u8; HASH_W * HASH_H * CELL * CELL];
+    let mut cells = [0u32; HASH_W * HASH_H];
+    let mut hashes = Vec::new();
+    while read_full(&mut reader, &mut frame)? {
+        cells.fill(0);
+        for (y, line) in frame.chunks_exact(HASH_W * CELL).enumerate() {
+            for (x, &luma) in line.iter().enumerate() {
+                cells[y / CELL * HASH_W + x / CELL] += u32::from(luma);
+            }
+        }
+        let mut hash = 0u64;
+        for row in 0..HASH_H {
+            for col in 0..HASH_W - 1 {
+                let left = cells[row * HASH_W + col];
+                let right = cells[row * HASH_W + col + 1];
+                hash = (hash << 1) | u64::from(left > right);
+            }
+        }
+        hashes.push(hash);
+    }
+    Ok(hashes)
+}
+
+/// Fills `buf`; `false` at the end of the input, dropping a partial tail.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("read fingerprint input: {}", e)),
+        }
+    }
+    Ok(true)
+}
+
+/// Streams the PCM through overlapping windows, so memory stays at one
+/// window regardless of the input length.
+fn audio_hashes(path: &Path) -> Result<Vec<u32>, String> {
+    let mut reader = open(path)?;
+    let window: Vec<f64> = (0..AUDIO_FRAME)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / AUDIO_FRAME as f64).cos())
+        .collect();
+    let classes = chroma_classes();
+
+    let mut samples = vec![0f64; AUDIO_FRAME];
+    let mut bytes = vec![0u8; AUDIO_HOP * 2];
+    let mut filled = 0;
+    let mut prev = [0f64; 12];
+    let mut hashes = Vec::new();
+    let (mut re, mut im) = (vec![0f64; AUDIO_FRAME], vec![0f64; AUDIO_FRAME]);
+    while read_full(&mut reader, &mut bytes)? {
+        samples.copy_within(AUDIO_HOP.., 0);
+        for (i, pair) in bytes.chunks_exact(2).enumerate() {
+            samples[AUDIO_FRAME - AUDIO_HOP + i] =
+                i16::from_le_bytes([pair[0], pair[1]]) as f64 / 32768.0;
+        }
+        filled += AUDIO_HOP;
+        if filled < AUDIO_FRAME {
+            continue;
+        }
+
+        for i in 0..AUDIO_FRAME {
+            re[i] = samples[i] * window[i];
+            im[i] = 0.0;
+        }
+        fft(&mut re, &mut im);
+        let mut chroma = [0f64; 12];
+        for (bin, class) in classes.iter().enumerate() {
+            if let Some(c) = class {
+                chroma[*c] += re[bin] * re[bin] + im[bin] * im[bin];
+            }
+        }
+        let total: f64 = chroma.iter().sum();
+        if total > 0.0 {
+            chroma.iter_mut().for_each(|c| *c /= total);
+        }
+        hashes.push(chroma_hash(&chroma, &prev));
+        prev = chroma;
+    }
+    Ok(hashes)
+}
+
+/// Pitch class of each FFT bin within the chroma range.
+fn chroma_classes() -> Vec<Option<usize>> {
+    (0..AUDIO_FRAME / 2)
+        .map(|bin| {
+            let freq = bin as f64 * AUDIO_RATE as f64 / AUDIO_FRAME as f64;
+            if !(CHROMA_MIN_FREQ..=CHROMA_MAX_FREQ).contains(&freq) {
+                return None;
+            }
+            let note = (12.0 * (freq / 440.0).log2() + 69.0).round() as i64;
+            Some(note.rem_euclid(12) as usize)
+        })
+        .collect()
+}
+
+/// 12 bits comparing neighbouring pitch classes and 12 bits comparing each
+/// class with the previous window.
+fn chroma_hash(chroma: &[f64; 12], prev: &[f64; 12]) -> u32 {
+    let mut hash = 0u32;
+    for i in 0..12 {
+        hash |= u32::from(chroma[i] > chroma[(i + 1) % 12]) << i;
+        hash |= u32::from(chroma[i] > prev[i]) << (12 + i);
+    }
+    hash
+}
+
+/// In-place iterative radix-2 FFT; `re.len()` must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        let (wr, wi) = (angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let (mut cr, mut ci) = (1.0, 0.0);
+            for k in 0..len / 2 {
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * cr - im[b] * ci;
+                let ti = re[b] * ci + im[b] * cr;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+                (cr, ci) = (cr * wr - ci * wi, cr * wi + ci * wr);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+impl MediaFingerprint {
+    /// Find the time offset within `max_offset` seconds at which `other`
+    /// best matches this fingerprint, and how well it matches there.
+    ///
+    /// Only offsets where the two overlap by at least half of the shorter
+    /// one are considered. Returns `None` when the fingerprints have no
+    /// kind of hash in common or were taken at different rates.
+    pub fn compare(&self, other: &MediaFingerprint, max_offset: f64) -> Option<FingerprintMatch> {
+        let video = (self.video_interval == other.video_interval)
+            .then(|| {
+                let shift = (max_offset / self.video_interval).round() as isize;
+                align(&self.video, &other.video, shift, 64, |a, b| {
+                    (a ^ b).count_ones()
+                })
+            })
+            .flatten();
+        let audio = (self.audio_interval == other.audio_interval)
+            .then(|| {
+                let shift = (max_offset / self.audio_interval).round() as isize;
+                align(&self.audio, &other.audio, shift, 24, |a, b| {
+                    (a ^ b).count_ones()
+                })
+            })
+            .flatten();
+
+        let offset = match (video, audio) {
+            (Some((shift, _)), _) => shift as f64 * self.video_interval,
+            (None, Some((shift, _))) => shift as f64 * self.audio_interval,
+            (None, None) => return None,
+        };
+        let video_similarity = video.map(|(_, s)| s);
+        let audio_similarity = audio.map(|(_, s)| s);
+        let scores: Vec<f64> = video_similarity
+            .into_iter()
+            .chain(audio_similarity)
+            .collect();
+        Some(FingerprintMatch {
+            similarity: scores.iter().sum::<f64>() / scores.len() as f64,
+            video_similarity,
+            audio_similarity,
+            offset,
+        })
+    }
+}
+
+/// Best shift of `b` against `a` (`a[i]` matches `b[i - shift]`) and its
+/// similarity. Unrelated hashes agree on about half their bits, so the bit
+/// agreement is rescaled to put that at 0.
+fn align<T: Copy>(
+    a: &[T],
+    b: &[T],
+    max_shift: isize,
+    bits: u32,
+    distance: impl Fn(T, T) -> u32,
+) -> Option<(isize, f64)> {
+    let min_overlap = (a.len().min(b.len()) / 2).max(1);
+    let mut best: Option<(isize, f64)> = None;
+    for shift in -max_shift..=max_shift {
+        let start = shift.max(0) as usize;
+        let end = (b.len() as isize + shift).min(a.len() as isize);
+        if end <= start as isize || ((end as usize) - start) < min_overlap {
+            continue;
+        }
+        let end = end as usize;
+        let differing: u64 = (start..end)
+            .map(|i| distance(a[i], b[(i as isize - shift) as usize]) as u64)
+            .sum();
+        let agreement = 1.0 - differing as f64 / ((end - start) as f64 * bits as f64);
+        let similarity = (2.0 * agreement - 1.0).max(0.0);
+        let better = match best {
+            None => true,
+            Some((s, v)) => similarity > v || (similarity == v && shift.abs() < s.abs()),
+        };
+        if better {
+            best = Some((shift, similarity));
+        }
+    }
+    best
+}
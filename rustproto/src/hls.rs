@@ -0,0 +1,855 @@
+//! HLS playlist (m3u8) model with parsing and serialization.
+//!
+//! Covers what ffmpeg's HLS muxer writes plus the LL-HLS tags
+//! (`EXT-X-PART`, `EXT-X-PRELOAD-HINT`, `EXT-X-SERVER-CONTROL`). Tags that are
+//! not modelled are kept verbatim so a parse/serialize round trip does not
+//! drop them.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Media(MediaPlaylist),
+    Master(MasterPlaylist),
+}
+
+impl Playlist {
+    pub fn rewrite_uris<F: FnMut(&str) -> String>(&mut self, f: F) {
+        match self {
+            Playlist::Media(p) => p.rewrite_uris(f),
+            Playlist::Master(p) => p.rewrite_uris(f),
+        }
+    }
+}
+
+impl fmt::Display for Playlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Playlist::Media(p) => p.fmt(f),
+            Playlist::Master(p) => p.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistType {
+    Event,
+    Vod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub length: u64,
+    pub offset: Option<u64>,
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "{}@{}", self.length, offset),
+            None => write!(f, "{}", self.length),
+        }
+    }
+}
+
+impl std::str::FromStr for ByteRange {
+    type Err = HlsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (length, offset) = match s.split_once('@') {
+            Some((l, o)) => (l, Some(o)),
+            None => (s, None),
+        };
+        let length = length
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| HlsError::new(0, format!("invalid byte range: {s}")))?;
+        let offset = match offset {
+            Some(o) => Some(
+                o.trim()
+                    .parse::<u64>()
+                    .map_err(|_| HlsError::new(0, format!("invalid byte range: {s}")))?,
+            ),
+            None => None,
+        };
+        Ok(ByteRange { length, offset })
+    }
+}
+
+/// `EXT-X-MAP`: the initialization section for the segments that follow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub uri: String,
+    pub byte_range: Option<ByteRange>,
+}
+
+/// `EXT-X-PART`: an LL-HLS partial segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub uri: String,
+    pub duration: f64,
+    pub independent: bool,
+    pub byte_range: Option<ByteRange>,
+    pub gap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreloadHintType {
+    Part,
+    Map,
+}
+
+/// `EXT-X-PRELOAD-HINT`: a resource the server is about to publish.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreloadHint {
+    pub hint_type: PreloadHintType,
+    pub uri: String,
+    pub byte_range_start: Option<u64>,
+    pub byte_range_length: Option<u64>,
+}
+
+/// `EXT-X-SERVER-CONTROL`: delivery directives for LL-HLS clients.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerControl {
+    pub can_block_reload: bool,
+    pub can_skip_until: Option<f64>,
+    pub hold_back: Option<f64>,
+    pub part_hold_back: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaSegment {
+    pub uri: String,
+    pub duration: f64,
+    pub title: Option<String>,
+    pub byte_range: Option<ByteRange>,
+    /// `EXT-X-DISCONTINUITY` precedes this segment.
+    pub discontinuity: bool,
+    /// `EXT-X-PROGRAM-DATE-TIME` as written, only where it appears.
+    pub program_date_time: Option<String>,
+    /// `EXT-X-MAP` declared right before this segment. Use
+    /// `MediaPlaylist::map_for` to get the map that is in effect.
+    pub map: Option<Map>,
+    /// Partial segments that make up this segment (LL-HLS).
+    pub parts: Vec<Part>,
+    /// Unmodelled tags between the previous segment and this one.
+    pub other_tags: Vec<String>,
+}
+
+impl MediaSegment {
+    pub fn new<U: Into<String>>(uri: U, duration: f64) -> Self {
+        Self {
+            uri: uri.into(),
+            duration,
+            title: None,
+            byte_range: None,
+            discontinuity: false,
+            program_date_time: None,
+            map: None,
+            parts: Vec::new(),
+            other_tags: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaPlaylist {
+    pub version: Option<u32>,
+    pub target_duration: u64,
+    pub media_sequence: u64,
+    pub discontinuity_sequence: u64,
+    pub playlist_type: Option<PlaylistType>,
+    pub independent_segments: bool,
+    pub server_control: Option<ServerControl>,
+    /// `EXT-X-PART-INF:PART-TARGET`, in seconds.
+    pub part_target: Option<f64>,
+    pub segments: Vec<MediaSegment>,
+    /// Parts of the segment that is still being written.
+    pub trailing_parts: Vec<Part>,
+    pub preload_hints: Vec<PreloadHint>,
+    pub end_list: bool,
+    /// Unmodelled header tags.
+    pub other_tags: Vec<String>,
+}
+
+impl MediaPlaylist {
+    pub fn parse(text: &str) -> Result<Self, HlsError> {
+        match parse(text)? {
+            Playlist::Media(p) => Ok(p),
+            Playlist::Master(_) => Err(HlsError::new(0, "expected a media playlist")),
+        }
+    }
+
+    /// The `EXT-X-MAP` in effect for `segments[index]`.
+    pub fn map_for(&self, index: usize) -> Option<&Map> {
+        self.segments
+            .get(..=index)?
+            .iter()
+            .rev()
+            .find_map(|s| s.map.as_ref())
+    }
+
+    pub fn total_duration(&self) -> f64 {
+        self.segments.iter().map(|s| s.duration).sum()
+    }
+
+    /// Start time of every segment relative to the first one, paired with it.
+    pub fn timeline(&self) -> impl Iterator<Item = (f64, &MediaSegment)> {
+        let mut start = 0.0;
+        self.segments.iter().map(move |s| {
+            let at = start;
+            start += s.duration;
+            (at, s)
+        })
+    }
+
+    pub fn rewrite_uris<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+        for segment in &mut self.segments {
+            segment.uri = f(&segment.uri);
+            if let Some(map) = &mut segment.map {
+                map.uri = f(&map.uri);
+            }
+            for part in &mut segment.parts {
+                part.uri = f(&part.uri);
+            }
+        }
+        for part in &mut self.trailing_parts {
+            part.uri = f(&part.uri);
+        }
+        for hint in &mut self.preload_hints {
+            hint.uri = f(&hint.uri);
+        }
+    }
+}
+
+impl fmt::Display for MediaPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        if let Some(version) = self.version {
+            writeln!(f, "#EXT-X-VERSION:{}", version)?;
+        }
+        writeln!(f, "#EXT-X-TARGETDURATION:{}", self.target_duration)?;
+        writeln!(f, "#EXT-X-MEDIA-SEQUENCE:{}", self.media_sequence)?;
+        if self.discontinuity_sequence != 0 {
+            writeln!(
+                f,
+                "#EXT-X-DISCONTINUITY-SEQUENCE:{}",
+                self.discontinuity_sequence
+            )?;
+        }
+        match self.playlist_type {
+            Some(PlaylistType::Event) => writeln!(f, "#EXT-X-PLAYLIST-TYPE:EVENT")?,
+            Some(PlaylistType::Vod) => writeln!(f, "#EXT-X-PLAYLIST-TYPE:VOD")?,
+            None => {}
+        }
+        if self.independent_segments {
+            writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+        }
+        if let Some(sc) = &self.server_control {
+            let mut attrs = Vec::new();
+            if sc.can_block_reload {
+                attrs.push("CAN-BLOCK-RELOAD=YES".to_string());
+            }
+            if let Some(v) = sc.can_skip_until {
+                attrs.push(format!("CAN-SKIP-UNTIL={}", format_decimal(v)));
+            }
+            if let Some(v) = sc.hold_back {
+                attrs.push(format!("HOLD-BACK={}", format_decimal(v)));
+            }
+            if let Some(v) = sc.part_hold_back {
+                attrs.push(format!("PART-HOLD-BACK={}", format_decimal(v)));
+            }
+            writeln!(f, "#EXT-X-SERVER-CONTROL:{}", attrs.join(","))?;
+        }
+        if let Some(target) = self.part_target {
+            writeln!(f, "#EXT-X-PART-INF:PART-TARGET={}", format_decimal(target))?;
+        }
+        for tag in &self.other_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        for segment in &self.segments {
+            if segment.discontinuity {
+                writeln!(f, "#EXT-X-DISCONTINUITY")?;
+            }
+            if let Some(pdt) = &segment.program_date_time {
+                writeln!(f, "#EXT-X-PROGRAM-DATE-TIME:{}", pdt)?;
+            }
+            if let Some(map) = &segment.map {
+                write!(f, "#EXT-X-MAP:URI=\"{}\"", map.uri)?;
+                if let Some(range) = &map.byte_range {
+                    write!(f, ",BYTERANGE=\"{}\"", range)?;
+                }
+                writeln!(f)?;
+            }
+            for tag in &segment.other_tags {
+                writeln!(f, "{}", tag)?;
+            }
+            for part in &segment.parts {
+                write_part(f, part)?;
+            }
+            write!(f, "#EXTINF:{},", format_duration(segment.duration))?;
+            if let Some(title) = &segment.title {
+                write!(f, "{}", title)?;
+            }
+            writeln!(f)?;
+            if let Some(range) = &segment.byte_range {
+                writeln!(f, "#EXT-X-BYTERANGE:{}", range)?;
+            }
+            writeln!(f, "{}", segment.uri)?;
+        }
+        for part in &self.trailing_parts {
+            write_part(f, part)?;
+        }
+        for hint in &self.preload_hints {
+            let kind = match hint.hint_type {
+                PreloadHintType::Part => "PART",
+                PreloadHintType::Map => "MAP",
+            };
+            write!(f, "#EXT-X-PRELOAD-HINT:TYPE={},URI=\"{}\"", kind, hint.uri)?;
+            if let Some(start) = hint.byte_range_start {
+                write!(f, ",BYTERANGE-START={}", start)?;
+            }
+            if let Some(length) = hint.byte_range_length {
+                write!(f, ",BYTERANGE-LENGTH={}", length)?;
+            }
+            writeln!(f)?;
+        }
+        if self.end_list {
+            writeln!(f, "#EXT-X-ENDLIST")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_part(f: &mut fmt::Formatter<'_>, part: &Part) -> fmt::Result {
+    write!(
+        f,
+        "#EXT-X-PART:DURATION={},URI=\"{}\"",
+        format_decimal(part.duration),
+        part.uri
+    )?;
+    if part.independent {
+        write!(f, ",INDEPENDENT=YES")?;
+    }
+    if let Some(range) = &part.byte_range {
+        write!(f, ",BYTERANGE=\"{}\"", range)?;
+    }
+    if part.gap {
+        write!(f, ",GAP=YES")?;
+    }
+    writeln!(f)
+}
+
+/// `EXT-X-STREAM-INF` entry of a master playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantStream {
+    pub uri: String,
+    pub bandwidth: u64,
+    pub average_bandwidth: Option<u64>,
+    pub codecs: Option<String>,
+    pub resolution: Option<(u32, u32)>,
+    pub frame_rate: Option<f64>,
+    pub audio: Option<String>,
+    pub video: Option<String>,
+    pub subtitles: Option<String>,
+    pub closed_captions: Option<String>,
+    /// Unmodelled attributes, values as written (including quotes).
+    pub other_attributes: Vec<(String, String)>,
+}
+
+/// `EXT-X-MEDIA` entry of a master playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+    pub media_type: String,
+    pub group_id: String,
+    pub name: String,
+    pub uri: Option<String>,
+    pub language: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    pub forced: bool,
+    pub channels: Option<String>,
+    /// Unmodelled attributes, values as written (including quotes).
+    pub other_attributes: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MasterPlaylist {
+    pub version: Option<u32>,
+    pub independent_segments: bool,
+    pub renditions: Vec<Rendition>,
+    pub variants: Vec<VariantStream>,
+    /// Unmodelled tags, e.g. `EXT-X-I-FRAME-STREAM-INF`.
+    pub other_tags: Vec<String>,
+}
+
+impl MasterPlaylist {
+    pub fn parse(text: &str) -> Result<Self, HlsError> {
+        match parse(text)? {
+            Playlist::Master(p) => Ok(p),
+            Playlist::Media(_) => Err(HlsError::new(0, "expected a master playlist")),
+        }
+    }
+
+    pub fn rewrite_uris<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+        for rendition in &mut self.renditions {
+            if let Some(uri) = &rendition.uri {
+                rendition.uri = Some(f(uri));
+            }
+        }
+        for variant in &mut self.variants {
+            variant.uri = f(&variant.uri);
+        }
+    }
+}
+
+impl fmt::Display for MasterPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        if let Some(version) = self.version {
+            writeln!(f, "#EXT-X-VERSION:{}", version)?;
+        }
+        if self.independent_segments {
+            writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+        }
+        for tag in &self.other_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        for r in &self.renditions {
+            let mut attrs = vec![
+                format!("TYPE={}", r.media_type),
+                format!("GROUP-ID=\"{}\"", r.group_id),
+                format!("NAME=\"{}\"", r.name),
+            ];
+            if let Some(language) = &r.language {
+                attrs.push(format!("LANGUAGE=\"{}\"", language));
+            }
+            if r.default {
+                attrs.push("DEFAULT=YES".to_string());
+            }
+            if r.autoselect {
+                attrs.push("AUTOSELECT=YES".to_string());
+            }
+            if r.forced {
+                attrs.push("FORCED=YES".to_string());
+            }
+            if let Some(channels) = &r.channels {
+                attrs.push(format!("CHANNELS=\"{}\"", channels));
+            }
+            if let Some(uri) = &r.uri {
+                attrs.push(format!("URI=\"{}\"", uri));
+            }
+            attrs.extend(r.other_attributes.iter().map(|(k, v)| format!("{k}={v}")));
+            writeln!(f, "#EXT-X-MEDIA:{}", attrs.join(","))?;
+        }
+        for v in &self.variants {
+            let mut attrs = vec![format!("BANDWIDTH={}", v.bandwidth)];
+            if let Some(avg) = v.average_bandwidth {
+                attrs.push(format!("AVERAGE-BANDWIDTH={}", avg));
+            }
+            if let Some((w, h)) = v.resolution {
+                attrs.push(format!("RESOLUTION={}x{}", w, h));
+            }
+            if let Some(rate) = v.frame_rate {
+                attrs.push(format!("FRAME-RATE={:.3}", rate));
+            }
+            if let Some(codecs) = &v.codecs {
+                attrs.push(format!("CODECS=\"{}\"", codecs));
+            }
+            if let Some(audio) = &v.audio {
+                attrs.push(format!("AUDIO=\"{}\"", audio));
+            }
+            if let Some(video) = &v.video {
+                attrs.push(format!("VIDEO=\"{}\"", video));
+            }
+            if let Some(subtitles) = &v.subtitles {
+                attrs.push(format!("SUBTITLES=\"{}\"", subtitles));
+            }
+            if let Some(cc) = &v.closed_captions {
+                // `NONE` is an enumerated value and must stay unquoted.
+                if cc == "NONE" {
+                    attrs.push("CLOSED-CAPTIONS=NONE".to_string());
+                } else {
+                    attrs.push(format!("CLOSED-CAPTIONS=\"{}\"", cc));
+                }
+            }
+            attrs.extend(v.other_attributes.iter().map(|(k, v)| format!("{k}={v}")));
+            writeln!(f, "#EXT-X-STREAM-INF:{}", attrs.join(","))?;
+            writeln!(f, "{}", v.uri)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HlsError {
+    /// 1-based line number, 0 if not tied to a line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl HlsError {
+    fn new<M: Into<String>>(line: usize, message: M) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for HlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}: {}", self.line, self.message)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for HlsError {}
+
+/// Parse a media or master playlist.
+pub fn parse(text: &str) -> Result<Playlist, HlsError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty());
+    match lines.next() {
+        Some((_, "#EXTM3U")) => {}
+        Some((n, _)) => return Err(HlsError::new(n, "missing #EXTM3U header")),
+        None => return Err(HlsError::new(0, "empty playlist")),
+    }
+    let lines: Vec<(usize, &str)> = lines.collect();
+    let is_master = lines
+        .iter()
+        .any(|(_, l)| l.starts_with("#EXT-X-STREAM-INF:") || l.starts_with("#EXT-X-MEDIA:"));
+    if is_master {
+        parse_master(&lines).map(Playlist::Master)
+    } else {
+        parse_media(&lines).map(Playlist::Media)
+    }
+}
+
+fn parse_media(lines: &[(usize, &str)]) -> Result<MediaPlaylist, HlsError> {
+    let mut playlist = MediaPlaylist::default();
+    let mut next = MediaSegment::new("", 0.0);
+    let mut saw_extinf = false;
+    // Everything before the first segment-level tag belongs to the header.
+    let mut in_header = true;
+
+    for &(n, line) in lines {
+        if !line.starts_with('#') {
+            if !saw_extinf {
+                return Err(HlsError::new(
+                    n,
+                    format!("segment URI without #EXTINF: {line}"),
+                ));
+            }
+            next.uri = line.to_string();
+            playlist.segments.push(next);
+            next = MediaSegment::new("", 0.0);
+            saw_extinf = false;
+            continue;
+        }
+        let (tag, value) = match line.split_once(':') {
+            Some((t, v)) => (t, v),
+            None => (line, ""),
+        };
+        match tag {
+            "#EXT-X-VERSION" => playlist.version = Some(parse_num(n, tag, value)?),
+            "#EXT-X-TARGETDURATION" => playlist.target_duration = parse_num(n, tag, value)?,
+            "#EXT-X-MEDIA-SEQUENCE" => playlist.media_sequence = parse_num(n, tag, value)?,
+            "#EXT-X-DISCONTINUITY-SEQUENCE" => {
+                playlist.discontinuity_sequence = parse_num(n, tag, value)?
+            }
+            "#EXT-X-PLAYLIST-TYPE" => {
+                playlist.playlist_type = match value {
+                    "EVENT" => Some(PlaylistType::Event),
+                    "VOD" => Some(PlaylistType::Vod),
+                    _ => return Err(HlsError::new(n, format!("invalid playlist type: {value}"))),
+                }
+            }
+            "#EXT-X-INDEPENDENT-SEGMENTS" => playlist.independent_segments = true,
+            "#EXT-X-ENDLIST" => playlist.end_list = true,
+            "#EXT-X-PART-INF" => {
+                let attrs = parse_attributes(value);
+                playlist.part_target = Some(
+                    attr_f64(n, &attrs, "PART-TARGET")?
+                        .ok_or_else(|| HlsError::new(n, "EXT-X-PART-INF without PART-TARGET"))?,
+                );
+            }
+            "#EXT-X-SERVER-CONTROL" => {
+                let attrs = parse_attributes(value);
+                playlist.server_control = Some(ServerControl {
+                    can_block_reload: attr_flag(&attrs, "CAN-BLOCK-RELOAD"),
+                    can_skip_until: attr_f64(n, &attrs, "CAN-SKIP-UNTIL")?,
+                    hold_back: attr_f64(n, &attrs, "HOLD-BACK")?,
+                    part_hold_back: attr_f64(n, &attrs, "PART-HOLD-BACK")?,
+                });
+            }
+            "#EXTINF" => {
+                in_header = false;
+                let (duration, title) = match value.split_once(',') {
+                    Some((d, t)) => (d, (!t.is_empty()).then(|| t.to_string())),
+                    None => (value, None),
+                };
+                next.duration = duration
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| HlsError::new(n, format!("invalid #EXTINF duration: {value}")))?;
+                next.title = title;
+                saw_extinf = true;
+            }
+            "#EXT-X-BYTERANGE" => {
+                in_header = false;
+                next.byte_range = Some(parse_byte_range(n, value)?);
+            }
+            "#EXT-X-DISCONTINUITY" => {
+                in_header = false;
+                next.discontinuity = true;
+            }
+            "#EXT-X-PROGRAM-DATE-TIME" => {
+                in_header = false;
+                next.program_date_time = Some(value.to_string());
+            }
+            "#EXT-X-MAP" => {
+                in_header = false;
+                let attrs = parse_attributes(value);
+                next.map = Some(Map {
+                    uri: attr_required(n, &attrs, "URI")?,
+                    byte_range: match attr(&attrs, "BYTERANGE") {
+                        Some(v) => Some(parse_byte_range(n, &v)?),
+                        None => None,
+                    },
+                });
+            }
+            "#EXT-X-PART" => {
+                in_header = false;
+                let attrs = parse_attributes(value);
+                next.parts.push(Part {
+                    uri: attr_required(n, &attrs, "URI")?,
+                    duration: attr_f64(n, &attrs, "DURATION")?
+                        .ok_or_else(|| HlsError::new(n, "EXT-X-PART without DURATION"))?,
+                    independent: attr_flag(&attrs, "INDEPENDENT"),
+                    byte_range: match attr(&attrs, "BYTERANGE") {
+                        Some(v) => Some(parse_byte_range(n, &v)?),
+                        None => None,
+                    },
+                    gap: attr_flag(&attrs, "GAP"),
+                });
+            }
+            "#EXT-X-PRELOAD-HINT" => {
+                let attrs = parse_attributes(value);
+                let hint_type = match attr(&attrs, "TYPE").as_deref() {
+                    Some("PART") => PreloadHintType::Part,
+                    Some("MAP") => PreloadHintType::Map,
+                    _ => return Err(HlsError::new(n, "invalid EXT-X-PRELOAD-HINT TYPE")),
+                };
+                playlist.preload_hints.push(PreloadHint {
+                    hint_type,
+                    uri: attr_required(n, &attrs, "URI")?,
+                    byte_range_start: attr_num(n, &attrs, "BYTERANGE-START")?,
+                    byte_range_length: attr_num(n, &attrs, "BYTERANGE-LENGTH")?,
+                });
+            }
+            _ if tag.starts_with("#EXT") => {
+                if in_header {
+                    playlist.other_tags.push(line.to_string());
+                } else {
+                    next.other_tags.push(line.to_string());
+                }
+            }
+            // Plain comments.
+            _ => {}
+        }
+    }
+
+    playlist.trailing_parts = next.parts;
+    Ok(playlist)
+}
+
+fn parse_master(lines: &[(usize, &str)]) -> Result<MasterPlaylist, HlsError> {
+    let mut playlist = MasterPlaylist::default();
+    let mut pending: Option<VariantStream> = None;
+
+    for &(n, line) in lines {
+        if !line.starts_with('#') {
+            let mut variant = pending.take().ok_or_else(|| {
+                HlsError::new(n, format!("URI without #EXT-X-STREAM-INF: {line}"))
+            })?;
+            variant.uri = line.to_string();
+            playlist.variants.push(variant);
+            continue;
+        }
+        let (tag, value) = match line.split_once(':') {
+            Some((t, v)) => (t, v),
+            None => (line, ""),
+        };
+        match tag {
+            "#EXT-X-VERSION" => playlist.version = Some(parse_num(n, tag, value)?),
+            "#EXT-X-INDEPENDENT-SEGMENTS" => playlist.independent_segments = true,
+            "#EXT-X-STREAM-INF" => {
+                let mut attrs = parse_attributes(value);
+                let resolution = match take_attr(&mut attrs, "RESOLUTION") {
+                    Some(v) => {
+                        let (w, h) = v
+                            .split_once(['x', 'X'])
+                            .ok_or_else(|| HlsError::new(n, format!("invalid RESOLUTION: {v}")))?;
+                        Some((
+                            parse_num(n, "RESOLUTION", w)?,
+                            parse_num(n, "RESOLUTION", h)?,
+                        ))
+                    }
+                    None => None,
+                };
+                let bandwidth = take_attr(&mut attrs, "BANDWIDTH")
+                    .ok_or_else(|| HlsError::new(n, "EXT-X-STREAM-INF without BANDWIDTH"))?;
+                pending = Some(VariantStream {
+                    uri: String::new(),
+                    bandwidth: parse_num(n, "BANDWIDTH", &bandwidth)?,
+                    average_bandwidth: match take_attr(&mut attrs, "AVERAGE-BANDWIDTH") {
+                        Some(v) => Some(parse_num(n, "AVERAGE-BANDWIDTH", &v)?),
+                        None => None,
+                    },
+                    codecs: take_attr(&mut attrs, "CODECS"),
+                    resolution,
+                    frame_rate: match take_attr(&mut attrs, "FRAME-RATE") {
+                        Some(v) => Some(parse_num(n, "FRAME-RATE", &v)?),
+                        None => None,
+                    },
+                    audio: take_attr(&mut attrs, "AUDIO"),
+                    video: take_attr(&mut attrs, "VIDEO"),
+                    subtitles: take_attr(&mut attrs, "SUBTITLES"),
+                    closed_captions: take_attr(&mut attrs, "CLOSED-CAPTIONS"),
+                    other_attributes: raw_attributes(attrs),
+                });
+            }
+            "#EXT-X-MEDIA" => {
+                let mut attrs = parse_attributes(value);
+                playlist.renditions.push(Rendition {
+                    media_type: take_attr(&mut attrs, "TYPE")
+                        .ok_or_else(|| HlsError::new(n, "EXT-X-MEDIA without TYPE"))?,
+                    group_id: take_attr(&mut attrs, "GROUP-ID")
+                        .ok_or_else(|| HlsError::new(n, "EXT-X-MEDIA without GROUP-ID"))?,
+                    name: take_attr(&mut attrs, "NAME")
+                        .ok_or_else(|| HlsError::new(n, "EXT-X-MEDIA without NAME"))?,
+                    uri: take_attr(&mut attrs, "URI"),
+                    language: take_attr(&mut attrs, "LANGUAGE"),
+                    default: take_attr(&mut attrs, "DEFAULT").as_deref() == Some("YES"),
+                    autoselect: take_attr(&mut attrs, "AUTOSELECT").as_deref() == Some("YES"),
+                    forced: take_attr(&mut attrs, "FORCED").as_deref() == Some("YES"),
+                    channels: take_attr(&mut attrs, "CHANNELS"),
+                    other_attributes: raw_attributes(attrs),
+                });
+            }
+            _ if tag.starts_with("#EXT") => playlist.other_tags.push(line.to_string()),
+            _ => {}
+        }
+    }
+
+    Ok(playlist)
+}
+
+/// Attribute from an attribute list: name, unquoted value, and whether it
+/// was quoted.
+type Attribute = (String, String, bool);
+
+fn parse_attributes(s: &str) -> Vec<Attribute> {
+    let mut out = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else {
+            break;
+        };
+        let name = name.trim().to_string();
+        if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            out.push((name, quoted[..end].to_string(), true));
+            rest = quoted.get(end + 1..).unwrap_or("");
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            out.push((name, after[..end].trim().to_string(), false));
+            rest = &after[end..];
+        }
+        rest = rest.trim_start_matches(',');
+    }
+    out
+}
+
+fn attr(attrs: &[Attribute], name: &str) -> Option<String> {
+    attrs
+        .iter()
+        .find(|(k, _, _)| k == name)
+        .map(|(_, v, _)| v.clone())
+}
+
+fn take_attr(attrs: &mut Vec<Attribute>, name: &str) -> Option<String> {
+    let pos = attrs.iter().position(|(k, _, _)| k == name)?;
+    Some(attrs.remove(pos).1)
+}
+
+fn raw_attributes(attrs: Vec<Attribute>) -> Vec<(String, String)> {
+    attrs
+        .into_iter()
+        .map(|(k, v, quoted)| {
+            if quoted {
+                (k, format!("\"{v}\""))
+            } else {
+                (k, v)
+            }
+        })
+        .collect()
+}
+
+fn attr_required(line: usize, attrs: &[Attribute], name: &str) -> Result<String, HlsError> {
+    attr(attrs, name).ok_or_else(|| HlsError::new(line, format!("missing {name} attribute")))
+}
+
+fn attr_flag(attrs: &[Attribute], name: &str) -> bool {
+    attr(attrs, name).as_deref() == Some("YES")
+}
+
+fn attr_f64(line: usize, attrs: &[Attribute], name: &str) -> Result<Option<f64>, HlsError> {
+    attr_num(line, attrs, name)
+}
+
+fn attr_num<T: std::str::FromStr>(
+    line: usize,
+    attrs: &[Attribute],
+    name: &str,
+) -> Result<Option<T>, HlsError> {
+    match attr(attrs, name) {
+        Some(v) => parse_num(line, name, &v).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(line: usize, what: &str, value: &str) -> Result<T, HlsError> {
+    value.trim().parse::<T>().map_err(|_| {
+        HlsError::new(
+            line,
+            format!("invalid {}: {}", what.trim_start_matches('#'), value),
+        )
+    })
+}
+
+fn parse_byte_range(line: usize, value: &str) -> Result<ByteRange, HlsError> {
+    value.parse::<ByteRange>().map_err(|mut e| {
+        e.line = line;
+        e
+    })
+}
+
+/// `EXTINF` durations in the same precision ffmpeg's HLS muxer uses.
+fn format_duration(v: f64) -> String {
+    format!("{:.6}", v)
+}
+
+/// Decimal attribute values without trailing zeros (`2`, `0.5`, `1.001`).
+fn format_decimal(v: f64) -> String {
+    let s = format!("{:.6}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    s.to_string()
+}
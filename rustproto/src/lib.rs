@@ -1,13 +1,71 @@
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::os::raw::{c_char, c_int, c_longlong, c_uchar, c_void};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+pub mod dash;
+pub mod hls;
+mod attachments;
+mod color;
+mod compat;
+mod detect;
+mod estimate;
+mod filter_metadata;
+mod fingerprint;
+mod llhls;
+mod loudness;
+mod many;
+mod markers;
+mod output;
+mod parse;
+mod probe_cache;
+mod rational;
+mod records;
+mod remux;
+mod select;
+mod subtitles;
+mod tags;
+mod waveform;
+
+pub use attachments::{extract_attachments, AttachmentKind, ExtractedAttachment};
+pub use color::{
+    ContentLightLevel, DisplayMatrix, DisplayPrimaries, DoviConfiguration, HdrFormat, Luminance,
+    MasteringDisplayMetadata, SideData,
+};
+pub use compat::{
+    encoder_available, Compatibility, PlaybackAction, PlaybackTarget, StreamDecision,
+};
+pub use detect::{
+    analyze_media, detect_black, detect_scenes, detect_silence, BlackOptions, Detection,
+    MediaAnalysis, SceneChange, SceneOptions, SilenceOptions, TimeRange,
+};
+pub use estimate::{scan_packets, Derived, PacketScan, Provenance, StreamScan};
+pub use fingerprint::{
+    media_fingerprint, FingerprintMatch, FingerprintOptions, MediaFingerprint,
+};
+pub use llhls::{run_ll_hls, LlHlsOptions, LlHlsPlaylist, RunEvent};
+pub use loudness::{analyze_loudness, Loudness, LoudnessWindow, ReplayGain};
+pub use many::{ffprobe_many, ffprobe_many_with_options, ProbeBatch};
+pub use markers::{detect_episode_markers, EpisodeMarkers, Marker, MarkerOptions};
+pub use output::{ArtifactRole, OutputFile, RunOutput};
+pub use parse::{LenientParse, ParseDiagnostic};
+pub use probe_cache::ProbeCache;
+pub use rational::rescale;
+pub use records::{
+    ffprobe_records, FrameRecord, PacketRecord, ProbeRecord, RecordOptions, RecordStream,
+};
+pub use remux::{remux, NewChapter, RemuxFormat, RemuxOptions, StreamEdit};
+pub use select::{StreamPreferences, StreamSelection};
+pub use subtitles::SubtitleKind;
+pub use tags::{normalize_language, Disposition};
+pub use waveform::{audio_waveform, Waveform, WaveformOptions};
+
 const AVSEEK_SIZE: i32 = 0x10000;
 const FALLBACK_PATH: &str =
     "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
@@ -19,6 +77,11 @@ pub trait Source: Send + Sync {
         false
     }
     fn cancel(&self) {}
+    /// Stable identity of the content, used as the `ProbeCache` key. Sources
+    /// without one are always probed.
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait ReadSeek: Read + Seek + Send {}
@@ -45,6 +108,21 @@ impl Source for FileSource {
     fn size(&self) -> std::io::Result<i64> {
         Ok(std::fs::metadata(&self.path)?.len() as i64)
     }
+
+    fn fingerprint(&self) -> Option<String> {
+        let meta = std::fs::metadata(&self.path).ok()?;
+        let mtime = meta
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some(format!(
+            "file:{}:{}:{}",
+            self.path,
+            meta.len(),
+            mtime.as_nanos()
+        ))
+    }
 }
 
 struct Registry {
@@ -67,10 +145,6 @@ impl SourceHandle {
     pub fn url(&self) -> String {
         format!("myproto://{}", self.id)
     }
-
-    pub fn cancel(&self) {
-        cancel_source(self.id);
-    }
 }
 
 impl Drop for SourceHandle {
@@ -89,8 +163,8 @@ fn register_source(source: Arc<dyn Source>) -> SourceHandle {
 }
 
 fn cancel_source(id: u64) {
-    let reg = REGISTRY.lock().unwrap();
-    if let Some(source) = reg.sources.get(&id) {
+    let source = REGISTRY.lock().unwrap().sources.get(&id).cloned();
+    if let Some(source) = source {
         source.cancel();
     }
 }
@@ -111,6 +185,7 @@ struct RsProtoCtx {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn rsproto_open(
     uri: *const c_char,
     _flags: c_int,
@@ -125,7 +200,10 @@ pub extern "C" fn rsproto_open(
     let mut streamed = false;
 
     if let Some(id) = parse_id(uri) {
-        if let Some(source_entry) = REGISTRY.lock().unwrap().sources.get(&id).cloned() {
+        // Release the registry before `open`, which may block on I/O; other
+        // runs need the lock to open their own sources.
+        let entry = REGISTRY.lock().unwrap().sources.get(&id).cloned();
+        if let Some(source_entry) = entry {
             match source_entry.open() {
                 Ok(s) => {
                     streamed = source_entry.is_streamed();
@@ -157,6 +235,7 @@ pub extern "C" fn rsproto_open(
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn rsproto_read(ctx: *mut c_void, buf: *mut c_uchar, size: c_int) -> c_int {
     if ctx.is_null() || buf.is_null() || size <= 0 {
         return -1;
@@ -173,6 +252,7 @@ pub extern "C" fn rsproto_read(ctx: *mut c_void, buf: *mut c_uchar, size: c_int)
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn rsproto_seek(ctx: *mut c_void, pos: c_longlong, whence: c_int) -> c_longlong {
     if ctx.is_null() {
         return -1;
@@ -185,12 +265,12 @@ pub extern "C" fn rsproto_seek(ctx: *mut c_void, pos: c_longlong, whence: c_int)
     }
 
     let new_pos = match whence {
-        0 => pos as i64,
+        0 => pos,
         1 => match ctx.handle.stream_position() {
-            Ok(cur) => cur as i64 + pos as i64,
+            Ok(cur) => cur as i64 + pos,
             Err(_) => return -1,
         },
-        2 => ctx.size + pos as i64,
+        2 => ctx.size + pos,
         _ => return -1,
     };
 
@@ -280,6 +360,87 @@ pub struct FfprobeRunOutput {
     pub tempdir: tempfile::TempDir,
     pub stdout: Vec<u8>,
     pub stderr: Vec<u8>,
+    /// Set when the run was stopped through `cancel_with`/`cancel`.
+    pub cancelled: Option<Cancellation>,
+}
+
+/// How a cancelled run should stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelMode {
+    /// Interrupt I/O and the source immediately; outputs may be truncated.
+    Abort,
+    /// Stop consuming input but let muxers flush and write their trailers, so
+    /// HLS/DASH outputs end with a final playlist (`#EXT-X-ENDLIST`).
+    ///
+    /// A run still going after a timeout (`GRACEFUL_STOP_TIMEOUT` unless
+    /// given to `cancel_graceful`), for example because it is blocked reading
+    /// a stalled `Source`, is aborted.
+    Graceful,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cancellation {
+    pub mode: CancelMode,
+    pub reason: String,
+}
+
+type CancelState = Arc<Mutex<Option<Cancellation>>>;
+
+/// How long a graceful stop requested through `cancel_with` may take before
+/// the run is aborted.
+pub const GRACEFUL_STOP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Signalled by the run thread once ffmpeg has returned.
+#[derive(Default)]
+struct RunDone {
+    done: Mutex<bool>,
+    cond: std::sync::Condvar,
+    /// Whether a graceful stop watchdog was started for this run.
+    watchdog: AtomicBool,
+}
+
+impl RunDone {
+    fn set(&self) {
+        *self.done.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.cond.notify_all();
+    }
+
+    fn is_set(&self) -> bool {
+        *self.done.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether the run finished within `timeout`.
+    fn wait_timeout(&self, timeout: std::time::Duration) -> bool {
+        let guard = self.done.lock().unwrap_or_else(|e| e.into_inner());
+        let (guard, _) = self
+            .cond
+            .wait_timeout_while(guard, timeout, |done| !*done)
+            .unwrap_or_else(|e| e.into_inner());
+        *guard
+    }
+}
+
+fn record_cancellation(state: &CancelState, cancellation: Cancellation) -> CancelMode {
+    let mut guard = match state.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    };
+    // Later requests are ignored unless they escalate a graceful stop to an abort.
+    let replace = match guard.as_ref() {
+        None => true,
+        Some(prev) => prev.mode == CancelMode::Graceful && cancellation.mode == CancelMode::Abort,
+    };
+    if replace {
+        *guard = Some(cancellation);
+    }
+    guard.as_ref().map(|c| c.mode).unwrap_or(CancelMode::Abort)
+}
+
+fn current_cancellation(state: &CancelState) -> Option<Cancellation> {
+    match state.lock() {
+        Ok(g) => g.clone(),
+        Err(e) => e.into_inner().clone(),
+    }
 }
 
 extern "C" {
@@ -288,6 +449,9 @@ extern "C" {
         -> *mut FftoolsContext;
     fn ffmpeg_ctx_free(ctx: *mut FftoolsContext);
     fn ffmpeg_ctx_request_exit(ctx: *mut FftoolsContext);
+    fn ffmpeg_ctx_request_stop(ctx: *mut FftoolsContext);
+    fn ffmpeg_ctx_transcode_result(ctx: *mut FftoolsContext) -> c_int;
+    fn avcodec_find_encoder_by_name(name: *const c_char) -> *const c_void;
     fn ffmpeg_run_with_ctx(ctx: *mut FftoolsContext, argc: c_int, argv: *mut *mut c_char)
         -> c_int;
 
@@ -323,6 +487,11 @@ pub struct RunHandle {
     _source: SourceHandle,
     ffmpeg_ctx: Option<std::sync::Arc<FfmpegCtxState>>,
     ffprobe_ctx: Option<std::sync::Arc<FFProbeCtxState>>,
+    cancel_state: CancelState,
+    finished: Arc<RunDone>,
+    watcher: Option<std::thread::JoinHandle<Result<(), String>>>,
+    events: Option<std::sync::mpsc::Receiver<RunEvent>>,
+    ll_hls: Option<LlHlsPlaylist>,
 }
 
 impl RunHandle {
@@ -333,18 +502,29 @@ impl RunHandle {
             .path()
     }
 
-    pub fn wait(self) -> Result<tempfile::TempDir, String> {
+    /// Wait for the run to finish and list the files it produced.
+    pub fn wait(self) -> Result<RunOutput, String> {
         let output = self.wait_with_output()?;
-        Ok(output.tempdir)
+        RunOutput::collect(output.tempdir, output.cancelled)
+            .map_err(|e| format!("ffmpeg_run output listing failed: {e}"))
     }
 
     pub fn wait_with_output(mut self) -> Result<FfprobeRunOutput, String> {
-        if let Some(join) = self.join.take() {
-            match join.join() {
-                Ok(res) => res?,
-                Err(_) => return Err("ffmpeg_run thread panicked".to_string()),
-            }
-        }
+        let res = match self.join.take() {
+            Some(join) => join
+                .join()
+                .unwrap_or_else(|_| Err("ffmpeg_run thread panicked".to_string())),
+            None => Ok(()),
+        };
+        // The watcher finalizes playlists once it sees the run has finished.
+        let packaged = match self.watcher.take() {
+            Some(watcher) => watcher
+                .join()
+                .unwrap_or_else(|_| Err("output watcher panicked".to_string())),
+            None => Ok(()),
+        };
+        res?;
+        packaged?;
         let stdout = self
             .ffprobe_stdout
             .take()
@@ -365,17 +545,31 @@ impl RunHandle {
             tempdir: dir,
             stdout,
             stderr,
+            cancelled: current_cancellation(&self.cancel_state),
         })
     }
 
+    /// Take the receiver for live events. Only runs that package their own
+    /// output, such as `run_ll_hls`, emit events.
+    pub fn events(&mut self) -> Option<std::sync::mpsc::Receiver<RunEvent>> {
+        self.events.take()
+    }
+
+    /// Live LL-HLS playlist of a `run_ll_hls` run, for blocking reloads.
+    pub fn ll_hls(&self) -> Option<&LlHlsPlaylist> {
+        self.ll_hls.as_ref()
+    }
+
     pub fn cancel(&self) {
-        self._source.cancel();
-        if let Some(ctx) = &self.ffmpeg_ctx {
-            unsafe { ffmpeg_ctx_request_exit(ctx.ptr) };
-        }
-        if let Some(ctx) = &self.ffprobe_ctx {
-            unsafe { ffprobe_ctx_request_exit(ctx.ptr) };
-        }
+        self.cancel_handle().cancel();
+    }
+
+    pub fn cancel_with<R: Into<String>>(&self, reason: R, mode: CancelMode) {
+        self.cancel_handle().cancel_with(reason, mode);
+    }
+
+    pub fn cancel_graceful<R: Into<String>>(&self, reason: R, timeout: std::time::Duration) {
+        self.cancel_handle().cancel_graceful(reason, timeout);
     }
 
     pub fn cancel_handle(&self) -> CancelHandle {
@@ -383,11 +577,13 @@ impl RunHandle {
             source_id: self._source.id,
             ffmpeg_ctx: self.ffmpeg_ctx.clone(),
             ffprobe_ctx: self.ffprobe_ctx.clone(),
+            state: Arc::clone(&self.cancel_state),
+            finished: Some(Arc::clone(&self.finished)),
         }
     }
 
     #[cfg(feature = "tokio")]
-    pub async fn wait_async(self) -> Result<tempfile::TempDir, String> {
+    pub async fn wait_async(self) -> Result<RunOutput, String> {
         tokio::task::spawn_blocking(move || self.wait())
             .await
             .map_err(|_| "ffmpeg_run async join failed".to_string())?
@@ -400,6 +596,9 @@ impl Drop for RunHandle {
         if let Some(join) = self.join.take() {
             let _ = join.join();
         }
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
         let _ = self.ffprobe_stdout.take();
         let _ = self.ffprobe_stderr.take();
         let _ = self.ffmpeg_ctx.take();
@@ -407,22 +606,24 @@ impl Drop for RunHandle {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rational {
     pub num: i64,
     pub den: i64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FfprobeOutput {
     pub format: Format,
     #[serde(default)]
     pub streams: Vec<Stream>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
     #[serde(flatten)]
     pub unknown: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Format {
     pub filename: String,
     #[serde(deserialize_with = "deserialize_i64")]
@@ -433,13 +634,29 @@ pub struct Format {
     pub nb_stream_groups: i64,
     pub format_name: String,
     pub format_long_name: String,
-    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub start_time: Option<f64>,
-    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub duration: Option<f64>,
-    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub size: Option<i64>,
-    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub bit_rate: Option<i64>,
     #[serde(deserialize_with = "deserialize_i64")]
     pub probe_score: i64,
@@ -449,7 +666,7 @@ pub struct Format {
     pub unknown: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamCommon {
     #[serde(deserialize_with = "deserialize_i64")]
     pub index: i64,
@@ -459,17 +676,38 @@ pub struct StreamCommon {
     pub codec_name: String,
     pub codec_long_name: String,
     pub profile: String,
-    #[serde(deserialize_with = "deserialize_rational")]
+    #[serde(deserialize_with = "deserialize_rational", serialize_with = "serialize_rational")]
     pub time_base: Rational,
-    #[serde(deserialize_with = "deserialize_rational")]
-    pub avg_frame_rate: Rational,
-    #[serde(deserialize_with = "deserialize_rational")]
-    pub r_frame_rate: Rational,
-    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    /// `None` when ffprobe reports `0/0`, i.e. an unknown rate.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_rational_opt",
+        serialize_with = "serialize_rational_opt"
+    )]
+    pub avg_frame_rate: Option<Rational>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_rational_opt",
+        serialize_with = "serialize_rational_opt"
+    )]
+    pub r_frame_rate: Option<Rational>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub start_time: Option<f64>,
-    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub duration: Option<f64>,
-    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub bit_rate: Option<i64>,
     #[serde(default)]
     pub disposition: HashMap<String, i64>,
@@ -477,7 +715,7 @@ pub struct StreamCommon {
     pub tags: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoStream {
     #[serde(flatten)]
     pub common: StreamCommon,
@@ -488,15 +726,63 @@ pub struct VideoStream {
     pub pix_fmt: String,
     #[serde(deserialize_with = "deserialize_i64")]
     pub level: i64,
-    #[serde(default, deserialize_with = "deserialize_string_opt")]
-    pub sample_aspect_ratio: Option<String>,
-    #[serde(default, deserialize_with = "deserialize_string_opt")]
-    pub display_aspect_ratio: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_rational_opt",
+        serialize_with = "serialize_aspect_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sample_aspect_ratio: Option<Rational>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_rational_opt",
+        serialize_with = "serialize_aspect_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub display_aspect_ratio: Option<Rational>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub field_order: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color_range: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color_space: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color_transfer: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color_primaries: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_known_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub chroma_location: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub side_data_list: Vec<SideData>,
     #[serde(flatten)]
     pub unknown: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioStream {
     #[serde(flatten)]
     pub common: StreamCommon,
@@ -511,19 +797,43 @@ pub struct AudioStream {
     pub unknown: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubtitleStream {
     #[serde(flatten)]
     pub common: StreamCommon,
-    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub width: Option<i64>,
-    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub height: Option<i64>,
     #[serde(flatten)]
     pub unknown: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// A file attached to the container, such as a font for ASS subtitles. The
+/// contents are the stream's extradata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentStream {
+    #[serde(flatten)]
+    pub common: StreamCommon,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_i64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub extradata_size: Option<i64>,
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OtherStream {
     #[serde(flatten)]
     pub common: StreamCommon,
@@ -532,13 +842,56 @@ pub struct OtherStream {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Stream {
     Video(VideoStream),
     Audio(AudioStream),
     Subtitle(SubtitleStream),
+    Attachment(AttachmentStream),
     Other(OtherStream),
 }
 
+impl Stream {
+    pub fn common(&self) -> &StreamCommon {
+        match self {
+            Stream::Video(v) => &v.common,
+            Stream::Audio(a) => &a.common,
+            Stream::Subtitle(s) => &s.common,
+            Stream::Attachment(a) => &a.common,
+            Stream::Other(o) => &o.common,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub id: i64,
+    #[serde(
+        deserialize_with = "deserialize_rational",
+        serialize_with = "serialize_rational"
+    )]
+    pub time_base: Rational,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub start: i64,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub start_time: Option<f64>,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub end: i64,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_f64_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub end_time: Option<f64>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
 impl<'de> Deserialize<'de> for Stream {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -559,6 +912,9 @@ impl<'de> Deserialize<'de> for Stream {
             "subtitle" => serde_json::from_value(value)
                 .map(Stream::Subtitle)
                 .map_err(serde::de::Error::custom),
+            "attachment" => serde_json::from_value(value)
+                .map(Stream::Attachment)
+                .map_err(serde::de::Error::custom),
             _ => serde_json::from_value(value)
                 .map(Stream::Other)
                 .map_err(serde::de::Error::custom),
@@ -566,6 +922,22 @@ impl<'de> Deserialize<'de> for Stream {
     }
 }
 
+impl Serialize for Stream {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // `codec_type` lives in `StreamCommon`, which tags the stream on the way back in.
+        match self {
+            Stream::Video(v) => v.serialize(serializer),
+            Stream::Audio(a) => a.serialize(serializer),
+            Stream::Subtitle(s) => s.serialize(serializer),
+            Stream::Attachment(a) => a.serialize(serializer),
+            Stream::Other(o) => o.serialize(serializer),
+        }
+    }
+}
+
 fn deserialize_i64<'de, D>(deserializer: D) -> Result<i64, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -629,6 +1001,14 @@ where
     }
 }
 
+/// Like `deserialize_string_opt`, also treating ffprobe's `unknown` as unset.
+fn deserialize_known_opt<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(deserialize_string_opt(deserializer)?.filter(|s| s != "unknown"))
+}
+
 fn deserialize_f64_opt<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -669,11 +1049,47 @@ where
     }
 }
 
+fn serialize_rational<S>(value: &Rational, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(&format_args!("{}/{}", value.num, value.den))
+}
+
+/// Zero rationals (`0/0` frame rates, `0:1` aspect ratios) and `N/A` are
+/// how ffprobe spells "unknown", so they map to `None`.
+fn deserialize_rational_opt<'de, D>(deserializer: D) -> Result<Option<Rational>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    let rational = match value {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::String(s)) if s == "N/A" => return Ok(None),
+        Some(value) => deserialize_rational(value).map_err(serde::de::Error::custom)?,
+    };
+    Ok(Some(rational).filter(|r| r.num != 0 && r.den != 0))
+}
+
+fn serialize_rational_opt<S>(value: &Option<Rational>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serialize_rational(&value.unwrap_or(Rational::new(0, 0)), serializer)
+}
+
+fn serialize_aspect_opt<S>(value: &Option<Rational>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match value {
+        Some(r) => serializer.collect_str(&format_args!("{}:{}", r.num, r.den)),
+        None => serializer.serialize_none(),
+    }
+}
+
 fn parse_rational(s: &str) -> Option<Rational> {
-    let (num_str, den_str) = s.split_once('/')?;
-    let num = num_str.parse::<i64>().ok()?;
-    let den = den_str.parse::<i64>().ok()?;
-    Some(Rational { num, den })
+    s.parse().ok()
 }
 
 #[derive(Clone)]
@@ -681,18 +1097,70 @@ pub struct CancelHandle {
     source_id: u64,
     ffmpeg_ctx: Option<std::sync::Arc<FfmpegCtxState>>,
     ffprobe_ctx: Option<std::sync::Arc<FFProbeCtxState>>,
+    state: CancelState,
+    /// Set once the run is over; `None` for runs that can't stop gracefully.
+    finished: Option<Arc<RunDone>>,
 }
 
 impl CancelHandle {
     pub fn cancel(&self) {
-        cancel_source(self.source_id);
-        if let Some(ctx) = &self.ffmpeg_ctx {
-            unsafe { ffmpeg_ctx_request_exit(ctx.ptr) };
-        }
-        if let Some(ctx) = &self.ffprobe_ctx {
-            unsafe { ffprobe_ctx_request_exit(ctx.ptr) };
+        self.cancel_with("cancelled", CancelMode::Abort);
+    }
+
+    /// Cancel the run, recording `reason` so it is reported back in
+    /// `RunOutput::cancelled` (or in the error of `RunHandle::wait` on abort).
+    ///
+    /// `CancelMode::Graceful` only applies to ffmpeg runs; ffprobe runs are
+    /// always aborted. A graceful stop is aborted after
+    /// `GRACEFUL_STOP_TIMEOUT`.
+    pub fn cancel_with<R: Into<String>>(&self, reason: R, mode: CancelMode) {
+        self.cancel_impl(reason.into(), mode, GRACEFUL_STOP_TIMEOUT);
+    }
+
+    /// Stop gracefully, aborting the run if it is still going after
+    /// `timeout`. Only the first graceful stop of a run sets the deadline.
+    pub fn cancel_graceful<R: Into<String>>(&self, reason: R, timeout: std::time::Duration) {
+        self.cancel_impl(reason.into(), CancelMode::Graceful, timeout);
+    }
+
+    fn cancel_impl(&self, reason: String, mode: CancelMode, timeout: std::time::Duration) {
+        let mode = record_cancellation(&self.state, Cancellation { mode, reason });
+        match (mode, &self.ffmpeg_ctx, &self.finished) {
+            (CancelMode::Graceful, Some(ctx), Some(finished)) => {
+                unsafe { ffmpeg_ctx_request_stop(ctx.ptr) };
+                if !finished.watchdog.swap(true, Ordering::AcqRel) {
+                    self.abort_after(timeout, Arc::clone(finished));
+                }
+            }
+            _ => {
+                cancel_source(self.source_id);
+                if let Some(ctx) = &self.ffmpeg_ctx {
+                    unsafe { ffmpeg_ctx_request_exit(ctx.ptr) };
+                }
+                if let Some(ctx) = &self.ffprobe_ctx {
+                    unsafe { ffprobe_ctx_request_exit(ctx.ptr) };
+                }
+            }
         }
     }
+
+    /// Stopping gracefully waits for the demuxers, which can't be interrupted
+    /// while a `Source` blocks in `read`; only `Source::cancel` unblocks them.
+    fn abort_after(&self, timeout: std::time::Duration, finished: Arc<RunDone>) {
+        let handle = self.clone();
+        std::thread::spawn(move || {
+            if finished.wait_timeout(timeout) {
+                return;
+            }
+            let reason = current_cancellation(&handle.state)
+                .map(|c| c.reason)
+                .unwrap_or_default();
+            handle.cancel_with(
+                format!("{} (graceful stop timed out)", reason),
+                CancelMode::Abort,
+            );
+        });
+    }
 }
 
 fn prepare_run<S: Source + 'static>(
@@ -747,6 +1215,7 @@ pub const FFPROBE_ARGS: &[&str] = &[
     "json",
     "-show_format",
     "-show_streams",
+    "-show_chapters",
     "-print_filename",
     "input",
     "-i",
@@ -768,14 +1237,101 @@ impl std::fmt::Display for FfprobeError {
 
 impl std::error::Error for FfprobeError {}
 
+/// Limits for a quick probe, e.g. over the part of a torrent that has been
+/// downloaded so far. `None` keeps ffprobe's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbeOptions {
+    /// Maximum bytes read to detect streams (`-probesize`).
+    pub probesize: Option<u64>,
+    /// Maximum microseconds of input analyzed (`-analyzeduration`).
+    pub analyzeduration: Option<u64>,
+    /// Frames used to estimate frame rates (`-fpsprobesize`).
+    pub fpsprobesize: Option<i64>,
+    /// Do not read packets to estimate the duration from timestamps.
+    pub skip_estimate_duration_from_pts: bool,
+    /// Return whatever streams were identified instead of failing when
+    /// required fields are missing or `N/A`, or when ffprobe exits with an
+    /// error after printing its output.
+    pub best_effort: bool,
+}
+
+impl ProbeOptions {
+    /// Small limits with best effort parsing, for a first probe.
+    pub fn fast() -> Self {
+        Self {
+            probesize: Some(512 * 1024),
+            analyzeduration: Some(500_000),
+            fpsprobesize: None,
+            skip_estimate_duration_from_pts: true,
+            best_effort: true,
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(v) = self.probesize {
+            args.extend(["-probesize".to_string(), v.to_string()]);
+        }
+        if let Some(v) = self.analyzeduration {
+            args.extend(["-analyzeduration".to_string(), v.to_string()]);
+        }
+        if let Some(v) = self.fpsprobesize {
+            args.extend(["-fpsprobesize".to_string(), v.to_string()]);
+        }
+        if self.skip_estimate_duration_from_pts {
+            args.extend([
+                "-skip_estimate_duration_from_pts".to_string(),
+                "1".to_string(),
+            ]);
+        }
+        args
+    }
+}
+
 pub async fn ffprobe<S: Source + 'static>(source: S) -> Result<FfprobeOutput, FfprobeError> {
-    ffprobe_blocking(source)
+    ffprobe_blocking(source, &ProbeOptions::default())
+}
+
+pub async fn ffprobe_with_options<S: Source + 'static>(
+    source: S,
+    options: &ProbeOptions,
+) -> Result<FfprobeOutput, FfprobeError> {
+    ffprobe_blocking(source, options)
+}
+
+/// Like `ffprobe`, but answers from `cache` when the source has a
+/// fingerprint that was probed before, and stores fresh results in it.
+pub async fn ffprobe_cached<S: Source + 'static>(
+    source: S,
+    cache: &ProbeCache,
+) -> Result<FfprobeOutput, FfprobeError> {
+    let key = source.fingerprint();
+    if let Some(hit) = key.as_deref().and_then(|k| cache.get(k)) {
+        return Ok(hit);
+    }
+    let parsed = ffprobe_blocking(source, &ProbeOptions::default())?;
+    if let Some(key) = key {
+        cache.insert(key, parsed.clone());
+    }
+    Ok(parsed)
 }
 
-fn ffprobe_blocking<S: Source + 'static>(source: S) -> Result<FfprobeOutput, FfprobeError> {
-    let args = ffprobe_args();
-    let capture = match run_ffprobe_capture(source, &args) {
+fn ffprobe_blocking<S: Source + 'static>(
+    source: S,
+    options: &ProbeOptions,
+) -> Result<FfprobeOutput, FfprobeError> {
+    let args = ffprobe_args(options);
+    parse_capture(run_ffprobe_capture(source, &args), args, options)
+}
+
+fn parse_capture(
+    capture: Result<FfprobeCapture, (String, FfprobeCapture)>,
+    args: Vec<String>,
+    options: &ProbeOptions,
+) -> Result<FfprobeOutput, FfprobeError> {
+    let capture = match capture {
         Ok(capture) => capture,
+        Err((_, capture)) if options.best_effort && !capture.stdout.is_empty() => capture,
         Err((message, capture)) => {
             return Err(FfprobeError {
                 message,
@@ -785,17 +1341,24 @@ fn ffprobe_blocking<S: Source + 'static>(source: S) -> Result<FfprobeOutput, Ffp
         }
     };
 
-    let parsed = serde_json::from_slice(&capture.stdout).map_err(|e| FfprobeError {
+    let parsed = if options.best_effort {
+        FfprobeOutput::from_json_lenient(&capture.stdout).map(|parsed| parsed.output)
+    } else {
+        FfprobeOutput::from_json_strict(&capture.stdout).map_err(|d| d.to_string())
+    };
+    parsed.map_err(|e| FfprobeError {
         message: format!("ffprobe json parse: {e}"),
         stderr: capture.stderr,
         args,
-    })?;
-
-    Ok(parsed)
+    })
 }
 
-fn ffprobe_args() -> Vec<String> {
-    FFPROBE_ARGS.iter().map(|arg| (*arg).to_string()).collect()
+fn ffprobe_args(options: &ProbeOptions) -> Vec<String> {
+    let mut args: Vec<String> = FFPROBE_ARGS.iter().map(|arg| (*arg).to_string()).collect();
+    // Format options have to precede `-i`.
+    let input_at = args.len() - 2;
+    args.splice(input_at..input_at, options.args());
+    args
 }
 
 struct FfprobeCapture {
@@ -806,6 +1369,25 @@ struct FfprobeCapture {
 fn run_ffprobe_capture<S: Source + 'static>(
     source: S,
     args: &[String],
+) -> Result<FfprobeCapture, (String, FfprobeCapture)> {
+    let ctx = unsafe { ffprobe_ctx_create() };
+    if ctx.is_null() {
+        return Err((
+            "ffprobe_ctx_create failed".to_string(),
+            FfprobeCapture {
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+        ));
+    }
+    run_ffprobe_capture_in(&FFProbeCtxState { ptr: ctx }, source, args)
+}
+
+/// Like `run_ffprobe_capture`, on a context that may be reused for later runs.
+fn run_ffprobe_capture_in<S: Source + 'static>(
+    ctx_state: &FFProbeCtxState,
+    source: S,
+    args: &[String],
 ) -> Result<FfprobeCapture, (String, FfprobeCapture)> {
     let (_dir, handle, replaced) = match prepare_run(source, args) {
         Ok(v) => v,
@@ -819,16 +1401,6 @@ fn run_ffprobe_capture<S: Source + 'static>(
             ))
         }
     };
-    let ctx = unsafe { ffprobe_ctx_create() };
-    if ctx.is_null() {
-        return Err((
-            "ffprobe_ctx_create failed".to_string(),
-            FfprobeCapture {
-                stdout: Vec::new(),
-                stderr: Vec::new(),
-            },
-        ));
-    }
 
     let stdout_capture = Box::new(CaptureBuffer::new());
     let stderr_capture = Box::new(CaptureBuffer::new());
@@ -836,7 +1408,7 @@ fn run_ffprobe_capture<S: Source + 'static>(
     let stderr_ptr = stderr_capture.as_ref() as *const CaptureBuffer as *mut c_void;
     unsafe {
         ffprobe_ctx_set_output(
-            ctx,
+            ctx_state.ptr,
             Some(capture_write),
             stdout_ptr,
             Some(capture_write),
@@ -844,36 +1416,29 @@ fn run_ffprobe_capture<S: Source + 'static>(
         );
     }
 
-    let ctx_state = FFProbeCtxState { ptr: ctx };
+    let ret = ffprobe_exec(ctx_state, &replaced).map_err(|message| {
+        (
+            message,
+            FfprobeCapture {
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+        )
+    })?;
 
-    let mut cstrings: Vec<CString> = Vec::with_capacity(replaced.len());
-    for arg in &replaced {
-        cstrings.push(
-            CString::new(arg.as_bytes())
-                .map_err(|_| format!("arg contains null byte: {}", arg))
-                .map_err(|message| {
-                    (
-                        message,
-                        FfprobeCapture {
-                            stdout: Vec::new(),
-                            stderr: Vec::new(),
-                        },
-                    )
-                })?,
+    // The buffers are freed below; a reused context must not write to them.
+    unsafe {
+        ffprobe_ctx_set_output(
+            ctx_state.ptr,
+            None,
+            std::ptr::null_mut(),
+            None,
+            std::ptr::null_mut(),
         );
     }
-
-    let mut argv: Vec<*mut c_char> = cstrings
-        .iter()
-        .map(|s| s.as_ptr() as *mut c_char)
-        .collect();
-
-    let ret = unsafe { ffprobe_run_with_ctx(ctx_state.ptr, argv.len() as c_int, argv.as_mut_ptr(), 0, 0) };
-
     let stdout = stdout_capture.into_inner();
     let stderr = stderr_capture.into_inner();
 
-    drop(ctx_state);
     drop(handle);
 
     if ret == 0 {
@@ -886,6 +1451,21 @@ fn run_ffprobe_capture<S: Source + 'static>(
     }
 }
 
+/// Run ffprobe on `ctx` with `args`, whose placeholders are already replaced.
+fn ffprobe_exec(ctx: &FFProbeCtxState, args: &[String]) -> Result<c_int, String> {
+    let mut cstrings: Vec<CString> = Vec::with_capacity(args.len());
+    for arg in args {
+        cstrings.push(
+            CString::new(arg.as_bytes()).map_err(|_| format!("arg contains null byte: {}", arg))?,
+        );
+    }
+    let mut argv: Vec<*mut c_char> = cstrings
+        .iter()
+        .map(|s| s.as_ptr() as *mut c_char)
+        .collect();
+    Ok(unsafe { ffprobe_run_with_ctx(ctx.ptr, argv.len() as c_int, argv.as_mut_ptr(), 0, 0) })
+}
+
 /// Run ffmpeg in-process with a temporary output directory.
 ///
 /// The `args` must include `{input}` which will be replaced with a
@@ -908,28 +1488,14 @@ pub fn run_ffmpeg<S: Source + 'static>(
     }
     let ctx_arc = std::sync::Arc::new(FfmpegCtxState { ptr: ctx });
     let ctx_for_thread = std::sync::Arc::clone(&ctx_arc);
+    let cancel_state: CancelState = Arc::new(Mutex::new(None));
+    let cancel_for_thread = Arc::clone(&cancel_state);
+    let finished = Arc::new(RunDone::default());
+    let finished_for_thread = Arc::clone(&finished);
     let join = std::thread::spawn(move || {
-        let mut cstrings: Vec<CString> = Vec::with_capacity(replaced.len());
-        for arg in &replaced {
-            cstrings.push(
-                CString::new(arg.as_bytes())
-                    .map_err(|_| format!("arg contains null byte: {}", arg))?,
-            );
-        }
-
-        let mut argv: Vec<*mut c_char> = cstrings
-            .iter()
-            .map(|s| s.as_ptr() as *mut c_char)
-            .collect();
-
-        let ret = unsafe {
-            ffmpeg_run_with_ctx(ctx_for_thread.ptr, argv.len() as c_int, argv.as_mut_ptr())
-        };
-        if ret == 0 {
-            Ok(())
-        } else {
-            Err(format!("ffmpeg_run failed: {}", ret))
-        }
+        let res = run_ffmpeg_thread(&ctx_for_thread, &replaced, &cancel_for_thread);
+        finished_for_thread.set();
+        res
     });
 
     Ok(RunHandle {
@@ -940,5 +1506,46 @@ pub fn run_ffmpeg<S: Source + 'static>(
         _source: handle,
         ffmpeg_ctx: Some(ctx_arc),
         ffprobe_ctx: None,
+        cancel_state,
+        finished,
+        watcher: None,
+        events: None,
+        ll_hls: None,
     })
 }
+
+fn run_ffmpeg_thread(
+    ctx: &FfmpegCtxState,
+    replaced: &[String],
+    cancel_state: &CancelState,
+) -> Result<(), String> {
+    let mut cstrings: Vec<CString> = Vec::with_capacity(replaced.len());
+    for arg in replaced {
+        cstrings.push(
+            CString::new(arg.as_bytes()).map_err(|_| format!("arg contains null byte: {}", arg))?,
+        );
+    }
+
+    let mut argv: Vec<*mut c_char> = cstrings
+        .iter()
+        .map(|s| s.as_ptr() as *mut c_char)
+        .collect();
+
+    let ret = unsafe { ffmpeg_run_with_ctx(ctx.ptr, argv.len() as c_int, argv.as_mut_ptr()) };
+    match (ret, current_cancellation(cancel_state)) {
+        (0, _) => Ok(()),
+        // ffmpeg exits with 255 after a signal even when the trailers were
+        // written, so look at what transcoding itself returned.
+        (255, Some(c)) if c.mode == CancelMode::Graceful => {
+            match unsafe { ffmpeg_ctx_transcode_result(ctx.ptr) } {
+                code if code >= 0 => Ok(()),
+                code => Err(format!(
+                    "ffmpeg_run failed after graceful stop ({}): {}",
+                    c.reason, code
+                )),
+            }
+        }
+        (_, Some(c)) => Err(format!("ffmpeg_run cancelled: {}", c.reason)),
+        (ret, None) => Err(format!("ffmpeg_run failed: {}", ret)),
+    }
+}
@@ -0,0 +1,533 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::hls::{self, MediaPlaylist, Part, PreloadHint, PreloadHintType, ServerControl};
+use crate::{run_ffmpeg, CancelHandle, CancelMode, RunDone, RunHandle, Source};
+
+/// Playlist ffmpeg writes one entry per part into. Removed once the run ends.
+const PARTS_PLAYLIST: &str = "parts.m3u8";
+/// The LL-HLS playlist served to players.
+pub const LL_HLS_PLAYLIST: &str = "index.m3u8";
+const INIT_SEGMENT: &str = "init.mp4";
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone)]
+pub struct LlHlsOptions {
+    /// Target duration of a partial segment in seconds (`EXT-X-PART-INF`).
+    pub part_target: f64,
+    /// Duration of a full segment in seconds, rounded up for
+    /// `EXT-X-TARGETDURATION`. Segments start on an independent part when one
+    /// arrives in time, and are cut before they would run past the target.
+    pub segment_target: f64,
+    /// Codec arguments placed between the input and the HLS muxer options.
+    pub codec_args: Vec<String>,
+}
+
+impl Default for LlHlsOptions {
+    fn default() -> Self {
+        Self {
+            part_target: 0.5,
+            segment_target: 4.0,
+            codec_args: ["-c:v", "copy", "-c:a", "aac", "-b:a", "128k", "-ac", "2"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Progress of a run that packages its own output, in the order it happens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunEvent {
+    /// A partial segment was written and added to the playlist.
+    PartComplete {
+        msn: u64,
+        part: u64,
+        uri: String,
+        duration: f64,
+        independent: bool,
+    },
+    /// The parts of media sequence number `msn` were joined into a full segment.
+    SegmentComplete {
+        msn: u64,
+        uri: String,
+        duration: f64,
+    },
+    /// Packaging failed and the run was aborted; no more events follow.
+    Error(String),
+    /// The playlist got its `EXT-X-ENDLIST`; no more events follow.
+    Finished,
+}
+
+struct LlState {
+    playlist: MediaPlaylist,
+    finished: bool,
+}
+
+/// The live `index.m3u8` of a `run_ll_hls` run.
+///
+/// Cheap to clone; every clone sees the same playlist.
+#[derive(Clone)]
+pub struct LlHlsPlaylist {
+    inner: Arc<(Mutex<LlState>, Condvar)>,
+}
+
+impl LlHlsPlaylist {
+    fn new(playlist: MediaPlaylist) -> Self {
+        Self {
+            inner: Arc::new((
+                Mutex::new(LlState {
+                    playlist,
+                    finished: false,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+
+    pub fn playlist(&self) -> MediaPlaylist {
+        self.inner.0.lock().unwrap().playlist.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inner.0.lock().unwrap().finished
+    }
+
+    /// Blocking playlist reload (`_HLS_msn` / `_HLS_part`).
+    ///
+    /// Waits until the playlist contains segment `msn` or, with `part`, that
+    /// part of it. Returns the playlist early if the run finished, and `None`
+    /// on timeout.
+    pub fn blocking_reload(
+        &self,
+        msn: u64,
+        part: Option<u64>,
+        timeout: Duration,
+    ) -> Option<MediaPlaylist> {
+        let deadline = Instant::now() + timeout;
+        let (lock, cvar) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if state.finished || contains(&state.playlist, msn, part) {
+                return Some(state.playlist.clone());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            state = cvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    fn publish(&self, playlist: &MediaPlaylist, finished: bool) {
+        let (lock, cvar) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        state.playlist = playlist.clone();
+        state.finished = finished;
+        cvar.notify_all();
+    }
+}
+
+fn contains(playlist: &MediaPlaylist, msn: u64, part: Option<u64>) -> bool {
+    let next_msn = playlist.media_sequence + playlist.segments.len() as u64;
+    match part {
+        None => msn < next_msn,
+        Some(part) => {
+            msn < next_msn || (msn == next_msn && part < playlist.trailing_parts.len() as u64)
+        }
+    }
+}
+
+/// Package `source` as low-latency HLS.
+///
+/// ffmpeg writes fMP4 parts of `part_target` seconds; a watcher thread
+/// groups them into segments and keeps `index.m3u8` in the output directory
+/// up to date. Progress is reported through `RunHandle::events` and the
+/// playlist can be reloaded through `RunHandle::ll_hls`.
+pub fn run_ll_hls<S: Source + 'static>(
+    source: S,
+    options: &LlHlsOptions,
+) -> Result<RunHandle, String> {
+    if !options.part_target.is_finite()
+        || !options.segment_target.is_finite()
+        || options.part_target <= 0.0
+        || options.segment_target < options.part_target
+    {
+        return Err("invalid LL-HLS targets".to_string());
+    }
+    let mut args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-i",
+        "{input}",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    args.extend(options.codec_args.iter().cloned());
+    args.extend([
+        "-f".to_string(),
+        "hls".to_string(),
+        "-hls_time".to_string(),
+        format!("{}", options.part_target),
+        "-hls_list_size".to_string(),
+        "0".to_string(),
+        "-hls_playlist_type".to_string(),
+        "event".to_string(),
+        "-hls_flags".to_string(),
+        "split_by_time".to_string(),
+        "-hls_segment_type".to_string(),
+        "fmp4".to_string(),
+        "-hls_fmp4_init_filename".to_string(),
+        INIT_SEGMENT.to_string(),
+        "-hls_segment_filename".to_string(),
+        "{outdir}/part_%05d.m4s".to_string(),
+        format!("{{outdir}}/{}", PARTS_PLAYLIST),
+    ]);
+    let mut handle = run_ffmpeg(source, &args)?;
+
+    let outdir = handle
+        .tempdir
+        .as_ref()
+        .map(|d| d.path().to_path_buf())
+        .ok_or_else(|| "run has no output directory".to_string())?;
+    let (tx, rx) = mpsc::channel();
+    let packager = Packager::new(outdir, options, tx, handle.cancel_handle());
+    let shared = packager.shared.clone();
+    let finished = Arc::clone(&handle.finished);
+    handle.watcher = Some(std::thread::spawn(move || packager.run(&finished)));
+    handle.events = Some(rx);
+    handle.ll_hls = Some(shared);
+    Ok(handle)
+}
+
+struct Packager {
+    outdir: PathBuf,
+    segment_target: f64,
+    playlist: MediaPlaylist,
+    cancel: CancelHandle,
+    /// Files of the parts in `playlist.trailing_parts`.
+    pending: Vec<PathBuf>,
+    parts_seen: usize,
+    tx: mpsc::Sender<RunEvent>,
+    shared: LlHlsPlaylist,
+}
+
+impl Packager {
+    fn new(
+        outdir: PathBuf,
+        options: &LlHlsOptions,
+        tx: mpsc::Sender<RunEvent>,
+        cancel: CancelHandle,
+    ) -> Self {
+        let playlist = MediaPlaylist {
+            version: Some(9),
+            target_duration: options.segment_target.ceil() as u64,
+            playlist_type: Some(hls::PlaylistType::Event),
+            server_control: Some(ServerControl {
+                can_block_reload: true,
+                can_skip_until: None,
+                hold_back: None,
+                part_hold_back: Some(options.part_target * 3.0),
+            }),
+            part_target: Some(options.part_target),
+            ..Default::default()
+        };
+        Self {
+            outdir,
+            segment_target: options.segment_target,
+            cancel,
+            shared: LlHlsPlaylist::new(playlist.clone()),
+            playlist,
+            pending: Vec::new(),
+            parts_seen: 0,
+            tx,
+        }
+    }
+
+    fn run(mut self, finished: &RunDone) -> Result<(), String> {
+        let result = self.package(finished);
+        if let Err(err) = &result {
+            let err = format!("ll-hls: {}", err);
+            // Stop ffmpeg, and release blocking reloads waiting for parts
+            // that will never be listed.
+            self.cancel.cancel_with(err.clone(), CancelMode::Abort);
+            self.shared.publish(&self.playlist, true);
+            let _ = self.tx.send(RunEvent::Error(err.clone()));
+            return Err(err);
+        }
+        result
+    }
+
+    fn package(&mut self, finished: &RunDone) -> Result<(), String> {
+        loop {
+            // Read the flag first so the last poll sees everything ffmpeg wrote.
+            let done = finished.is_set();
+            self.poll()?;
+            if done {
+                break;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        self.finish()
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let text = match fs::read_to_string(self.outdir.join(PARTS_PLAYLIST)) {
+            Ok(text) => text,
+            Err(_) => return Ok(()),
+        };
+        // ffmpeg rewrites the playlist in place; a partial read fails to
+        // parse or lists fewer parts and is picked up on the next poll.
+        let parts = match MediaPlaylist::parse(&text) {
+            Ok(parts) => parts,
+            Err(_) => return Ok(()),
+        };
+        if parts.segments.len() <= self.parts_seen {
+            return Ok(());
+        }
+        for entry in &parts.segments[self.parts_seen..] {
+            self.push_part(&entry.uri, entry.duration)?;
+        }
+        self.parts_seen = parts.segments.len();
+        self.publish(false)
+    }
+
+    fn push_part(&mut self, uri: &str, duration: f64) -> Result<(), String> {
+        let name = Path::new(uri)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("invalid part uri: {}", uri))?;
+        let path = self.outdir.join(&name);
+        let data = fs::read(&path).map_err(|e| format!("read {}: {}", name, e))?;
+        let independent = fragment_is_independent(&data);
+
+        let target = self.playlist.target_duration;
+        if exceeds(duration, target) {
+            return Err(format!(
+                "part {} lasts {:.3}s, longer than the {}s target duration",
+                name, duration, target
+            ));
+        }
+        let pending_duration: f64 = self
+            .playlist
+            .trailing_parts
+            .iter()
+            .map(|p| p.duration)
+            .sum();
+        if (independent && pending_duration >= self.segment_target)
+            || exceeds(pending_duration + duration, target)
+        {
+            self.close_segment()?;
+        }
+
+        let msn = self.playlist.media_sequence + self.playlist.segments.len() as u64;
+        let part = self.playlist.trailing_parts.len() as u64;
+        self.playlist.trailing_parts.push(Part {
+            uri: name.clone(),
+            duration,
+            independent,
+            byte_range: None,
+            gap: false,
+        });
+        self.pending.push(path);
+        let _ = self.tx.send(RunEvent::PartComplete {
+            msn,
+            part,
+            uri: name,
+            duration,
+            independent,
+        });
+        Ok(())
+    }
+
+    fn close_segment(&mut self) -> Result<(), String> {
+        if self.playlist.trailing_parts.is_empty() {
+            return Ok(());
+        }
+        let msn = self.playlist.media_sequence + self.playlist.segments.len() as u64;
+        let uri = format!("seg_{:05}.m4s", msn);
+        let mut data = Vec::new();
+        for path in self.pending.drain(..) {
+            data.extend(fs::read(&path).map_err(|e| format!("read {}: {}", path.display(), e))?);
+        }
+        write_atomic(&self.outdir.join(&uri), &data)?;
+
+        let parts = std::mem::take(&mut self.playlist.trailing_parts);
+        let mut segment =
+            hls::MediaSegment::new(uri.clone(), parts.iter().map(|p| p.duration).sum());
+        segment.parts = parts;
+        let duration = segment.duration;
+        self.playlist.segments.push(segment);
+        let _ = self
+            .tx
+            .send(RunEvent::SegmentComplete { msn, uri, duration });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.close_segment()?;
+        self.playlist.end_list = true;
+        self.publish(true)?;
+        let _ = fs::remove_file(self.outdir.join(PARTS_PLAYLIST));
+        let _ = self.tx.send(RunEvent::Finished);
+        Ok(())
+    }
+
+    fn publish(&mut self, finished: bool) -> Result<(), String> {
+        // The map has to precede the first part, which exists before the
+        // first full segment does.
+        let map = hls::Map {
+            uri: INIT_SEGMENT.to_string(),
+            byte_range: None,
+        };
+        self.playlist.other_tags.clear();
+        match self.playlist.segments.first_mut() {
+            Some(first) => first.map = Some(map),
+            None => self
+                .playlist
+                .other_tags
+                .push(format!("#EXT-X-MAP:URI=\"{}\"", map.uri)),
+        }
+        self.playlist.preload_hints.clear();
+        if !finished {
+            self.playlist.preload_hints.push(PreloadHint {
+                hint_type: PreloadHintType::Part,
+                uri: format!("part_{:05}.m4s", self.parts_seen),
+                byte_range_start: None,
+                byte_range_length: None,
+            });
+        }
+        write_atomic(
+            &self.outdir.join(LL_HLS_PLAYLIST),
+            self.playlist.to_string().as_bytes(),
+        )?;
+        self.shared.publish(&self.playlist, finished);
+        Ok(())
+    }
+}
+
+/// Whether a segment of `duration` breaks `EXT-X-TARGETDURATION`, which may
+/// not change during a live playlist. Durations are compared rounded to the
+/// nearest integer, as RFC 8216 specifies.
+fn exceeds(duration: f64, target: u64) -> bool {
+    duration.round() > target as f64
+}
+
+/// Write through a temporary file so readers never see a partial file.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut f = fs::File::create(&tmp).map_err(|e| format!("create {}: {}", tmp.display(), e))?;
+    f.write_all(data)
+        .map_err(|e| format!("write {}: {}", tmp.display(), e))?;
+    drop(f);
+    fs::rename(&tmp, path).map_err(|e| format!("rename {}: {}", tmp.display(), e))
+}
+
+/// Whether an fMP4 fragment starts with a sync sample in every track.
+///
+/// Looks at the first sample flags of the first `moof`; fragments that
+/// cannot be parsed count as independent.
+fn fragment_is_independent(data: &[u8]) -> bool {
+    let Some(moof) = boxes(data).find(|(t, _)| t == b"moof").map(|(_, b)| b) else {
+        return true;
+    };
+    for (_, traf) in boxes(moof).filter(|(t, _)| t == b"traf") {
+        let mut default_flags = None;
+        let mut first_flags = None;
+        for (kind, body) in boxes(traf) {
+            match &kind {
+                b"tfhd" => default_flags = tfhd_default_sample_flags(body),
+                b"trun" if first_flags.is_none() => first_flags = trun_first_sample_flags(body),
+                _ => {}
+            }
+        }
+        // sample_is_non_sync_sample
+        if first_flags
+            .or(default_flags)
+            .is_some_and(|f| f & 0x10000 != 0)
+        {
+            return false;
+        }
+    }
+    true
+}
+
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        let size = read_u32(data, 0)? as u64;
+        let kind = [data[4], data[5], data[6], data[7]];
+        let (header, size) = match size {
+            0 => (8, data.len() as u64),
+            1 => (16, read_u64(data, 8)?),
+            n => (8, n),
+        };
+        if size < header || size > data.len() as u64 {
+            return None;
+        }
+        let body = &data[header as usize..size as usize];
+        data = &data[size as usize..];
+        Some((kind, body))
+    })
+}
+
+fn tfhd_default_sample_flags(body: &[u8]) -> Option<u32> {
+    let flags = read_u32(body, 0)? & 0xffffff;
+    if flags & 0x20 == 0 {
+        return None;
+    }
+    // track_ID, then the optional fields before default_sample_flags.
+    let mut pos = 8;
+    for (bit, len) in [(0x1, 8), (0x2, 4), (0x8, 4), (0x10, 4)] {
+        if flags & bit != 0 {
+            pos += len;
+        }
+    }
+    read_u32(body, pos)
+}
+
+fn trun_first_sample_flags(body: &[u8]) -> Option<u32> {
+    let flags = read_u32(body, 0)? & 0xffffff;
+    if read_u32(body, 4)? == 0 {
+        return None;
+    }
+    let mut pos = 8;
+    if flags & 0x1 != 0 {
+        pos += 4;
+    }
+    if flags & 0x4 != 0 {
+        return read_u32(body, pos);
+    }
+    if flags & 0x400 != 0 {
+        // Per-sample flags of the first sample follow its duration and size.
+        for bit in [0x100, 0x200] {
+            if flags & bit != 0 {
+                pos += 4;
+            }
+        }
+        return read_u32(body, pos);
+    }
+    None
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], pos: usize) -> Option<u64> {
+    let bytes = data.get(pos..pos + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
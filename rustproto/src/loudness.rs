@@ -0,0 +1,147 @@
+use crate::filter_metadata::{read_metadata_print, FrameMetadata};
+use crate::{run_ffmpeg, Source};
+
+/// EBU R128 and ReplayGain measurements of one audio stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness in LUFS.
+    pub integrated: f64,
+    /// Loudness range in LU.
+    pub loudness_range: f64,
+    /// Low and high ends of the loudness range in LUFS.
+    pub lra_low: f64,
+    pub lra_high: f64,
+    /// Highest true peak over all channels in dBTP.
+    pub true_peak: f64,
+    /// Highest sample peak over all channels in dBFS.
+    pub sample_peak: f64,
+    pub replaygain: ReplayGain,
+    /// Measurements every 100 ms, in stream order.
+    pub windows: Vec<LoudnessWindow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayGain {
+    /// Gain in dB to bring the track to the 89 dB reference level.
+    pub track_gain: f64,
+    /// Highest absolute sample value, where 1.0 is full scale.
+    pub track_peak: f64,
+}
+
+/// Loudness at one point of the stream. Values are `-inf` when the window
+/// is silent or not yet full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessWindow {
+    /// Start of the 100 ms frame the values were measured at, in seconds.
+    pub time: f64,
+    /// Momentary loudness (400 ms window) in LUFS.
+    pub momentary: f64,
+    /// Short-term loudness (3 s window) in LUFS.
+    pub short_term: f64,
+    /// Integrated loudness up to this point in LUFS.
+    pub integrated: f64,
+    /// Loudness range up to this point in LU.
+    pub loudness_range: f64,
+}
+
+/// Run the `ebur128` and `replaygain` filters over audio stream
+/// `audio_stream` (as in `0:a:<n>`) of `source`.
+///
+/// The filters write their results as frame metadata which is printed to
+/// files in the run's output directory, so nothing is read from stderr.
+pub fn analyze_loudness<S: Source + 'static>(
+    source: S,
+    audio_stream: usize,
+) -> Result<Loudness, String> {
+    let graph = format!(
+        "[0:a:{}]asplit[r128][rg];\
+         [r128]ebur128=peak=sample+true:metadata=1,\
+         ametadata=mode=print:file={{outdir}}/ebur128.txt[r128out];\
+         [rg]aformat=sample_fmts=flt:channel_layouts=stereo,replaygain=metadata=1,\
+         ametadata=mode=print:file={{outdir}}/replaygain.txt[rgout]",
+        audio_stream
+    );
+    let args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-i",
+        "{input}",
+        "-filter_complex",
+        &graph,
+        "-map",
+        "[r128out]",
+        "-f",
+        "null",
+        "-",
+        "-map",
+        "[rgout]",
+        "-f",
+        "null",
+        "-",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let output = run_ffmpeg(source, &args)?.wait()?;
+    let r128 = read_metadata_print(&output.path().join("ebur128.txt"))?;
+    let rg = read_metadata_print(&output.path().join("replaygain.txt"))?;
+    loudness_from_metadata(&r128, &rg)
+}
+
+fn loudness_from_metadata(
+    r128: &[FrameMetadata],
+    rg: &[FrameMetadata],
+) -> Result<Loudness, String> {
+    let windows: Vec<LoudnessWindow> = r128
+        .iter()
+        .filter_map(|f| {
+            Some(LoudnessWindow {
+                time: f.pts_time?,
+                momentary: f.f64("lavfi.r128.M")?,
+                short_term: f.f64("lavfi.r128.S")?,
+                integrated: f.f64("lavfi.r128.I")?,
+                loudness_range: f.f64("lavfi.r128.LRA")?,
+            })
+        })
+        .collect();
+    let last = r128
+        .iter()
+        .rev()
+        .find(|f| f.values.contains_key("lavfi.r128.I"))
+        .ok_or("ebur128 produced no measurements")?;
+    let value = |key: &str| {
+        last.f64(key)
+            .ok_or_else(|| format!("ebur128: missing or invalid {}", key))
+    };
+    // The peaks are running linear maxima; report them like the filter's
+    // summary does.
+    let dbfs = |linear: f64| 20.0 * linear.log10();
+
+    let last_rg = rg
+        .iter()
+        .rev()
+        .find(|f| f.values.contains_key("lavfi.replaygain.track_gain"))
+        .ok_or("replaygain produced no measurements")?;
+    let replaygain = ReplayGain {
+        track_gain: last_rg
+            .f64("lavfi.replaygain.track_gain")
+            .ok_or("replaygain: invalid track_gain")?,
+        track_peak: last_rg
+            .f64("lavfi.replaygain.track_peak")
+            .ok_or("replaygain: invalid track_peak")?,
+    };
+
+    Ok(Loudness {
+        integrated: value("lavfi.r128.I")?,
+        loudness_range: value("lavfi.r128.LRA")?,
+        lra_low: value("lavfi.r128.LRA.low")?,
+        lra_high: value("lavfi.r128.LRA.high")?,
+        true_peak: dbfs(value("lavfi.r128.true_peak")?),
+        sample_peak: dbfs(value("lavfi.r128.sample_peak")?),
+        replaygain,
+        windows,
+    })
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::{
+    ffprobe_args, ffprobe_ctx_create, parse_capture, run_ffprobe_capture_in, FFProbeCtxState,
+    FfprobeError, FfprobeOutput, ProbeOptions, Source,
+};
+
+type BatchItem = (usize, Result<FfprobeOutput, FfprobeError>);
+
+/// Results of `ffprobe_many`, as `(position in the input, result)` pairs in
+/// completion order.
+///
+/// Dropping the batch stops starting new probes and waits for the running
+/// ones.
+pub struct ProbeBatch {
+    rx: Option<Receiver<BatchItem>>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Iterator for ProbeBatch {
+    type Item = BatchItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for ProbeBatch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        drop(self.rx.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Probe every source with up to `concurrency` runs at a time.
+///
+/// Sources are pulled from `sources` only when a worker is free, so a long
+/// iterator is never registered all at once. Each worker reuses one ffprobe
+/// context for all of its runs.
+pub fn ffprobe_many<I, S>(sources: I, concurrency: usize) -> ProbeBatch
+where
+    I: IntoIterator<Item = S>,
+    I::IntoIter: Send + 'static,
+    S: Source + 'static,
+{
+    ffprobe_many_with_options(sources, concurrency, &ProbeOptions::default())
+}
+
+pub fn ffprobe_many_with_options<I, S>(
+    sources: I,
+    concurrency: usize,
+    options: &ProbeOptions,
+) -> ProbeBatch
+where
+    I: IntoIterator<Item = S>,
+    I::IntoIter: Send + 'static,
+    S: Source + 'static,
+{
+    let queue = Arc::new(Mutex::new(sources.into_iter().enumerate()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = channel();
+    let workers = (0..concurrency.max(1))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let stop = Arc::clone(&stop);
+            let tx = tx.clone();
+            let options = options.clone();
+            std::thread::spawn(move || {
+                let args = ffprobe_args(&options);
+                let mut ctx: Option<FFProbeCtxState> = None;
+                while !stop.load(Ordering::SeqCst) {
+                    let next = match queue.lock() {
+                        Ok(mut q) => q.next(),
+                        Err(e) => e.into_inner().next(),
+                    };
+                    let Some((i, source)) = next else {
+                        break;
+                    };
+                    if ctx.is_none() {
+                        let ptr = unsafe { ffprobe_ctx_create() };
+                        ctx = (!ptr.is_null()).then_some(FFProbeCtxState { ptr });
+                    }
+                    let result = match &ctx {
+                        Some(ctx) => parse_capture(
+                            run_ffprobe_capture_in(ctx, source, &args),
+                            args.clone(),
+                            &options,
+                        ),
+                        None => Err(FfprobeError {
+                            message: "ffprobe_ctx_create failed".to_string(),
+                            stderr: Vec::new(),
+                            args: args.clone(),
+                        }),
+                    };
+                    if tx.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    ProbeBatch {
+        rx: Some(rx),
+        stop,
+        workers,
+    }
+}
@@ -0,0 +1,263 @@
+use crate::detect::{
+    BlackOptions, Detection, Detectors, MediaAnalysis, SceneOptions, SilenceOptions, TimeRange,
+};
+use crate::Source;
+
+/// A proposed skip range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    pub range: TimeRange,
+    /// From 0 to 1; how clearly the range is delimited by black frames and
+    /// silence.
+    pub confidence: f64,
+}
+
+/// Intro and end-credits markers proposed for an episode, with the
+/// detections they were derived from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpisodeMarkers {
+    pub intro: Option<Marker>,
+    pub credits: Option<Marker>,
+    pub analysis: MediaAnalysis,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerOptions {
+    pub black: BlackOptions,
+    /// `None` for inputs without audio.
+    pub silence: Option<SilenceOptions>,
+    /// `None` to skip scene scoring, which is the slowest detector.
+    pub scenes: Option<SceneOptions>,
+    /// Only the first `intro_search` seconds (and at most half the input)
+    /// are searched for an intro.
+    pub intro_search: f64,
+    pub intro_min: f64,
+    pub intro_max: f64,
+    /// Only the last `credits_search` seconds (and at most half the input)
+    /// are searched for credits.
+    pub credits_search: f64,
+    pub credits_min: f64,
+    /// Markers below this confidence are not proposed.
+    pub min_confidence: f64,
+}
+
+impl Default for MarkerOptions {
+    /// Episode transitions are short fades, so the detectors are set to
+    /// report much shorter ranges than the filters' defaults.
+    fn default() -> Self {
+        Self {
+            black: BlackOptions {
+                min_duration: 0.1,
+                ..Default::default()
+            },
+            silence: Some(SilenceOptions {
+                noise_db: -50.0,
+                min_duration: 0.3,
+                ..Default::default()
+            }),
+            scenes: Some(SceneOptions::default()),
+            intro_search: 600.0,
+            intro_min: 10.0,
+            intro_max: 150.0,
+            credits_search: 600.0,
+            credits_min: 10.0,
+            min_confidence: 0.45,
+        }
+    }
+}
+
+/// Run black, silence and scene detection in one pass and propose intro and
+/// credits markers from the result, see `EpisodeMarkers::from_analysis`.
+pub fn detect_episode_markers<S: Source + 'static>(
+    source: S,
+    options: &MarkerOptions,
+) -> Result<Detection<EpisodeMarkers>, String> {
+    let detectors = Detectors {
+        black: Some(options.black.clone()),
+        silence: options.silence.clone(),
+        scenes: options.scenes.clone(),
+    };
+    let options = options.clone();
+    detectors.start(source, move |analysis| {
+        EpisodeMarkers::from_analysis(analysis, &options)
+    })
+}
+
+/// A black range, a silent range, or both overlapping.
+#[derive(Debug, Clone, Copy)]
+struct Break {
+    range: TimeRange,
+    strength: f64,
+}
+
+const BLACK_AND_SILENT: f64 = 1.0;
+const START_OF_INPUT: f64 = 0.8;
+const BLACK_ONLY: f64 = 0.6;
+const SILENT_ONLY: f64 = 0.4;
+
+const CUT_MARGIN: f64 = 0.5;
+
+impl EpisodeMarkers {
+    /// Intros and credits are usually cut off from the episode by a fade to
+    /// black with a gap in the audio. The intro is the span between two such
+    /// breaks (or the start of the input and a break) near the start; the
+    /// credits run from a break near the end to the end of the input.
+    ///
+    /// With scene changes available, intros are expected to cut at least as
+    /// often as the episode on average and credits less often; candidates
+    /// that don't are scored lower.
+    pub fn from_analysis(analysis: MediaAnalysis, options: &MarkerOptions) -> Self {
+        let breaks = breaks(&analysis);
+        let cuts = CutRate::new(&analysis);
+        let intro = find_intro(&breaks, &cuts, analysis.duration, options);
+        let credits = analysis
+            .duration
+            .and_then(|d| find_credits(&breaks, &cuts, d, options));
+        EpisodeMarkers {
+            intro: intro.filter(|m| m.confidence >= options.min_confidence),
+            credits: credits.filter(|m| m.confidence >= options.min_confidence),
+            analysis,
+        }
+    }
+}
+
+fn breaks(analysis: &MediaAnalysis) -> Vec<Break> {
+    let mut out: Vec<Break> = analysis
+        .black
+        .iter()
+        .map(
+            |black| match analysis.silence.iter().find(|s| s.overlaps(black)) {
+                Some(silence) => Break {
+                    range: TimeRange {
+                        start: black.start.min(silence.start),
+                        end: black.end.max(silence.end),
+                    },
+                    strength: BLACK_AND_SILENT,
+                },
+                None => Break {
+                    range: *black,
+                    strength: BLACK_ONLY,
+                },
+            },
+        )
+        .collect();
+    for silence in &analysis.silence {
+        if !analysis.black.iter().any(|b| b.overlaps(silence)) {
+            out.push(Break {
+                range: *silence,
+                strength: SILENT_ONLY,
+            });
+        }
+    }
+    out.sort_by(|a, b| a.range.start.total_cmp(&b.range.start));
+    out
+}
+
+struct CutRate {
+    times: Vec<f64>,
+    average: Option<f64>,
+}
+
+impl CutRate {
+    fn new(analysis: &MediaAnalysis) -> Self {
+        let average = analysis
+            .duration
+            .filter(|d| *d > 0.0 && !analysis.scenes.is_empty())
+            .map(|d| analysis.scenes.len() as f64 / d);
+        CutRate {
+            times: analysis.scenes.iter().map(|s| s.time).collect(),
+            average,
+        }
+    }
+
+    /// Scene changes per second within `start..end`, relative to the
+    /// average. `None` without scene data.
+    ///
+    /// The cuts into and out of the breaks themselves are not counted.
+    fn relative(&self, start: f64, end: f64) -> Option<f64> {
+        let average = self.average?;
+        let (start, end) = (start + CUT_MARGIN, end - CUT_MARGIN);
+        if end <= start {
+            return None;
+        }
+        let n = self
+            .times
+            .iter()
+            .filter(|t| **t > start && **t < end)
+            .count();
+        Some(n as f64 / (end - start) / average)
+    }
+}
+
+fn find_intro(
+    breaks: &[Break],
+    cuts: &CutRate,
+    duration: Option<f64>,
+    options: &MarkerOptions,
+) -> Option<Marker> {
+    let search_end = match duration {
+        Some(d) => options.intro_search.min(d / 2.0),
+        None => options.intro_search,
+    };
+    let start_of_input = Break {
+        range: TimeRange {
+            start: 0.0,
+            end: 0.0,
+        },
+        strength: START_OF_INPUT,
+    };
+    let mut best: Option<Marker> = None;
+    for end in breaks.iter().filter(|b| b.range.start <= search_end) {
+        for start in std::iter::once(&start_of_input).chain(breaks.iter()) {
+            let length = end.range.start - start.range.end;
+            if length < options.intro_min || length > options.intro_max {
+                continue;
+            }
+            let montage = match cuts.relative(start.range.end, end.range.start) {
+                Some(r) if r < 1.0 => 0.8,
+                _ => 1.0,
+            };
+            let confidence = start.strength * end.strength * montage;
+            if best.is_none_or(|b| confidence > b.confidence) {
+                best = Some(Marker {
+                    range: TimeRange {
+                        start: start.range.end,
+                        end: end.range.end,
+                    },
+                    confidence,
+                });
+            }
+        }
+    }
+    best
+}
+
+fn find_credits(
+    breaks: &[Break],
+    cuts: &CutRate,
+    duration: f64,
+    options: &MarkerOptions,
+) -> Option<Marker> {
+    let search_start = duration - options.credits_search.min(duration / 2.0);
+    let mut best: Option<Marker> = None;
+    for b in breaks {
+        if b.range.start < search_start || duration - b.range.end < options.credits_min {
+            continue;
+        }
+        let calm = match cuts.relative(b.range.end, duration) {
+            Some(r) if r > 1.0 => 0.8,
+            _ => 1.0,
+        };
+        let confidence = b.strength * calm;
+        if best.is_none_or(|m| confidence > m.confidence) {
+            best = Some(Marker {
+                range: TimeRange {
+                    start: b.range.start,
+                    end: duration,
+                },
+                confidence,
+            });
+        }
+    }
+    best
+}
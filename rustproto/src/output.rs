@@ -0,0 +1,265 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::Cancellation;
+use crate::{dash, hls};
+
+/// What a file written by ffmpeg is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtifactRole {
+    /// HLS media or master playlist (`.m3u8`).
+    Playlist,
+    /// fMP4 initialization segment (`EXT-X-MAP`, `init*.mp4`).
+    InitSegment,
+    /// Media segment referenced by a playlist or manifest.
+    MediaSegment,
+    /// LL-HLS partial segment (`EXT-X-PART`).
+    PartialSegment,
+    /// DASH manifest (`.mpd`).
+    Manifest,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputFile {
+    /// Path relative to the run's output directory.
+    pub path: PathBuf,
+    pub size: u64,
+    /// Only filled in by `RunOutput::hash_files`.
+    pub sha256: Option<[u8; 32]>,
+    pub role: ArtifactRole,
+    /// Segment duration in seconds, as declared by the playlist that references it.
+    pub duration: Option<f64>,
+}
+
+/// Result of a completed `run_ffmpeg` call.
+///
+/// Keeps the temporary output directory alive and lists every file written
+/// into it, sorted by path.
+#[derive(Debug)]
+pub struct RunOutput {
+    pub tempdir: tempfile::TempDir,
+    pub files: Vec<OutputFile>,
+    pub cancelled: Option<Cancellation>,
+}
+
+impl RunOutput {
+    pub(crate) fn collect(
+        tempdir: tempfile::TempDir,
+        cancelled: Option<Cancellation>,
+    ) -> std::io::Result<Self> {
+        let files = collect_files(tempdir.path())?;
+        Ok(Self {
+            tempdir,
+            files,
+            cancelled,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.tempdir.path()
+    }
+
+    pub fn into_tempdir(self) -> tempfile::TempDir {
+        self.tempdir
+    }
+
+    pub fn file<P: AsRef<Path>>(&self, rel: P) -> Option<&OutputFile> {
+        self.files.iter().find(|f| f.path == rel.as_ref())
+    }
+
+    pub fn files_with_role(&self, role: ArtifactRole) -> impl Iterator<Item = &OutputFile> {
+        self.files.iter().filter(move |f| f.role == role)
+    }
+
+    /// Sum of all media segment durations, if there are segments and every
+    /// one has a duration.
+    pub fn total_duration(&self) -> Option<f64> {
+        let mut segments = self.files_with_role(ArtifactRole::MediaSegment).peekable();
+        segments.peek()?;
+        segments.map(|f| f.duration).sum()
+    }
+
+    /// Compute the SHA-256 of every file that does not have one yet.
+    pub fn hash_files(&mut self) -> std::io::Result<()> {
+        let base = self.tempdir.path().to_path_buf();
+        for file in &mut self.files {
+            if file.sha256.is_none() {
+                file.sha256 = Some(sha256_file(&base.join(&file.path))?);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect_files(base: &Path) -> std::io::Result<Vec<OutputFile>> {
+    let mut paths = Vec::new();
+    list_files(base, Path::new(""), &mut paths)?;
+    paths.sort();
+
+    let mut files = Vec::with_capacity(paths.len());
+    let mut playlists = Vec::new();
+    let mut manifests = Vec::new();
+    for rel in paths {
+        let size = fs::metadata(base.join(&rel))?.len();
+        let role = role_from_name(&rel);
+        match role {
+            ArtifactRole::Playlist => playlists.push(rel.clone()),
+            ArtifactRole::Manifest => manifests.push(rel.clone()),
+            _ => {}
+        }
+        files.push(OutputFile {
+            path: rel,
+            size,
+            sha256: None,
+            role,
+            duration: None,
+        });
+    }
+
+    // Playlists and manifests are the authority on what is a segment and how
+    // long it lasts.
+    let mut durations: HashMap<PathBuf, f64> = HashMap::new();
+    let mut init_segments: Vec<PathBuf> = Vec::new();
+    let mut parts: HashMap<PathBuf, f64> = HashMap::new();
+    for rel in &playlists {
+        let text = match fs::read_to_string(base.join(rel)) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let playlist = match hls::MediaPlaylist::parse(&text) {
+            Ok(playlist) => playlist,
+            // Master playlists only point at other playlists.
+            Err(_) => continue,
+        };
+        let dir = rel.parent().unwrap_or(Path::new(""));
+        for segment in &playlist.segments {
+            if let Some(map) = &segment.map {
+                if let Some(path) = resolve_local(base, dir, &map.uri) {
+                    init_segments.push(path);
+                }
+            }
+            if let Some(path) = resolve_local(base, dir, &segment.uri) {
+                // With `-hls_flags single_file` several byte ranges share a file.
+                *durations.entry(path).or_insert(0.0) += segment.duration;
+            }
+        }
+        for part in playlist
+            .segments
+            .iter()
+            .flat_map(|s| &s.parts)
+            .chain(&playlist.trailing_parts)
+        {
+            if let Some(path) = resolve_local(base, dir, &part.uri) {
+                *parts.entry(path).or_insert(0.0) += part.duration;
+            }
+        }
+    }
+    for rel in &manifests {
+        let mpd = match fs::read_to_string(base.join(rel)) {
+            Ok(text) => match dash::Mpd::parse(&text) {
+                Ok(mpd) => mpd,
+                Err(_) => continue,
+            },
+            Err(_) => continue,
+        };
+        let dir = rel.parent().unwrap_or(Path::new(""));
+        let Ok(segments) = mpd.segments() else {
+            continue;
+        };
+        for segment in segments {
+            let Some(path) = resolve_local(base, dir, &segment.url) else {
+                continue;
+            };
+            if segment.is_init() {
+                init_segments.push(path);
+            } else {
+                *durations.entry(path).or_insert(0.0) += segment.duration();
+            }
+        }
+    }
+
+    for file in &mut files {
+        if init_segments.contains(&file.path) {
+            file.role = ArtifactRole::InitSegment;
+        } else if let Some(duration) = parts.get(&file.path) {
+            file.role = ArtifactRole::PartialSegment;
+            file.duration = Some(*duration);
+        } else if let Some(duration) = durations.get(&file.path) {
+            file.role = ArtifactRole::MediaSegment;
+            file.duration = Some(*duration);
+        }
+    }
+
+    Ok(files)
+}
+
+fn list_files(base: &Path, rel: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(base.join(rel))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let rel_path = rel.join(entry.file_name());
+        if file_type.is_dir() {
+            list_files(base, &rel_path, out)?;
+        } else if file_type.is_file() {
+            out.push(rel_path);
+        }
+    }
+    Ok(())
+}
+
+fn role_from_name(path: &Path) -> ArtifactRole {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "m3u8" | "m3u" => ArtifactRole::Playlist,
+        "mpd" => ArtifactRole::Manifest,
+        "mp4" | "m4s" | "m4v" | "m4a" | "cmfv" | "cmfa" | "webm" if name.starts_with("init") => {
+            ArtifactRole::InitSegment
+        }
+        "ts" | "m4s" | "aac" | "ac3" | "mp3" | "vtt" | "cmfv" | "cmfa" | "chk" => {
+            ArtifactRole::MediaSegment
+        }
+        _ => ArtifactRole::Other,
+    }
+}
+
+/// Map a playlist URI to a path relative to the output directory. Absolute
+/// URLs are ignored since they cannot point into the temp directory.
+fn resolve_local(base: &Path, dir: &Path, uri: &str) -> Option<PathBuf> {
+    if uri.contains("://") {
+        return None;
+    }
+    let uri = uri.split(['?', '#']).next().unwrap_or(uri);
+    let path = Path::new(uri);
+    if path.is_absolute() {
+        // ffmpeg writes absolute segment paths when `{outdir}` is used in
+        // `-hls_segment_filename` without `-hls_base_url`. Keep any
+        // subdirectories, as with `v%v/seg%d.ts`.
+        return path.strip_prefix(base).ok().map(Path::to_path_buf);
+    }
+    Some(dir.join(path))
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut f = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 16384];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().into())
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use crate::{deserialize_i64, deserialize_rational, Chapter, FfprobeOutput, Format, Stream};
+
+/// A value in ffprobe's JSON that does not fit the Rust types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseDiagnostic {
+    /// JSON path of the value, e.g. `streams[2].width`.
+    pub path: String,
+    /// The offending value, `None` when the field is missing.
+    pub raw: Option<Value>,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.raw {
+            Some(raw) => write!(f, "{}: {} (got {})", self.path, self.reason, raw),
+            None => write!(f, "{}: {}", self.path, self.reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseDiagnostic {}
+
+/// Result of `FfprobeOutput::from_json_lenient`.
+#[derive(Debug, Clone)]
+pub struct LenientParse {
+    pub output: FfprobeOutput,
+    /// Everything that was replaced, removed or skipped to get `output`.
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Int(i64),
+    Str(&'static str),
+    Rational,
+}
+
+type Fields = &'static [(&'static str, Kind)];
+
+const FORMAT_FIELDS: Fields = &[
+    ("filename", Kind::Str("")),
+    ("nb_streams", Kind::Int(0)),
+    ("nb_programs", Kind::Int(0)),
+    ("nb_stream_groups", Kind::Int(0)),
+    ("format_name", Kind::Str("unknown")),
+    ("format_long_name", Kind::Str("unknown")),
+    ("probe_score", Kind::Int(0)),
+];
+
+const STREAM_FIELDS: Fields = &[
+    ("index", Kind::Int(0)),
+    ("codec_tag", Kind::Str("0x0000")),
+    ("codec_tag_string", Kind::Str("[0][0][0][0]")),
+    ("codec_type", Kind::Str("unknown")),
+    ("codec_name", Kind::Str("unknown")),
+    ("codec_long_name", Kind::Str("unknown")),
+    ("profile", Kind::Str("unknown")),
+    ("time_base", Kind::Rational),
+];
+
+const VIDEO_FIELDS: Fields = &[
+    ("width", Kind::Int(0)),
+    ("height", Kind::Int(0)),
+    ("pix_fmt", Kind::Str("unknown")),
+    ("level", Kind::Int(-99)),
+];
+
+const AUDIO_FIELDS: Fields = &[
+    ("sample_rate", Kind::Int(0)),
+    ("channels", Kind::Int(0)),
+    ("channel_layout", Kind::Str("unknown")),
+    ("bits_per_sample", Kind::Int(0)),
+];
+
+const CHAPTER_FIELDS: Fields = &[
+    ("id", Kind::Int(0)),
+    ("time_base", Kind::Rational),
+    ("start", Kind::Int(0)),
+    ("end", Kind::Int(0)),
+];
+
+impl FfprobeOutput {
+    /// Parse ffprobe JSON, failing on the first value that does not fit.
+    /// Unlike a plain `serde_json::from_slice`, the error names the JSON
+    /// path of that value.
+    pub fn from_json_strict(json: &[u8]) -> Result<FfprobeOutput, ParseDiagnostic> {
+        let err = match serde_json::from_slice(json) {
+            Ok(parsed) => return Ok(parsed),
+            Err(err) => err,
+        };
+        let fallback = ParseDiagnostic {
+            path: String::new(),
+            raw: None,
+            reason: err.to_string(),
+        };
+        let Ok(Value::Object(root)) = serde_json::from_slice::<Value>(json) else {
+            return Err(fallback);
+        };
+
+        match root.get("format") {
+            Some(Value::Object(format)) => {
+                if let Some(d) = check_fields(format, "format", FORMAT_FIELDS)
+                    .or_else(|| check::<Format>(format, "format", &[FORMAT_FIELDS]))
+                {
+                    return Err(d);
+                }
+            }
+            other => return Err(missing_section("format", other)),
+        }
+        for (i, stream) in array(&root, "streams").iter().enumerate() {
+            let path = format!("streams[{}]", i);
+            let Value::Object(stream) = stream else {
+                return Err(not_an_object(path, stream));
+            };
+            if let Some(d) = check_fields(stream, &path, STREAM_FIELDS)
+                .or_else(|| check_fields(stream, &path, typed_fields(stream)))
+                .or_else(|| check::<Stream>(stream, &path, &[STREAM_FIELDS, typed_fields(stream)]))
+            {
+                return Err(d);
+            }
+        }
+        for (i, chapter) in array(&root, "chapters").iter().enumerate() {
+            let path = format!("chapters[{}]", i);
+            let Value::Object(chapter) = chapter else {
+                return Err(not_an_object(path, chapter));
+            };
+            if let Some(d) = check_fields(chapter, &path, CHAPTER_FIELDS)
+                .or_else(|| check::<Chapter>(chapter, &path, &[CHAPTER_FIELDS]))
+            {
+                return Err(d);
+            }
+        }
+        Err(fallback)
+    }
+
+    /// Parse ffprobe JSON, keeping whatever fits. Required fields that are
+    /// missing or invalid get placeholder values, optional fields that do
+    /// not parse are dropped, and streams or chapters that still do not
+    /// parse are skipped. Each of these is recorded as a diagnostic.
+    ///
+    /// Fails only when the input is not a JSON object.
+    pub fn from_json_lenient(json: &[u8]) -> Result<LenientParse, String> {
+        let mut value: Value = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+        let root = value
+            .as_object_mut()
+            .ok_or_else(|| "expected a json object".to_string())?;
+        let mut diagnostics = Vec::new();
+
+        let raw_streams = take_array(root, "streams");
+        let mut streams = Vec::with_capacity(raw_streams.len());
+        for (i, raw) in raw_streams.into_iter().enumerate() {
+            let path = format!("streams[{}]", i);
+            let Value::Object(mut stream) = raw else {
+                diagnostics.push(skipped(not_an_object(path, &raw)));
+                continue;
+            };
+            if fill(&mut stream, &path, STREAM_FIELDS, &mut diagnostics).contains(&"index") {
+                stream.insert("index".to_string(), json!(i));
+            }
+            let typed = typed_fields(&stream);
+            fill(&mut stream, &path, typed, &mut diagnostics);
+            match parse_or_prune::<Stream>(stream, &path, &[STREAM_FIELDS, typed], &mut diagnostics)
+            {
+                Ok(stream) => streams.push(stream),
+                Err(d) => diagnostics.push(skipped(d)),
+            }
+        }
+
+        let raw_chapters = take_array(root, "chapters");
+        let mut chapters = Vec::with_capacity(raw_chapters.len());
+        for (i, raw) in raw_chapters.into_iter().enumerate() {
+            let path = format!("chapters[{}]", i);
+            let Value::Object(chapter) = raw else {
+                diagnostics.push(skipped(not_an_object(path, &raw)));
+                continue;
+            };
+            // Chapters have no sensible placeholders for their times.
+            if let Some(d) = check_fields(&chapter, &path, CHAPTER_FIELDS) {
+                diagnostics.push(skipped(d));
+                continue;
+            }
+            match parse_or_prune::<Chapter>(chapter, &path, &[CHAPTER_FIELDS], &mut diagnostics) {
+                Ok(chapter) => chapters.push(chapter),
+                Err(d) => diagnostics.push(skipped(d)),
+            }
+        }
+
+        let mut format = match root.remove("format") {
+            Some(Value::Object(format)) => format,
+            other => {
+                diagnostics.push(missing_section("format", other.as_ref()));
+                Map::new()
+            }
+        };
+        if fill(&mut format, "format", FORMAT_FIELDS, &mut diagnostics).contains(&"nb_streams") {
+            format.insert("nb_streams".to_string(), json!(streams.len()));
+        }
+        let format = parse_or_prune::<Format>(format, "format", &[FORMAT_FIELDS], &mut diagnostics)
+            .map_err(|d| d.to_string())?;
+
+        let unknown = std::mem::take(root).into_iter().collect();
+        Ok(LenientParse {
+            output: FfprobeOutput {
+                format,
+                streams,
+                chapters,
+                unknown,
+            },
+            diagnostics,
+        })
+    }
+}
+
+fn typed_fields(stream: &Map<String, Value>) -> Fields {
+    match stream.get("codec_type").and_then(Value::as_str) {
+        Some("video") => VIDEO_FIELDS,
+        Some("audio") => AUDIO_FIELDS,
+        _ => &[],
+    }
+}
+
+fn check_value(kind: Kind, value: &Value) -> Result<(), String> {
+    match kind {
+        Kind::Int(_) => deserialize_i64(value).map(drop).map_err(|e| e.to_string()),
+        Kind::Rational => deserialize_rational(value)
+            .map(drop)
+            .map_err(|e| e.to_string()),
+        // `N/A` is a valid value for string fields.
+        Kind::Str(_) if value.is_string() => Ok(()),
+        Kind::Str(_) => Err("expected a string".to_string()),
+    }
+}
+
+/// The first required field of `obj` that is missing or invalid.
+fn check_fields(obj: &Map<String, Value>, path: &str, fields: Fields) -> Option<ParseDiagnostic> {
+    fields.iter().find_map(|(name, kind)| {
+        let reason = match obj.get(*name) {
+            None | Some(Value::Null) => "missing required field".to_string(),
+            Some(value) => check_value(*kind, value).err()?,
+        };
+        Some(ParseDiagnostic {
+            path: format!("{}.{}", path, name),
+            raw: obj.get(*name).cloned(),
+            reason,
+        })
+    })
+}
+
+/// Locate why `obj` does not deserialize as `T`: the first field that
+/// does not parse next to the required fields, or `obj` itself.
+fn check<T: DeserializeOwned>(
+    obj: &Map<String, Value>,
+    path: &str,
+    required: &[Fields],
+) -> Option<ParseDiagnostic> {
+    let reason = serde_json::from_value::<T>(Value::Object(obj.clone()))
+        .err()?
+        .to_string();
+    Some(
+        culprits::<T>(obj, path, required)
+            .into_iter()
+            .next()
+            .unwrap_or(ParseDiagnostic {
+                path: path.to_string(),
+                raw: None,
+                reason,
+            }),
+    )
+}
+
+/// Every field of `obj` outside `required` that does not deserialize as
+/// part of a `T` holding just the required fields and that one field.
+/// Empty when the required fields alone do not parse.
+fn culprits<T: DeserializeOwned>(
+    obj: &Map<String, Value>,
+    path: &str,
+    required: &[Fields],
+) -> Vec<ParseDiagnostic> {
+    let is_required = |key: &str| {
+        required
+            .iter()
+            .flat_map(|f| f.iter())
+            .any(|(n, _)| *n == key)
+    };
+    let base: Map<String, Value> = obj
+        .iter()
+        .filter(|(key, _)| is_required(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if serde_json::from_value::<T>(Value::Object(base.clone())).is_err() {
+        return Vec::new();
+    }
+    obj.iter()
+        .filter(|(key, _)| !is_required(key))
+        .filter_map(|(key, value)| {
+            let mut with = base.clone();
+            with.insert(key.clone(), value.clone());
+            let err = serde_json::from_value::<T>(Value::Object(with)).err()?;
+            Some(ParseDiagnostic {
+                path: format!("{}.{}", path, key),
+                raw: Some(value.clone()),
+                reason: err.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Replace missing or invalid required fields with placeholders, returning
+/// the names of the replaced fields.
+fn fill(
+    obj: &mut Map<String, Value>,
+    path: &str,
+    fields: Fields,
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Vec<&'static str> {
+    let mut replaced = Vec::new();
+    for (name, kind) in fields {
+        let reason = match obj.get(*name) {
+            None | Some(Value::Null) => "missing required field".to_string(),
+            Some(value) => match check_value(*kind, value) {
+                Ok(()) => continue,
+                Err(reason) => reason,
+            },
+        };
+        diagnostics.push(ParseDiagnostic {
+            path: format!("{}.{}", path, name),
+            raw: obj.get(*name).cloned(),
+            reason,
+        });
+        let placeholder = match kind {
+            Kind::Int(v) => json!(v),
+            Kind::Str(s) => json!(s),
+            Kind::Rational => json!("0/0"),
+        };
+        obj.insert((*name).to_string(), placeholder);
+        replaced.push(*name);
+    }
+    replaced
+}
+
+/// Deserialize `obj`, dropping optional fields that do not parse.
+fn parse_or_prune<T: DeserializeOwned>(
+    mut obj: Map<String, Value>,
+    path: &str,
+    required: &[Fields],
+    diagnostics: &mut Vec<ParseDiagnostic>,
+) -> Result<T, ParseDiagnostic> {
+    if let Ok(parsed) = serde_json::from_value::<T>(Value::Object(obj.clone())) {
+        return Ok(parsed);
+    }
+    let dropped = culprits::<T>(&obj, path, required);
+    for d in &dropped {
+        obj.remove(&d.path[path.len() + 1..]);
+    }
+    let parsed =
+        serde_json::from_value::<T>(Value::Object(obj)).map_err(|err| ParseDiagnostic {
+            path: path.to_string(),
+            raw: None,
+            reason: err.to_string(),
+        })?;
+    diagnostics.extend(dropped);
+    Ok(parsed)
+}
+
+fn array<'a>(root: &'a Map<String, Value>, key: &str) -> &'a [Value] {
+    root.get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn take_array(root: &mut Map<String, Value>, key: &str) -> Vec<Value> {
+    match root.remove(key) {
+        Some(Value::Array(items)) => items,
+        _ => Vec::new(),
+    }
+}
+
+fn missing_section(key: &str, value: Option<&Value>) -> ParseDiagnostic {
+    ParseDiagnostic {
+        path: key.to_string(),
+        raw: value.cloned(),
+        reason: "expected an object".to_string(),
+    }
+}
+
+fn not_an_object(path: String, value: &Value) -> ParseDiagnostic {
+    ParseDiagnostic {
+        path,
+        raw: Some(value.clone()),
+        reason: "expected an object".to_string(),
+    }
+}
+
+fn skipped(mut d: ParseDiagnostic) -> ParseDiagnostic {
+    d.reason = format!("skipped: {}", d.reason);
+    d
+}
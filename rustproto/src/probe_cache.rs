@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::FfprobeOutput;
+
+/// In-memory LRU of probe results keyed by `Source::fingerprint`, optionally
+/// persisted to a JSON file.
+///
+/// Safe to share between threads; wrap it in an `Arc` to use it from
+/// several tasks.
+pub struct ProbeCache {
+    capacity: usize,
+    path: Option<PathBuf>,
+    inner: Mutex<Inner>,
+    /// Held across snapshot, write and rename so concurrent saves land in
+    /// order.
+    save_lock: Mutex<()>,
+}
+
+struct Inner {
+    entries: HashMap<String, Entry>,
+    tick: u64,
+    /// Entries changed since the last save.
+    dirty: bool,
+}
+
+struct Entry {
+    output: FfprobeOutput,
+    last_used: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry {
+    key: String,
+    output: FfprobeOutput,
+}
+
+impl ProbeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            path: None,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                tick: 0,
+                dirty: false,
+            }),
+            save_lock: Mutex::new(()),
+        }
+    }
+
+    /// Cache backed by `path`. Entries already in the file are loaded. A
+    /// missing file starts an empty cache.
+    ///
+    /// Changes are written by `save` and, best effort, when the cache is
+    /// dropped.
+    pub fn with_persistence<P: AsRef<Path>>(capacity: usize, path: P) -> std::io::Result<Self> {
+        let mut cache = Self::new(capacity);
+        let path = path.as_ref().to_path_buf();
+        match fs::read(&path) {
+            Ok(data) => {
+                let entries: Vec<PersistedEntry> = serde_json::from_slice(&data)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                // The file is ordered from least to most recently used.
+                for entry in entries {
+                    cache.insert_entry(entry.key, entry.output);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        cache.inner.get_mut().unwrap().dirty = false;
+        cache.path = Some(path);
+        Ok(cache)
+    }
+
+    pub fn get(&self, key: &str) -> Option<FfprobeOutput> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = tick;
+        let output = entry.output.clone();
+        // Recency is persisted too.
+        inner.dirty = true;
+        Some(output)
+    }
+
+    /// Insert or replace `key`, evicting the least recently used entry when
+    /// full.
+    pub fn insert<K: Into<String>>(&self, key: K, output: FfprobeOutput) {
+        self.insert_entry(key.into(), output);
+    }
+
+    pub fn remove(&self, key: &str) -> Option<FfprobeOutput> {
+        let mut inner = self.inner.lock().unwrap();
+        let removed = inner.entries.remove(key);
+        inner.dirty |= removed.is_some();
+        removed.map(|e| e.output)
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.dirty |= !inner.entries.is_empty();
+        inner.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the cache to its backing file if it changed since the last
+    /// save. No-op for in-memory caches.
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let _saving = self.save_lock.lock().unwrap();
+        let data = {
+            let mut inner = self.inner.lock().unwrap();
+            if !inner.dirty {
+                return Ok(());
+            }
+            inner.dirty = false;
+            let mut entries: Vec<(&String, &Entry)> = inner.entries.iter().collect();
+            entries.sort_by_key(|(_, e)| e.last_used);
+            let persisted: Vec<PersistedEntry> = entries
+                .into_iter()
+                .map(|(key, e)| PersistedEntry {
+                    key: key.clone(),
+                    output: e.output.clone(),
+                })
+                .collect();
+            serde_json::to_vec(&persisted)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        };
+        let written = write_atomic(path, &data);
+        if written.is_err() {
+            self.inner.lock().unwrap().dirty = true;
+        }
+        written
+    }
+
+    fn insert_entry(&self, key: String, output: FfprobeOutput) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let last_used = inner.tick;
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.entries.insert(key, Entry { output, last_used });
+        inner.dirty = true;
+    }
+}
+
+impl Drop for ProbeCache {
+    fn drop(&mut self) {
+        let _ = self.save();
+    }
+}
+
+/// Write through a uniquely named file next to `path`, so other processes
+/// sharing the file never see a partial write.
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.write_all(data)?;
+    tmp.persist(path).map(|_| ()).map_err(|e| e.error)
+}
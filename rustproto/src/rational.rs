@@ -0,0 +1,172 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::Rational;
+
+impl Rational {
+    pub const fn new(num: i64, den: i64) -> Self {
+        Rational { num, den }
+    }
+
+    /// Lowest terms with a non-negative denominator. `0/0` is kept as is,
+    /// and so is a value whose lowest terms do not fit in `i64`, such as
+    /// `i64::MIN/-1`.
+    pub fn reduce(self) -> Self {
+        let (num, den) = self.wide();
+        match (i64::try_from(num), i64::try_from(den)) {
+            (Ok(num), Ok(den)) => Rational { num, den },
+            _ => self,
+        }
+    }
+
+    /// Lowest terms with a non-negative denominator, widened so that every
+    /// value has them.
+    fn wide(self) -> (i128, i128) {
+        let (num, den) = (self.num as i128, self.den as i128);
+        let g = gcd(num, den);
+        if g == 0 {
+            return (num, den);
+        }
+        let sign = if den < 0 { -1 } else { 1 };
+        (sign * num / g, sign * den / g)
+    }
+
+    pub fn inv(self) -> Self {
+        Rational {
+            num: self.den,
+            den: self.num,
+        }
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        from_wide(
+            self.num as i128 * rhs.num as i128,
+            self.den as i128 * rhs.den as i128,
+        )
+    }
+
+    /// `None` on overflow or when `rhs` is zero.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.num == 0 {
+            return None;
+        }
+        self.checked_mul(rhs.inv())
+    }
+
+    /// NaN for `0/0`, infinite for `n/0`.
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// The value as a number of seconds. `None` for a zero denominator or a
+    /// negative value.
+    pub fn to_duration(self) -> Option<Duration> {
+        let (num, den) = self.wide();
+        if den == 0 || num < 0 {
+            return None;
+        }
+        let secs = num / den;
+        let rem = num % den;
+        let nanos = ((rem * 1_000_000_000 + den / 2) / den) as u64;
+        Some(Duration::from_secs(secs as u64) + Duration::from_nanos(nanos))
+    }
+
+    /// `ts` in units of this time base, as a duration.
+    pub fn timestamp_to_duration(self, ts: i64) -> Option<Duration> {
+        self.checked_mul(Rational::from(ts))?.to_duration()
+    }
+}
+
+/// Rescale `ts` from time base `from` to time base `to`, rounding to the
+/// nearest value with halfway cases away from zero, like `av_rescale_q`.
+/// Returns `None` for a zero time base or when the result overflows.
+pub fn rescale(ts: i64, from: Rational, to: Rational) -> Option<i64> {
+    let mut b = from.num as i128 * to.den as i128;
+    let mut c = from.den as i128 * to.num as i128;
+    if c == 0 {
+        return None;
+    }
+    if c < 0 {
+        b = -b;
+        c = -c;
+    }
+    let r = ts as i128 * b;
+    let scaled = if r < 0 {
+        -((-r + c / 2) / c)
+    } else {
+        (r + c / 2) / c
+    };
+    i64::try_from(scaled).ok()
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    a = a.abs();
+    b = b.abs();
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn from_wide(num: i128, den: i128) -> Option<Rational> {
+    let g = gcd(num, den).max(1);
+    let sign = if den < 0 { -1 } else { 1 };
+    Some(Rational {
+        num: i64::try_from(sign * num / g).ok()?,
+        den: i64::try_from(sign * den / g).ok()?,
+    })
+}
+
+impl From<i64> for Rational {
+    fn from(value: i64) -> Self {
+        Rational { num: value, den: 1 }
+    }
+}
+
+/// Equality is by value, so `1/2 == 2/4`.
+impl PartialEq for Rational {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl Eq for Rational {}
+
+/// `0/0` is unordered against everything but itself.
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let (a, b) = (self.wide(), other.wide());
+        if a == b {
+            return Some(Ordering::Equal);
+        }
+        if a.1 == 0 || b.1 == 0 {
+            return self.to_f64().partial_cmp(&other.to_f64());
+        }
+        (a.0 * b.1).partial_cmp(&(b.0 * a.1))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// Parses `num/den`, the `num:den` aspect ratio form, or a plain integer.
+impl FromStr for Rational {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (num, den) = match s.split_once(['/', ':']) {
+            Some((num, den)) => (num.trim(), den.trim()),
+            None => (s, "1"),
+        };
+        match (num.parse::<i64>(), den.parse::<i64>()) {
+            (Ok(num), Ok(den)) => Ok(Rational { num, den }),
+            _ => Err(format!("invalid rational: {:?}", s)),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture_write, deserialize_f64_opt, deserialize_i64, deserialize_i64_opt,
+    deserialize_string_opt, ffprobe_ctx_create, ffprobe_ctx_set_output, ffprobe_exec, prepare_run,
+    CancelHandle, CaptureBuffer, FFProbeCtxState, Source,
+};
+
+/// What `ffprobe_records` reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordOptions {
+    /// `-show_packets`.
+    pub packets: bool,
+    /// `-show_frames`; this decodes the selected streams.
+    pub frames: bool,
+    /// `-select_streams`, e.g. `v:0`.
+    pub select_streams: Option<String>,
+    /// `-read_intervals`, e.g. `%+30` for the first 30 seconds.
+    pub read_intervals: Option<String>,
+    /// Records buffered ahead of the consumer before ffprobe is paused.
+    pub buffer: usize,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            packets: true,
+            frames: false,
+            select_streams: None,
+            read_intervals: None,
+            buffer: 256,
+        }
+    }
+}
+
+impl RecordOptions {
+    fn args(&self) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "ffprobe",
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-of",
+            "json",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        if self.packets {
+            args.push("-show_packets".to_string());
+        }
+        if self.frames {
+            args.push("-show_frames".to_string());
+        }
+        if let Some(v) = &self.select_streams {
+            args.extend(["-select_streams".to_string(), v.clone()]);
+        }
+        if let Some(v) = &self.read_intervals {
+            args.extend(["-read_intervals".to_string(), v.clone()]);
+        }
+        args.extend(["-i".to_string(), "{input}".to_string()]);
+        args
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketRecord {
+    pub codec_type: String,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub stream_index: i64,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub pts: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub pts_time: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub dts: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub dts_time: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub duration: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub duration_time: Option<f64>,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub size: i64,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub pos: Option<i64>,
+    /// `K` for keyframes, `D` for discarded packets, `C` for corrupt ones.
+    #[serde(default)]
+    pub flags: String,
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+impl PacketRecord {
+    pub fn is_keyframe(&self) -> bool {
+        self.flags.starts_with('K')
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub media_type: String,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub stream_index: i64,
+    #[serde(deserialize_with = "deserialize_i64")]
+    pub key_frame: i64,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub pts: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub pts_time: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub best_effort_timestamp_time: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_f64_opt")]
+    pub duration_time: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub pkt_pos: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub pkt_size: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub width: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub height: Option<i64>,
+    #[serde(default, deserialize_with = "deserialize_string_opt")]
+    pub pict_type: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_i64_opt")]
+    pub nb_samples: Option<i64>,
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProbeRecord {
+    Packet(PacketRecord),
+    Frame(FrameRecord),
+}
+
+type RecordResult = Result<ProbeRecord, String>;
+
+/// Records from a running `ffprobe_records` call, in ffprobe's output order.
+///
+/// At most `RecordOptions::buffer` records are held at a time; ffprobe waits
+/// while the buffer is full. Dropping the stream stops the run.
+pub struct RecordStream {
+    rx: Option<Receiver<RecordResult>>,
+    cancel: CancelHandle,
+    join: Option<JoinHandle<()>>,
+}
+
+impl RecordStream {
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+}
+
+impl Iterator for RecordStream {
+    type Item = RecordResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for RecordStream {
+    fn drop(&mut self) {
+        // Unblocks the writer callback if it is waiting on a full buffer.
+        drop(self.rx.take());
+        if let Some(join) = self.join.take() {
+            if !join.is_finished() {
+                self.cancel
+                    .cancel_with("record stream dropped", crate::CancelMode::Abort);
+            }
+            let _ = join.join();
+        }
+    }
+}
+
+/// Run ffprobe with `-show_packets` and/or `-show_frames` and yield each
+/// record as soon as ffprobe prints it, instead of parsing one JSON document
+/// at the end. A failed run ends the stream with an `Err` item.
+pub fn ffprobe_records<S: Source + 'static>(
+    source: S,
+    options: &RecordOptions,
+) -> Result<RecordStream, String> {
+    let (_dir, handle, replaced) = prepare_run(source, &options.args())?;
+    let ctx = unsafe { ffprobe_ctx_create() };
+    if ctx.is_null() {
+        return Err("ffprobe_ctx_create failed".to_string());
+    }
+    let ctx_state = Arc::new(FFProbeCtxState { ptr: ctx });
+
+    let (tx, rx) = sync_channel(options.buffer.max(1));
+    let sink = Box::new(RecordSink {
+        state: Mutex::new(SinkState {
+            splitter: JsonSplitter::default(),
+            tx: Some(tx),
+        }),
+    });
+    let stderr_capture = Box::new(CaptureBuffer::new());
+    unsafe {
+        ffprobe_ctx_set_output(
+            ctx,
+            Some(record_write),
+            sink.as_ref() as *const RecordSink as *mut c_void,
+            Some(capture_write),
+            stderr_capture.as_ref() as *const CaptureBuffer as *mut c_void,
+        );
+    }
+
+    let cancel_state = Arc::new(Mutex::new(None));
+    let cancel = CancelHandle {
+        source_id: handle.id,
+        ffmpeg_ctx: None,
+        ffprobe_ctx: Some(Arc::clone(&ctx_state)),
+        state: Arc::clone(&cancel_state),
+        finished: None,
+    };
+
+    let join = std::thread::spawn(move || {
+        let ret = ffprobe_exec(&ctx_state, &replaced);
+        drop(ctx_state);
+        drop(handle);
+        let stderr = stderr_capture.into_inner();
+        let RecordSink { state } = *sink;
+        let mut state = match state.into_inner() {
+            Ok(state) => state,
+            Err(e) => e.into_inner(),
+        };
+        let Some(tx) = state.tx.take() else {
+            return;
+        };
+        let failure = match ret {
+            Ok(0) => return,
+            Ok(_) if crate::current_cancellation(&cancel_state).is_some() => return,
+            Ok(code) => format!(
+                "ffprobe_run failed: {}: {}",
+                code,
+                String::from_utf8_lossy(&stderr).trim()
+            ),
+            Err(message) => message,
+        };
+        let _ = tx.send(Err(failure));
+    });
+
+    Ok(RecordStream {
+        rx: Some(rx),
+        cancel,
+        join: Some(join),
+    })
+}
+
+struct RecordSink {
+    state: Mutex<SinkState>,
+}
+
+struct SinkState {
+    splitter: JsonSplitter,
+    /// `None` once the consumer is gone.
+    tx: Option<SyncSender<RecordResult>>,
+}
+
+extern "C" fn record_write(opaque: *mut c_void, buf: *const u8, len: c_int) -> c_int {
+    if opaque.is_null() || buf.is_null() || len <= 0 {
+        return 0;
+    }
+    let sink = unsafe { &*(opaque as *const RecordSink) };
+    let slice = unsafe { std::slice::from_raw_parts(buf, len as usize) };
+    let mut guard = match sink.state.lock() {
+        Ok(g) => g,
+        Err(e) => e.into_inner(),
+    };
+    let state = &mut *guard;
+    if state.tx.is_none() {
+        return len;
+    }
+    for (section, object) in state.splitter.feed(slice) {
+        let record = parse_record(&section, &object);
+        if state.tx.as_ref().is_some_and(|tx| tx.send(record).is_err()) {
+            state.tx = None;
+            break;
+        }
+    }
+    len
+}
+
+fn parse_record(section: &str, object: &[u8]) -> RecordResult {
+    let value: serde_json::Value =
+        serde_json::from_slice(object).map_err(|e| format!("{} record: {}", section, e))?;
+    let is_frame = match section {
+        "frames" => true,
+        "packets" => false,
+        _ => value.get("type").and_then(|t| t.as_str()) == Some("frame"),
+    };
+    if is_frame {
+        serde_json::from_value(value).map(ProbeRecord::Frame)
+    } else {
+        serde_json::from_value(value).map(ProbeRecord::Packet)
+    }
+    .map_err(|e| format!("{} record: {}", section, e))
+}
+
+/// Splits ffprobe's JSON output `{"packets": [{...}, ...]}` into the
+/// objects of its top-level arrays, holding only the current object.
+#[derive(Default)]
+struct JsonSplitter {
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+    /// The last string seen at the top level, i.e. the current array's key.
+    key: Vec<u8>,
+    section: String,
+    object: Vec<u8>,
+}
+
+impl JsonSplitter {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut complete = Vec::new();
+        for &b in bytes {
+            if self.depth >= 3 {
+                self.object.push(b);
+            }
+            if self.in_string {
+                if self.escape {
+                    self.escape = false;
+                } else if b == b'\\' {
+                    self.escape = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    if self.depth == 1 {
+                        self.section = String::from_utf8_lossy(&self.key).into_owned();
+                    }
+                } else if self.depth == 1 {
+                    self.key.push(b);
+                }
+                continue;
+            }
+            match b {
+                b'"' => {
+                    self.in_string = true;
+                    if self.depth == 1 {
+                        self.key.clear();
+                    }
+                }
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    if self.depth == 3 {
+                        self.object.clear();
+                        self.object.push(b);
+                    }
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 2 && b == b'}' {
+                        complete.push((self.section.clone(), std::mem::take(&mut self.object)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        complete
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::{normalize_language, run_ffmpeg, FfprobeOutput, RunOutput, Source, Stream};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemuxFormat {
+    Matroska,
+    Mp4,
+}
+
+impl RemuxFormat {
+    fn muxer(self) -> &'static str {
+        match self {
+            RemuxFormat::Matroska => "matroska",
+            RemuxFormat::Mp4 => "mp4",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RemuxFormat::Matroska => "mkv",
+            RemuxFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+/// Changes to one input stream. Fields left as `None` keep the input's
+/// value; an empty string removes the tag.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamEdit {
+    /// Input stream index, as in `StreamCommon::index`.
+    pub index: i64,
+    pub title: Option<String>,
+    /// ISO 639-1 or 639-2 code; written as 639-2/B.
+    pub language: Option<String>,
+    pub default: Option<bool>,
+    pub forced: Option<bool>,
+    /// Any other tags, applied after `title` and `language`.
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewChapter {
+    /// Seconds.
+    pub start: f64,
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemuxOptions {
+    pub format: RemuxFormat,
+    /// Container tags; an empty value removes the tag.
+    pub tags: Vec<(String, String)>,
+    pub streams: Vec<StreamEdit>,
+    /// Input stream indices left out of the output.
+    pub drop: Vec<i64>,
+    /// `None` keeps the input's chapters, `Some` replaces them; an empty
+    /// list removes them all.
+    pub chapters: Option<Vec<NewChapter>>,
+}
+
+impl Default for RemuxOptions {
+    fn default() -> Self {
+        Self {
+            format: RemuxFormat::Matroska,
+            tags: Vec::new(),
+            streams: Vec::new(),
+            drop: Vec::new(),
+            chapters: None,
+        }
+    }
+}
+
+/// Copy the streams of `source` into a new container without re-encoding,
+/// applying `options`. `probe` must describe the same source.
+///
+/// The result is `remux.mkv` or `remux.mp4` in the run's output directory.
+pub fn remux<S: Source + 'static>(
+    source: S,
+    probe: &FfprobeOutput,
+    options: &RemuxOptions,
+) -> Result<RunOutput, String> {
+    let indices: HashSet<i64> = probe.streams.iter().map(|s| s.common().index).collect();
+    for index in options
+        .drop
+        .iter()
+        .chain(options.streams.iter().map(|e| &e.index))
+    {
+        if !indices.contains(index) {
+            return Err(format!("remux: no input stream {}", index));
+        }
+    }
+
+    let kept: Vec<&Stream> = probe
+        .streams
+        .iter()
+        .filter(|s| !options.drop.contains(&s.common().index))
+        .collect();
+    if kept.is_empty() {
+        return Err("remux: every stream was dropped".to_string());
+    }
+    if options.format == RemuxFormat::Mp4 {
+        if let Some(a) = kept.iter().find(|s| matches!(s, Stream::Attachment(_))) {
+            return Err(format!(
+                "remux: attachment stream {} cannot be stored in MP4",
+                a.common().index
+            ));
+        }
+    }
+
+    let mut args: Vec<String> = ["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.extend(["-i".to_string(), "{input}".to_string()]);
+
+    // Replacement chapters come from an FFMETADATA file given as a second
+    // input; it has to outlive the run.
+    let chapters_file = match &options.chapters {
+        Some(chapters) if !chapters.is_empty() => Some(write_chapters(chapters)?),
+        _ => None,
+    };
+    if let Some(file) = &chapters_file {
+        args.extend([
+            "-f".to_string(),
+            "ffmetadata".to_string(),
+            "-i".to_string(),
+            file.path().to_string_lossy().into_owned(),
+        ]);
+    }
+
+    for stream in &kept {
+        args.extend(["-map".to_string(), format!("0:{}", stream.common().index)]);
+    }
+    args.extend(["-c".to_string(), "copy".to_string()]);
+    args.extend(["-map_metadata".to_string(), "0".to_string()]);
+    let map_chapters = match (&options.chapters, &chapters_file) {
+        (None, _) => "0",
+        (Some(_), Some(_)) => "1",
+        (Some(_), None) => "-1",
+    };
+    args.extend(["-map_chapters".to_string(), map_chapters.to_string()]);
+
+    for (key, value) in &options.tags {
+        args.extend(["-metadata".to_string(), format!("{}={}", key, value)]);
+    }
+
+    for edit in &options.streams {
+        // Output streams follow input order, minus the dropped ones.
+        let out = kept
+            .iter()
+            .position(|s| s.common().index == edit.index)
+            .ok_or_else(|| format!("remux: stream {} is both edited and dropped", edit.index))?;
+        let spec = format!("-metadata:s:{}", out);
+        if let Some(title) = &edit.title {
+            args.extend([spec.clone(), format!("title={}", title)]);
+        }
+        if let Some(language) = &edit.language {
+            let code = if language.is_empty() {
+                String::new()
+            } else {
+                normalize_language(language)
+                    .ok_or_else(|| format!("remux: unknown language {:?}", language))?
+            };
+            args.extend([spec.clone(), format!("language={}", code)]);
+        }
+        for (key, value) in &edit.tags {
+            args.extend([spec.clone(), format!("{}={}", key, value)]);
+        }
+
+        // Relative to the input's flags, which are copied.
+        let mut disposition = String::new();
+        for (name, value) in [("default", edit.default), ("forced", edit.forced)] {
+            match value {
+                Some(true) => disposition.push_str(&format!("+{}", name)),
+                Some(false) => disposition.push_str(&format!("-{}", name)),
+                None => {}
+            }
+        }
+        if !disposition.is_empty() {
+            args.extend([format!("-disposition:{}", out), disposition]);
+        }
+    }
+
+    args.extend([
+        "-f".to_string(),
+        options.format.muxer().to_string(),
+        format!("{{outdir}}/remux.{}", options.format.extension()),
+    ]);
+
+    let output = run_ffmpeg(source, &args)?.wait()?;
+    drop(chapters_file);
+    Ok(output)
+}
+
+fn write_chapters(chapters: &[NewChapter]) -> Result<tempfile::NamedTempFile, String> {
+    let mut text = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        let valid = chapter.start >= 0.0 && chapter.end > chapter.start;
+        if !valid {
+            return Err(format!(
+                "remux: invalid chapter {}..{}",
+                chapter.start, chapter.end
+            ));
+        }
+        text.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\n",
+            (chapter.start * 1000.0).round() as i64,
+            (chapter.end * 1000.0).round() as i64
+        ));
+        if let Some(title) = &chapter.title {
+            text.push_str(&format!("title={}\n", escape_metadata(title)));
+        }
+    }
+    let mut file = tempfile::Builder::new()
+        .prefix("chapters")
+        .suffix(".txt")
+        .tempfile()
+        .map_err(|e| format!("remux: create chapters file: {}", e))?;
+    file.write_all(text.as_bytes())
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("remux: write chapters file: {}", e))?;
+    Ok(file)
+}
+
+/// FFMETADATA values escape `=`, `;`, `#`, `\` and newlines with a
+/// backslash.
+fn escape_metadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
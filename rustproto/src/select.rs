@@ -0,0 +1,153 @@
+use std::cmp::Reverse;
+
+use crate::{normalize_language, FfprobeOutput, Stream, StreamCommon};
+
+/// What to pick when several streams of a kind are available.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamPreferences {
+    /// Audio languages, most preferred first. Any ISO 639 form.
+    pub audio_languages: Vec<String>,
+    /// Subtitle languages, most preferred first. Empty selects no
+    /// subtitles.
+    pub subtitle_languages: Vec<String>,
+    /// Rank commentary tracks below everything else.
+    pub avoid_commentary: bool,
+    /// Audio channel count to aim for; the closest count wins.
+    pub channels: Option<i64>,
+    /// Break ties in favour of the `default` disposition.
+    pub prefer_default: bool,
+}
+
+impl Default for StreamPreferences {
+    fn default() -> Self {
+        Self {
+            audio_languages: Vec::new(),
+            subtitle_languages: Vec::new(),
+            avoid_commentary: true,
+            channels: None,
+            prefer_default: true,
+        }
+    }
+}
+
+/// Chosen input stream indices, as in `StreamCommon::index`. These don't
+/// shift when streams of another type are added or removed, unlike the
+/// `0:a:<n>` form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamSelection {
+    pub video: Option<i64>,
+    pub audio: Option<i64>,
+    pub subtitle: Option<i64>,
+}
+
+impl StreamSelection {
+    pub fn indices(&self) -> impl Iterator<Item = i64> {
+        [self.video, self.audio, self.subtitle]
+            .into_iter()
+            .flatten()
+    }
+
+    /// `-map` arguments for `run_ffmpeg`, video first, then audio, then
+    /// subtitles.
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        self.indices()
+            .flat_map(|index| ["-map".to_string(), format!("0:{}", index)])
+            .collect()
+    }
+}
+
+impl FfprobeOutput {
+    /// Pick one video, audio and subtitle stream according to `preferences`.
+    ///
+    /// Audio is ranked by commentary (if avoided), language, channel count,
+    /// default disposition and finally input order. A subtitle stream is only
+    /// chosen if its language is in `subtitle_languages`; full subtitles rank
+    /// above forced ones.
+    pub fn select_streams(&self, preferences: &StreamPreferences) -> StreamSelection {
+        let audio_languages = normalize_all(&preferences.audio_languages);
+        let subtitle_languages = normalize_all(&preferences.subtitle_languages);
+        let default =
+            |c: &StreamCommon| preferences.prefer_default && c.disposition_flags().default;
+        let commentary = |c: &StreamCommon| preferences.avoid_commentary && is_commentary(c);
+
+        let video = self
+            .streams
+            .iter()
+            .filter_map(|s| match s {
+                Stream::Video(v) if !v.common.disposition_flags().attached_pic => Some(&v.common),
+                _ => None,
+            })
+            .max_by_key(|c| (default(c), Reverse(c.index)))
+            .map(|c| c.index);
+
+        let audio = self
+            .streams
+            .iter()
+            .filter_map(|s| match s {
+                Stream::Audio(a) => Some(a),
+                _ => None,
+            })
+            .max_by_key(|a| {
+                let c = &a.common;
+                let channels = preferences
+                    .channels
+                    .map(|want| (Reverse((a.channels - want).abs()), a.channels));
+                (
+                    !commentary(c),
+                    language_rank(c, &audio_languages),
+                    channels,
+                    default(c),
+                    Reverse(c.index),
+                )
+            })
+            .map(|a| a.common.index);
+
+        let subtitle = self
+            .streams
+            .iter()
+            .filter_map(|s| match s {
+                Stream::Subtitle(s) => Some(&s.common),
+                _ => None,
+            })
+            .filter(|c| language_rank(c, &subtitle_languages) > 0)
+            .max_by_key(|c| {
+                (
+                    !commentary(c),
+                    language_rank(c, &subtitle_languages),
+                    !c.disposition_flags().forced,
+                    default(c),
+                    Reverse(c.index),
+                )
+            })
+            .map(|c| c.index);
+
+        StreamSelection {
+            video,
+            audio,
+            subtitle,
+        }
+    }
+}
+
+fn normalize_all(languages: &[String]) -> Vec<String> {
+    languages
+        .iter()
+        .filter_map(|l| normalize_language(l))
+        .collect()
+}
+
+/// Higher for earlier entries in `preferred`, 0 if the stream's language is
+/// not listed.
+fn language_rank(common: &StreamCommon, preferred: &[String]) -> usize {
+    common
+        .language()
+        .and_then(|l| preferred.iter().position(|p| *p == l))
+        .map_or(0, |pos| preferred.len() - pos)
+}
+
+fn is_commentary(common: &StreamCommon) -> bool {
+    common.disposition_flags().comment
+        || common
+            .title()
+            .is_some_and(|t| t.to_ascii_lowercase().contains("commentary"))
+}
@@ -0,0 +1,50 @@
+use crate::SubtitleStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleKind {
+    /// Decoded to text events, so it can be converted to WebVTT.
+    Text,
+    /// Decoded to images; it can only be burned into the video.
+    Bitmap,
+    /// A codec ffmpeg cannot decode in this build (e.g. `ttml`,
+    /// `dvb_teletext`).
+    Unknown,
+}
+
+impl SubtitleKind {
+    pub fn from_codec(codec_name: &str) -> Self {
+        match codec_name {
+            "subrip" | "srt" | "ass" | "ssa" | "webvtt" | "mov_text" | "text" | "microdvd"
+            | "subviewer" | "subviewer1" | "sami" | "realtext" | "jacosub" | "mpl2" | "pjs"
+            | "vplayer" | "stl" | "eia_608" => SubtitleKind::Text,
+            "hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub" => SubtitleKind::Bitmap,
+            _ => SubtitleKind::Unknown,
+        }
+    }
+}
+
+impl SubtitleStream {
+    pub fn kind(&self) -> SubtitleKind {
+        SubtitleKind::from_codec(&self.common.codec_name)
+    }
+
+    pub fn is_text(&self) -> bool {
+        self.kind() == SubtitleKind::Text
+    }
+
+    pub fn is_bitmap(&self) -> bool {
+        self.kind() == SubtitleKind::Bitmap
+    }
+
+    /// Whether `-c:s webvtt` works for this stream. ASS/SSA styling and
+    /// positioning are lost in the conversion.
+    pub fn can_convert_to_webvtt(&self) -> bool {
+        self.is_text()
+    }
+
+    /// Whether the only way to show this stream is to render it into the
+    /// video frames.
+    pub fn requires_burn_in(&self) -> bool {
+        self.is_bitmap()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{StreamCommon, VideoStream};
+
+/// Typed view of a stream's `disposition` flags.
+///
+/// Flags ffprobe reports that are not listed here stay available in
+/// `StreamCommon::disposition`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Disposition {
+    pub default: bool,
+    pub dub: bool,
+    pub original: bool,
+    pub comment: bool,
+    pub lyrics: bool,
+    pub karaoke: bool,
+    pub forced: bool,
+    pub hearing_impaired: bool,
+    pub visual_impaired: bool,
+    pub clean_effects: bool,
+    pub attached_pic: bool,
+    pub timed_thumbnails: bool,
+    pub non_diegetic: bool,
+    pub captions: bool,
+    pub descriptions: bool,
+    pub metadata: bool,
+    pub dependent: bool,
+    pub still_image: bool,
+    pub multilayer: bool,
+}
+
+impl Disposition {
+    pub fn from_map(map: &std::collections::HashMap<String, i64>) -> Self {
+        let flag = |name: &str| map.get(name).is_some_and(|v| *v != 0);
+        Self {
+            default: flag("default"),
+            dub: flag("dub"),
+            original: flag("original"),
+            comment: flag("comment"),
+            lyrics: flag("lyrics"),
+            karaoke: flag("karaoke"),
+            forced: flag("forced"),
+            hearing_impaired: flag("hearing_impaired"),
+            visual_impaired: flag("visual_impaired"),
+            clean_effects: flag("clean_effects"),
+            attached_pic: flag("attached_pic"),
+            timed_thumbnails: flag("timed_thumbnails"),
+            non_diegetic: flag("non_diegetic"),
+            captions: flag("captions"),
+            descriptions: flag("descriptions"),
+            metadata: flag("metadata"),
+            dependent: flag("dependent"),
+            still_image: flag("still_image"),
+            multilayer: flag("multilayer"),
+        }
+    }
+}
+
+impl StreamCommon {
+    pub fn disposition_flags(&self) -> Disposition {
+        Disposition::from_map(&self.disposition)
+    }
+
+    /// Tag lookup ignoring case; Matroska files often use upper case keys.
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str).or_else(|| {
+            self.tags
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.as_str())
+        })
+    }
+
+    /// The `language` tag as an ISO 639-2/B code, `None` if it is unset or
+    /// `und`.
+    pub fn language(&self) -> Option<String> {
+        self.tag("language").and_then(normalize_language)
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.tag("title")
+    }
+
+    pub fn handler_name(&self) -> Option<&str> {
+        self.tag("handler_name")
+    }
+
+    pub fn encoder(&self) -> Option<&str> {
+        self.tag("encoder")
+    }
+
+    /// Clockwise rotation in degrees from the legacy `rotate` tag, in `0..360`.
+    pub fn rotation(&self) -> Option<i64> {
+        let degrees = self.tag("rotate")?.trim().parse::<f64>().ok()?;
+        Some((degrees.round() as i64).rem_euclid(360))
+    }
+}
+
+impl VideoStream {
+    /// Clockwise rotation in degrees, in `0..360`. The display matrix side
+    /// data takes precedence over the `rotate` tag.
+    pub fn rotation(&self) -> Option<i64> {
+        let from_side_data = self.display_matrix().map(|m| m.rotation);
+        match from_side_data {
+            // The display matrix stores the counter-clockwise angle.
+            Some(degrees) => Some((-(degrees.round() as i64)).rem_euclid(360)),
+            None => self.common.rotation(),
+        }
+    }
+}
+
+/// Normalize an ISO 639-1, 639-2/T or 639-2/B code to 639-2/B, the form
+/// Matroska and ffmpeg use. Unknown three letter codes are kept as is.
+pub fn normalize_language(code: &str) -> Option<String> {
+    let code = code.trim().to_ascii_lowercase();
+    // Region suffixes such as `en-US` or `pt_BR`.
+    let code = code.split(['-', '_']).next().unwrap_or("");
+    if code.is_empty() || code == "und" || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    match code.len() {
+        2 => ISO_639_1
+            .iter()
+            .find(|(short, _)| *short == code)
+            .map(|(_, long)| long.to_string()),
+        3 => Some(
+            ISO_639_2_T_TO_B
+                .iter()
+                .find(|(t, _)| *t == code)
+                .map_or(code, |(_, b)| b)
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+#[rustfmt::skip]
+const ISO_639_1: &[(&str, &str)] = &[
+    ("aa", "aar"), ("ab", "abk"), ("ae", "ave"), ("af", "afr"), ("ak", "aka"), ("am", "amh"),
+    ("an", "arg"), ("ar", "ara"), ("as", "asm"), ("av", "ava"), ("ay", "aym"), ("az", "aze"),
+    ("ba", "bak"), ("be", "bel"), ("bg", "bul"), ("bh", "bih"), ("bi", "bis"), ("bm", "bam"),
+    ("bn", "ben"), ("bo", "tib"), ("br", "bre"), ("bs", "bos"), ("ca", "cat"), ("ce", "che"),
+    ("ch", "cha"), ("co", "cos"), ("cr", "cre"), ("cs", "cze"), ("cu", "chu"), ("cv", "chv"),
+    ("cy", "wel"), ("da", "dan"), ("de", "ger"), ("dv", "div"), ("dz", "dzo"), ("ee", "ewe"),
+    ("el", "gre"), ("en", "eng"), ("eo", "epo"), ("es", "spa"), ("et", "est"), ("eu", "baq"),
+    ("fa", "per"), ("ff", "ful"), ("fi", "fin"), ("fj", "fij"), ("fo", "fao"), ("fr", "fre"),
+    ("fy", "fry"), ("ga", "gle"), ("gd", "gla"), ("gl", "glg"), ("gn", "grn"), ("gu", "guj"),
+    ("gv", "glv"), ("ha", "hau"), ("he", "heb"), ("hi", "hin"), ("ho", "hmo"), ("hr", "hrv"),
+    ("ht", "hat"), ("hu", "hun"), ("hy", "arm"), ("hz", "her"), ("ia", "ina"), ("id", "ind"),
+    ("ie", "ile"), ("ig", "ibo"), ("ii", "iii"), ("ik", "ipk"), ("io", "ido"), ("is", "ice"),
+    ("it", "ita"), ("iu", "iku"), ("ja", "jpn"), ("jv", "jav"), ("ka", "geo"), ("kg", "kon"),
+    ("ki", "kik"), ("kj", "kua"), ("kk", "kaz"), ("kl", "kal"), ("km", "khm"), ("kn", "kan"),
+    ("ko", "kor"), ("kr", "kau"), ("ks", "kas"), ("ku", "kur"), ("kv", "kom"), ("kw", "cor"),
+    ("ky", "kir"), ("la", "lat"), ("lb", "ltz"), ("lg", "lug"), ("li", "lim"), ("ln", "lin"),
+    ("lo", "lao"), ("lt", "lit"), ("lu", "lub"), ("lv", "lav"), ("mg", "mlg"), ("mh", "mah"),
+    ("mi", "mao"), ("mk", "mac"), ("ml", "mal"), ("mn", "mon"), ("mr", "mar"), ("ms", "may"),
+    ("mt", "mlt"), ("my", "bur"), ("na", "nau"), ("nb", "nob"), ("nd", "nde"), ("ne", "nep"),
+    ("ng", "ndo"), ("nl", "dut"), ("nn", "nno"), ("no", "nor"), ("nr", "nbl"), ("nv", "nav"),
+    ("ny", "nya"), ("oc", "oci"), ("oj", "oji"), ("om", "orm"), ("or", "ori"), ("os", "oss"),
+    ("pa", "pan"), ("pi", "pli"), ("pl", "pol"), ("ps", "pus"), ("pt", "por"), ("qu", "que"),
+    ("rm", "roh"), ("rn", "run"), ("ro", "rum"), ("ru", "rus"), ("rw", "kin"), ("sa", "san"),
+    ("sc", "srd"), ("sd", "snd"), ("se", "sme"), ("sg", "sag"), ("si", "sin"), ("sk", "slo"),
+    ("sl", "slv"), ("sm", "smo"), ("sn", "sna"), ("so", "som"), ("sq", "alb"), ("sr", "srp"),
+    ("ss", "ssw"), ("st", "sot"), ("su", "sun"), ("sv", "swe"), ("sw", "swa"), ("ta", "tam"),
+    ("te", "tel"), ("tg", "tgk"), ("th", "tha"), ("ti", "tir"), ("tk", "tuk"), ("tl", "tgl"),
+    ("tn", "tsn"), ("to", "ton"), ("tr", "tur"), ("ts", "tso"), ("tt", "tat"), ("tw", "twi"),
+    ("ty", "tah"), ("ug", "uig"), ("uk", "ukr"), ("ur", "urd"), ("uz", "uzb"), ("ve", "ven"),
+    ("vi", "vie"), ("vo", "vol"), ("wa", "wln"), ("wo", "wol"), ("xh", "xho"), ("yi", "yid"),
+    ("yo", "yor"), ("za", "zha"), ("zh", "chi"), ("zu", "zul"),
+];
+
+#[rustfmt::skip]
+const ISO_639_2_T_TO_B: &[(&str, &str)] = &[
+    ("bod", "tib"), ("ces", "cze"), ("cym", "wel"), ("deu", "ger"), ("ell", "gre"), ("eus", "baq"),
+    ("fas", "per"), ("fra", "fre"), ("hye", "arm"), ("isl", "ice"), ("kat", "geo"), ("mkd", "mac"),
+    ("mri", "mao"), ("msa", "may"), ("mya", "bur"), ("nld", "dut"), ("ron", "rum"), ("slk", "slo"),
+    ("sqi", "alb"), ("zho", "chi"),
+];
@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::{run_ffmpeg, Source};
+
+/// Audio is resampled to a multiple of the bucket rate close to this, so
+/// every bucket covers the same whole number of samples.
+const BASE_SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveformOptions {
+    /// Audio stream, as in `0:a:<n>`.
+    pub stream: usize,
+    pub buckets_per_second: u32,
+}
+
+impl Default for WaveformOptions {
+    fn default() -> Self {
+        Self {
+            stream: 0,
+            buckets_per_second: 100,
+        }
+    }
+}
+
+/// Min/max peaks of the audio downmixed to mono, with samples in `-1.0..=1.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub samples_per_bucket: u32,
+    /// `(min, max)` per bucket.
+    pub peaks: Vec<(f32, f32)>,
+}
+
+impl Waveform {
+    pub fn buckets_per_second(&self) -> f64 {
+        self.sample_rate as f64 / self.samples_per_bucket as f64
+    }
+
+    /// Write the peaks in audiowaveform's binary `.dat` format (version 1,
+    /// 16-bit).
+    pub fn write_dat<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        w.write_all(&1i32.to_le_bytes())?;
+        // Flags: 0 for 16-bit values.
+        w.write_all(&0u32.to_le_bytes())?;
+        w.write_all(&(self.sample_rate as i32).to_le_bytes())?;
+        w.write_all(&(self.samples_per_bucket as i32).to_le_bytes())?;
+        w.write_all(&(self.peaks.len() as u32).to_le_bytes())?;
+        for &(min, max) in &self.peaks {
+            w.write_all(&to_i16(min).to_le_bytes())?;
+            w.write_all(&to_i16(max).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn to_dat(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(20 + self.peaks.len() * 4);
+        self.write_dat(&mut out).expect("write to Vec");
+        out
+    }
+}
+
+/// Same conversion as ffmpeg's float to s16.
+fn to_i16(v: f32) -> i16 {
+    (v * 32768.0)
+        .round()
+        .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// Decode audio stream `options.stream` of `source` and reduce it to min/max
+/// peaks, `options.buckets_per_second` of them per second.
+///
+/// The reduction happens in a single pass inside the filter graph
+/// (`asetnsamples` + `astats`), so memory does not grow with the length of
+/// the input; only the peaks are written out and read back.
+pub fn audio_waveform<S: Source + 'static>(
+    source: S,
+    options: &WaveformOptions,
+) -> Result<Waveform, String> {
+    if options.buckets_per_second == 0 {
+        return Err("audio_waveform: buckets_per_second must be positive".to_string());
+    }
+    let samples_per_bucket =
+        ((BASE_SAMPLE_RATE as f64 / options.buckets_per_second as f64).round() as u32).max(1);
+    let sample_rate = samples_per_bucket
+        .checked_mul(options.buckets_per_second)
+        .ok_or("audio_waveform: buckets_per_second too large")?;
+
+    let filters = format!(
+        "aformat=sample_fmts=flt:sample_rates={}:channel_layouts=mono,\
+         asetnsamples=n={}:p=0,\
+         astats=metadata=1:reset=1:measure_perchannel=none:measure_overall=Min_level+Max_level,\
+         ametadata=mode=print:file={{outdir}}/peaks.txt",
+        sample_rate, samples_per_bucket
+    );
+    let args: Vec<String> = vec![
+        "ffmpeg".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-i".to_string(),
+        "{input}".to_string(),
+        "-map".to_string(),
+        format!("0:a:{}", options.stream),
+        "-af".to_string(),
+        filters,
+        "-f".to_string(),
+        "null".to_string(),
+        "-".to_string(),
+    ];
+
+    let output = run_ffmpeg(source, &args)?.wait()?;
+    let path = output.path().join("peaks.txt");
+    let file = File::open(&path).map_err(|e| format!("open {}: {}", path.display(), e))?;
+    Ok(Waveform {
+        sample_rate,
+        samples_per_bucket,
+        peaks: read_peaks(BufReader::new(file))?,
+    })
+}
+
+fn read_peaks<R: BufRead>(reader: R) -> Result<Vec<(f32, f32)>, String> {
+    let mut peaks = Vec::new();
+    let mut current: Option<(Option<f32>, Option<f32>)> = None;
+    let mut finish = |current: Option<(Option<f32>, Option<f32>)>| {
+        if let Some((Some(min), Some(max))) = current {
+            peaks.push((min, max));
+        }
+    };
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("read peaks: {}", e))?;
+        if line.starts_with("frame:") {
+            finish(current.replace((None, None)));
+        } else if let (Some(bucket), Some((key, value))) = (current.as_mut(), line.split_once('='))
+        {
+            let value = value.trim().parse::<f32>().ok();
+            match key {
+                "lavfi.astats.Overall.Min_level" => bucket.0 = value,
+                "lavfi.astats.Overall.Max_level" => bucket.1 = value,
+                _ => {}
+            }
+        }
+    }
+    finish(current);
+    Ok(peaks)
+}
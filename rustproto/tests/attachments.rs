@@ -0,0 +1,136 @@
+use rsproto::{
+    extract_attachments, ffprobe, run_ffmpeg, AttachmentKind, FileSource, RunOutput, Stream,
+};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+#[test]
+fn extract_fonts_cover_and_chapters() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    let work = tempfile::tempdir().expect("tempdir");
+    let font = work.path().join("Subs.ttf");
+    let font_data: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 251) as u8).collect();
+    std::fs::write(&font, &font_data).expect("write font");
+    let meta = work.path().join("chapters.txt");
+    std::fs::write(
+        &meta,
+        ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=2000\ntitle=Opening\n\
+         [CHAPTER]\nTIMEBASE=1/1000\nSTART=2000\nEND=4000\ntitle=Middle\n",
+    )
+    .expect("write chapters");
+
+    let cover = ffmpeg(
+        &input_path,
+        &[
+            "-i",
+            "{input}",
+            "-frames:v",
+            "1",
+            "-update",
+            "1",
+            "{outdir}/cover.jpg",
+        ],
+    );
+    let cover_path = cover.path().join("cover.jpg");
+    let cover_data = std::fs::read(&cover_path).expect("cover");
+
+    let mkv = ffmpeg(
+        &input_path,
+        &[
+            "-i",
+            "{input}",
+            "-f",
+            "ffmetadata",
+            "-i",
+            meta.to_str().unwrap(),
+            "-map",
+            "0:v:0",
+            "-map_chapters",
+            "1",
+            "-c",
+            "copy",
+            "-t",
+            "4",
+            "-attach",
+            font.to_str().unwrap(),
+            "-metadata:s:t:0",
+            "mimetype=font/ttf",
+            "-attach",
+            cover_path.to_str().unwrap(),
+            "-metadata:s:t:1",
+            "mimetype=image/jpeg",
+            "{outdir}/with_attachments.mkv",
+        ],
+    );
+    let mkv_path = mkv.path().join("with_attachments.mkv");
+
+    let probe = block_on(ffprobe(FileSource::new(&mkv_path))).expect("ffprobe run failed");
+    let titles: Vec<Option<&str>> = probe.chapters.iter().map(|c| c.title()).collect();
+    assert_eq!(titles, [Some("Opening"), Some("Middle")]);
+    assert_eq!(probe.chapters[1].start_time, Some(2.0));
+
+    let fonts: Vec<_> = probe.attachments().collect();
+    assert_eq!(fonts.len(), 1);
+    assert_eq!(fonts[0].filename(), Some("Subs.ttf"));
+    assert_eq!(fonts[0].mimetype(), Some("font/ttf"));
+    assert_eq!(fonts[0].size(), Some(font_data.len() as u64));
+    assert!(fonts[0].is_font());
+    assert!(probe.streams.iter().any(|s| matches!(s,
+        Stream::Video(v) if v.common.disposition_flags().attached_pic)));
+
+    let extracted = extract_attachments(FileSource::new(&mkv_path), &probe).expect("extract");
+    assert_eq!(extracted.len(), 2);
+    let font_out = extracted
+        .iter()
+        .find(|e| e.kind == AttachmentKind::Attachment)
+        .expect("font");
+    assert_eq!(font_out.data, font_data);
+    let picture = extracted
+        .iter()
+        .find(|e| e.kind == AttachmentKind::AttachedPicture)
+        .expect("cover art");
+    assert_eq!(picture.mimetype.as_deref(), Some("image/jpeg"));
+    assert_eq!(picture.data, cover_data);
+}
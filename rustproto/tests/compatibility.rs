@@ -0,0 +1,145 @@
+use rsproto::{encoder_available, FfprobeOutput, PlaybackAction, PlaybackTarget};
+
+fn probe(format_name: &str, streams: &str) -> FfprobeOutput {
+    let json = format!(
+        r#"{{
+            "streams": [{}],
+            "format": {{
+                "filename": "input", "nb_streams": 0, "nb_programs": 0,
+                "nb_stream_groups": 0, "format_name": "{}", "format_long_name": "",
+                "probe_score": 100
+            }}
+        }}"#,
+        streams, format_name
+    );
+    serde_json::from_str(&json).expect("parse sample")
+}
+
+fn stream(index: usize, codec_type: &str, codec_name: &str, extra: &str) -> String {
+    format!(
+        r#"{{"index": {}, "codec_type": "{}", "codec_name": "{}", "codec_long_name": "",
+            "codec_tag": "0x0000", "r_frame_rate": "0/0", "avg_frame_rate": "0/0",
+            "time_base": "1/1000"{}}}"#,
+        index, codec_type, codec_name, extra
+    )
+}
+
+const H264_MP4: &str = r#", "profile": "High", "codec_tag_string": "avc1", "width": 1920,
+    "height": 1080, "pix_fmt": "yuv420p", "level": 40"#;
+const HEVC_HEV1: &str = r#", "profile": "Main 10", "codec_tag_string": "hev1", "width": 3840,
+    "height": 2160, "pix_fmt": "yuv420p10le", "level": 150, "color_transfer": "smpte2084""#;
+const AAC_STEREO: &str = r#", "profile": "LC", "codec_tag_string": "mp4a", "sample_rate": 48000,
+    "channels": 2, "channel_layout": "stereo", "bits_per_sample": 0"#;
+const EAC3_51: &str = r#", "profile": "unknown", "codec_tag_string": "[0][0][0][0]",
+    "sample_rate": 48000, "channels": 6, "channel_layout": "5.1(side)", "bits_per_sample": 0"#;
+const SUBTITLE: &str = r#", "profile": "unknown", "codec_tag_string": "[0][0][0][0]""#;
+
+#[test]
+fn mp4_h264_aac_direct_plays_everywhere() {
+    let probe = probe(
+        "mov,mp4,m4a,3gp,3g2,mj2",
+        &[
+            stream(0, "video", "h264", H264_MP4),
+            stream(1, "audio", "aac", AAC_STEREO),
+        ]
+        .join(","),
+    );
+    for target in [
+        PlaybackTarget::SafariHls,
+        PlaybackTarget::Chromecast,
+        PlaybackTarget::ExoPlayer,
+        PlaybackTarget::Mse,
+    ] {
+        let compat = probe.compatibility(target);
+        assert!(compat.can_direct_play(), "{:?}: {:?}", target, compat);
+        assert_eq!(
+            compat.ffmpeg_args(),
+            ["-map", "0:0", "-c:v:0", "copy", "-map", "0:1", "-c:a:0", "copy"]
+        );
+    }
+}
+
+#[test]
+fn mkv_hevc_for_safari_and_mse() {
+    let probe = probe(
+        "matroska,webm",
+        &[
+            stream(0, "video", "hevc", HEVC_HEV1),
+            stream(1, "audio", "eac3", EAC3_51),
+            stream(2, "subtitle", "subrip", SUBTITLE),
+            stream(3, "subtitle", "hdmv_pgs_subtitle", SUBTITLE),
+        ]
+        .join(","),
+    );
+
+    let safari = probe.compatibility(PlaybackTarget::SafariHls);
+    assert!(!safari.container_supported);
+    let actions: Vec<PlaybackAction> = safari.streams.iter().map(|s| s.action).collect();
+    assert_eq!(
+        actions,
+        [
+            PlaybackAction::Remux,
+            PlaybackAction::Remux,
+            PlaybackAction::Transcode,
+            PlaybackAction::Skip
+        ]
+    );
+    assert_eq!(safari.action(), PlaybackAction::Transcode);
+    assert_eq!(
+        safari.ffmpeg_args(),
+        [
+            "-map", "0:0", "-c:v:0", "copy", "-tag:v:0", "hvc1", "-map", "0:1", "-c:a:0", "copy",
+            "-map", "0:2", "-c:s:0", "webvtt"
+        ]
+    );
+
+    let mse = probe.compatibility(PlaybackTarget::Mse);
+    let video = &mse.streams[0];
+    assert_eq!(video.action, PlaybackAction::Transcode);
+    assert!(video.reasons.iter().any(|r| r.contains("tone mapping")));
+    assert_eq!(mse.streams[1].action, PlaybackAction::Transcode);
+    assert!(mse.streams[1]
+        .args
+        .windows(2)
+        .any(|w| w == ["-ac:a:0", "2"]));
+
+    let exo = probe.compatibility(PlaybackTarget::ExoPlayer);
+    assert_eq!(exo.action(), PlaybackAction::DirectPlay);
+    assert_eq!(exo.streams[3].action, PlaybackAction::DirectPlay);
+}
+
+#[test]
+fn video_transcode_uses_an_available_encoder() {
+    assert!(encoder_available("aac"));
+    assert!(!encoder_available("no_such_encoder"));
+
+    let probe = probe("matroska,webm", &stream(0, "video", "hevc", HEVC_HEV1));
+    let video = &probe.compatibility(PlaybackTarget::Mse).streams[0];
+    assert_eq!(video.action, PlaybackAction::Transcode);
+    let at = video
+        .args
+        .iter()
+        .position(|a| a == "-c:v:0")
+        .expect("codec");
+    let encoder = video.args[at + 1].as_str();
+    if encoder == "h264" {
+        assert!(video.reasons.iter().any(|r| r.contains("no H.264 encoder")));
+    } else {
+        assert!(encoder_available(encoder), "{}", encoder);
+    }
+    // The profile and level options are libx264's.
+    let has_profile = video.args.iter().any(|a| a == "-profile:v:0");
+    assert_eq!(has_profile, encoder == "libx264");
+}
+
+#[test]
+fn bit_depth_from_pix_fmt() {
+    let probe = probe(
+        "mov,mp4,m4a,3gp,3g2,mj2",
+        &stream(0, "video", "hevc", HEVC_HEV1),
+    );
+    match &probe.streams[0] {
+        rsproto::Stream::Video(v) => assert_eq!(v.bit_depth(), 10),
+        other => panic!("expected video, got {:?}", other),
+    }
+}
@@ -1,44 +1,11 @@
-use rsproto::{run_ffmpeg, FileSource};
-use sha2::{Digest, Sha256};
+use rsproto::{run_ffmpeg, ArtifactRole, FileSource};
 use std::env;
-use std::fs;
-use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::thread;
 
 const DEFAULT_INPUT: &str =
     "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
 
-fn list_files(base: &Path, rel: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
-    let path = base.join(rel);
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        let name = entry.file_name();
-        let rel_path = rel.join(name);
-        if file_type.is_dir() {
-            list_files(base, &rel_path, out)?;
-        } else if file_type.is_file() {
-            out.push(rel_path);
-        }
-    }
-    Ok(())
-}
-
-fn sha256_file(path: &Path) -> std::io::Result<Vec<u8>> {
-    let mut f = fs::File::open(path)?;
-    let mut hasher = Sha256::new();
-    let mut buf = [0u8; 16384];
-    loop {
-        let n = f.read(&mut buf)?;
-        if n == 0 {
-            break;
-        }
-        hasher.update(&buf[..n]);
-    }
-    Ok(hasher.finalize().to_vec())
-}
-
 #[test]
 fn concurrent_parity() {
     let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
@@ -59,23 +26,120 @@ fn concurrent_parity() {
     let r2 = t2.join().expect("thread 2 join");
     let direct_handle = r1.expect("direct run failed");
     let proto_handle = r2.expect("proto run failed");
-    let direct_dir = direct_handle.wait().expect("direct wait failed");
-    let proto_dir = proto_handle.wait().expect("proto wait failed");
-
-    let mut direct_files = Vec::new();
-    let mut proto_files = Vec::new();
-    list_files(direct_dir.path(), Path::new("."), &mut direct_files).expect("list direct");
-    list_files(proto_dir.path(), Path::new("."), &mut proto_files).expect("list proto");
-    direct_files.sort();
-    proto_files.sort();
+    let mut direct = direct_handle.wait().expect("direct wait failed");
+    let mut proto = proto_handle.wait().expect("proto wait failed");
+    direct.hash_files().expect("sha direct");
+    proto.hash_files().expect("sha proto");
 
+    let direct_files: Vec<&PathBuf> = direct.files.iter().map(|f| &f.path).collect();
+    let proto_files: Vec<&PathBuf> = proto.files.iter().map(|f| &f.path).collect();
     assert_eq!(direct_files, proto_files, "file lists differ");
 
-    for rel in direct_files {
-        let a = sha256_file(&direct_dir.path().join(&rel)).expect("sha direct");
-        let b = sha256_file(&proto_dir.path().join(&rel)).expect("sha proto");
-        assert_eq!(a, b, "hash mismatch for {:?}", rel);
+    for (a, b) in direct.files.iter().zip(&proto.files) {
+        assert_eq!(a.role, b.role, "role mismatch for {:?}", a.path);
+        assert_eq!(a.sha256, b.sha256, "hash mismatch for {:?}", a.path);
+    }
+
+    assert!(direct.file("out.m3u8").is_some(), "playlist missing");
+    assert!(direct.file("init.mp4").is_some(), "init segment missing");
+    assert_eq!(
+        direct.file("init.mp4").map(|f| f.role),
+        Some(ArtifactRole::InitSegment)
+    );
+    assert!(
+        direct.total_duration().unwrap_or(0.0) > 0.0,
+        "no segment durations"
+    );
+}
+
+#[test]
+fn absolute_segment_uris_in_subdirectory() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    // The `%v` directory is created by the muxer, and the base URL makes the
+    // playlist list absolute paths into it.
+    let args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-i",
+        "{input}",
+        "-c:v",
+        "copy",
+        "-c:a",
+        "aac",
+        "-f",
+        "hls",
+        "-hls_time",
+        "4",
+        "-hls_list_size",
+        "0",
+        "-var_stream_map",
+        "v:0,a:0",
+        "-hls_base_url",
+        "{outdir}/v0/",
+        "-hls_segment_filename",
+        "{outdir}/v%v/seg%d.ts",
+        "{outdir}/index.m3u8",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let out = run_ffmpeg(FileSource::new(&input_path), &args)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed");
+
+    let playlist = std::fs::read_to_string(out.path().join("index.m3u8")).expect("playlist");
+    assert!(playlist.contains(&format!("{}/v0/seg0.ts", out.path().display())));
+    let seg = out.file("v0/seg0.ts").expect("segment missing");
+    assert_eq!(seg.role, ArtifactRole::MediaSegment);
+    assert!(seg.duration.unwrap_or(0.0) > 0.0, "{:?}", seg);
+    assert!(
+        out.total_duration().unwrap_or(0.0) > 0.0,
+        "no segment durations"
+    );
+}
+
+#[test]
+fn no_segments_no_total_duration() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
     }
+
+    let args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-i",
+        "{input}",
+        "-t",
+        "2",
+        "-c:v",
+        "copy",
+        "-c:a",
+        "aac",
+        "{outdir}/out.mp4",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let out = run_ffmpeg(FileSource::new(&input_path), &args)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed");
+
+    assert!(out.file("out.mp4").is_some(), "output missing");
+    assert_eq!(out.files_with_role(ArtifactRole::MediaSegment).count(), 0);
+    assert_eq!(out.total_duration(), None);
 }
 
 fn run_args(input: &str, outdir: &str) -> Vec<String> {
@@ -0,0 +1,155 @@
+use rsproto::dash::{self, expand_template, Mpd, TemplateVars};
+use std::time::Duration;
+
+const FFMPEG_MPD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<MPD xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"
+	xmlns="urn:mpeg:dash:schema:mpd:2011"
+	profiles="urn:mpeg:dash:profile:isoff-live:2011"
+	type="static"
+	mediaPresentationDuration="PT10.0S"
+	maxSegmentDuration="PT4.0S"
+	minBufferTime="PT8.0S">
+	<ProgramInformation>
+	</ProgramInformation>
+	<Period id="0" start="PT0.0S">
+		<AdaptationSet id="0" contentType="video" startWithSAP="1" segmentAlignment="true" frameRate="24/1">
+			<Representation id="0" mimeType="video/mp4" codecs="avc1.64001f" bandwidth="1000000" width="1280" height="720" sar="1:1">
+				<SegmentTemplate timescale="12288" initialization="init-$RepresentationID$.mp4" media="chunk-$RepresentationID$-$Number%05d$.m4s" startNumber="1">
+					<SegmentTimeline>
+						<S t="0" d="49152" r="1" />
+						<S d="24576" />
+					</SegmentTimeline>
+				</SegmentTemplate>
+			</Representation>
+		</AdaptationSet>
+		<AdaptationSet id="1" contentType="audio" lang="eng">
+			<Representation id="1" mimeType="audio/mp4" codecs="mp4a.40.2" bandwidth="128000" audioSamplingRate="48000">
+				<AudioChannelConfiguration schemeIdUri="urn:mpeg:dash:23003:3:audio_channel_configuration:2011" value="2" />
+				<SegmentTemplate timescale="48000" initialization="init-$RepresentationID$.mp4" media="chunk-$RepresentationID$-$Number%05d$.m4s" startNumber="1">
+					<SegmentTimeline>
+						<S t="0" d="192000" r="-1" />
+					</SegmentTimeline>
+				</SegmentTemplate>
+			</Representation>
+		</AdaptationSet>
+	</Period>
+</MPD>
+"#;
+
+#[test]
+fn parse_ffmpeg_manifest() {
+    let mpd = Mpd::parse(FFMPEG_MPD).expect("parse");
+    assert_eq!(mpd.mpd_type.as_deref(), Some("static"));
+    assert_eq!(
+        mpd.media_presentation_duration,
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(mpd.periods.len(), 1);
+    let sets = &mpd.periods[0].adaptation_sets;
+    assert_eq!(sets.len(), 2);
+    assert_eq!(sets[1].lang.as_deref(), Some("eng"));
+    let video = mpd.representation("0").expect("video representation");
+    assert_eq!((video.width, video.height), (Some(1280), Some(720)));
+    let audio = mpd.representation("1").expect("audio representation");
+    assert_eq!(audio.audio_sampling_rate, Some(48000));
+    assert_eq!(audio.other_elements[0].name, "AudioChannelConfiguration");
+}
+
+#[test]
+fn enumerate_segments() {
+    let mpd = Mpd::parse(FFMPEG_MPD).expect("parse");
+    let segments = mpd.segments().expect("segments");
+
+    let video: Vec<_> = segments
+        .iter()
+        .filter(|s| s.representation_id == "0")
+        .collect();
+    let urls: Vec<&str> = video.iter().map(|s| s.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        vec![
+            "init-0.mp4",
+            "chunk-0-00001.m4s",
+            "chunk-0-00002.m4s",
+            "chunk-0-00003.m4s"
+        ]
+    );
+    assert!(video[0].is_init());
+    assert_eq!((video[2].start, video[2].end), (4.0, 8.0));
+    assert_eq!(video[3].duration(), 2.0);
+
+    // `r="-1"` repeats up to the presentation duration.
+    let audio_media = segments
+        .iter()
+        .filter(|s| s.representation_id == "1" && !s.is_init())
+        .count();
+    assert_eq!(audio_media, 3);
+}
+
+#[test]
+fn unbounded_timelines_are_rejected() {
+    let audio_timeline = r#"<S t="0" d="192000" r="-1" />"#;
+    let with_timeline = |timeline: &str, duration: &str| {
+        let text = FFMPEG_MPD
+            .replace(audio_timeline, timeline)
+            .replace("PT10.0S", duration);
+        Mpd::parse(&text).expect("parse")
+    };
+
+    let huge_repeat = with_timeline(r#"<S t="0" d="48000" r="4000000000" />"#, "PT10.0S");
+    let err = huge_repeat.segments().expect_err("huge r");
+    assert!(err.to_string().contains("segments"), "{}", err);
+
+    // r="-1" repeats up to the end of a period lasting years.
+    let open_ended = with_timeline(r#"<S t="0" d="1" r="-1" />"#, "PT100000000.0S");
+    let err = open_ended.segments().expect_err("open-ended r");
+    assert!(err.to_string().contains("segments"), "{}", err);
+
+    let overflow = with_timeline(r#"<S t="18446744073709551610" d="10" r="1" />"#, "PT10.0S");
+    let err = overflow.segments().expect_err("time overflow");
+    assert!(err.to_string().contains("overflow"), "{}", err);
+}
+
+#[test]
+fn serialize_round_trip() {
+    let mpd = Mpd::parse(FFMPEG_MPD).expect("parse");
+    let text = mpd.to_string();
+    assert!(text.starts_with("<?xml"));
+    let reparsed = Mpd::parse(&text).expect("reparse");
+    assert_eq!(mpd, reparsed);
+}
+
+#[test]
+fn template_expansion() {
+    let vars = TemplateVars {
+        representation_id: "v1",
+        number: Some(7),
+        time: Some(90000),
+        bandwidth: Some(500000),
+    };
+    assert_eq!(
+        expand_template(
+            "$RepresentationID$/$Number%05d$-$Time$-$Bandwidth$$$.m4s",
+            &vars
+        ),
+        "v1/00007-90000-500000$.m4s"
+    );
+    assert_eq!(expand_template("$Unknown$", &vars), "$Unknown$");
+}
+
+#[test]
+fn xs_duration() {
+    assert_eq!(
+        dash::parse_duration("PT1M34.5S"),
+        Some(Duration::from_millis(94_500))
+    );
+    assert_eq!(
+        dash::parse_duration("P1DT1H"),
+        Some(Duration::from_secs(90_000))
+    );
+    assert_eq!(dash::parse_duration("1M"), None);
+    assert_eq!(
+        dash::format_duration(Duration::from_millis(94_500)),
+        "PT0H1M34.500S"
+    );
+}
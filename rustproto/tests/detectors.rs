@@ -0,0 +1,256 @@
+use rsproto::{
+    analyze_media, detect_black, detect_episode_markers, detect_scenes, detect_silence, run_ffmpeg,
+    BlackOptions, CancelMode, EpisodeMarkers, FileSource, MarkerOptions, MediaAnalysis, RunOutput,
+    SceneChange, SceneOptions, SilenceOptions, TimeRange,
+};
+use std::env;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+/// 20 s of picture and tone with black, silent gaps at 0-2 s, 8-9 s,
+/// 14-15 s and from 17 s to the end.
+fn gapped_clip() -> (RunOutput, PathBuf) {
+    let graph = "color=black:s=160x120:r=25:d=2[v0];anullsrc=r=48000:cl=mono:d=2[a0];\
+                 testsrc=s=160x120:r=25:d=6[v1];sine=f=440:r=48000:d=6[a1];\
+                 color=black:s=160x120:r=25:d=1[v2];anullsrc=r=48000:cl=mono:d=1[a2];\
+                 testsrc2=s=160x120:r=25:d=5[v3];sine=f=660:r=48000:d=5[a3];\
+                 color=black:s=160x120:r=25:d=1[v4];anullsrc=r=48000:cl=mono:d=1[a4];\
+                 testsrc=s=160x120:r=25:d=2[v5];sine=f=880:r=48000:d=2[a5];\
+                 color=black:s=160x120:r=25:d=3[v6];anullsrc=r=48000:cl=mono:d=3[a6];\
+                 [v0][a0][v1][a1][v2][a2][v3][a3][v4][a4][v5][a5][v6][a6]\
+                 concat=n=7:v=1:a=1[v][a]";
+    let out = ffmpeg(
+        &input_path(),
+        &[
+            "-i",
+            "{input}",
+            "-filter_complex",
+            graph,
+            "-map",
+            "[v]",
+            "-map",
+            "[a]",
+            "-c:v",
+            "mpeg4",
+            "-q:v",
+            "2",
+            "-c:a",
+            "pcm_s16le",
+            "{outdir}/gapped.mkv",
+        ],
+    );
+    let path = out.path().join("gapped.mkv");
+    (out, path)
+}
+
+fn assert_range(actual: &TimeRange, start: f64, end: f64) {
+    assert!(
+        (actual.start - start).abs() < 0.1 && (actual.end - end).abs() < 0.1,
+        "{:?} is not {}..{}",
+        actual,
+        start,
+        end
+    );
+}
+
+#[test]
+fn black_silence_and_scenes() {
+    let (_clip, path) = gapped_clip();
+
+    let black = detect_black(
+        FileSource::new(&path),
+        &BlackOptions {
+            min_duration: 0.5,
+            ..Default::default()
+        },
+    )
+    .expect("start")
+    .wait()
+    .expect("detect_black");
+    assert_eq!(black.len(), 4, "{:?}", black);
+    assert_range(&black[0], 0.0, 2.0);
+    assert_range(&black[1], 8.0, 9.0);
+    assert_range(&black[2], 14.0, 15.0);
+    // Still black at the end of the input.
+    assert_range(&black[3], 17.0, 19.96);
+
+    // The filter's default minimum drops the 1 s gaps.
+    let long = detect_black(FileSource::new(&path), &BlackOptions::default())
+        .expect("start")
+        .wait()
+        .expect("detect_black");
+    assert_eq!(long.len(), 2, "{:?}", long);
+
+    let silence = detect_silence(
+        FileSource::new(&path),
+        &SilenceOptions {
+            min_duration: 0.5,
+            ..Default::default()
+        },
+    )
+    .expect("start")
+    .wait()
+    .expect("detect_silence");
+    assert_eq!(silence.len(), 4, "{:?}", silence);
+    assert_range(&silence[0], 0.0, 2.0);
+    assert_range(&silence[1], 8.0, 9.0);
+    assert_range(&silence[2], 14.0, 15.0);
+    assert!((silence[3].start - 17.0).abs() < 0.1, "{:?}", silence[3]);
+
+    let scenes = detect_scenes(FileSource::new(&path), &SceneOptions::default())
+        .expect("start")
+        .wait()
+        .expect("detect_scenes");
+    for cut in [2.0, 8.0, 9.0, 14.0, 15.0, 17.0] {
+        assert!(
+            scenes.iter().any(|s| (s.time - cut).abs() < 0.05),
+            "no cut at {}: {:?}",
+            cut,
+            scenes
+        );
+    }
+    assert!(scenes.iter().all(|s| s.score >= 10.0));
+
+    let all = analyze_media(
+        FileSource::new(&path),
+        Some(&BlackOptions {
+            min_duration: 0.5,
+            ..Default::default()
+        }),
+        None,
+        Some(&SceneOptions::default()),
+    )
+    .expect("start")
+    .wait()
+    .expect("analyze_media");
+    assert_eq!(all.black, black);
+    assert_eq!(all.scenes, scenes);
+    assert!(all.silence.is_empty());
+    assert!(
+        (all.duration.unwrap() - 19.96).abs() < 0.1,
+        "{:?}",
+        all.duration
+    );
+}
+
+#[test]
+fn markers_for_generated_episode() {
+    let (_clip, path) = gapped_clip();
+    let options = MarkerOptions {
+        intro_min: 4.0,
+        intro_max: 10.0,
+        credits_min: 2.0,
+        ..Default::default()
+    };
+    let markers = detect_episode_markers(FileSource::new(&path), &options)
+        .expect("start")
+        .wait()
+        .expect("detect_episode_markers");
+    let intro = markers.intro.expect("intro");
+    assert_range(&intro.range, 2.0, 9.0);
+    // Black and silent on both ends, but testsrc doesn't cut like an intro.
+    assert_eq!(intro.confidence, 0.8);
+    let credits = markers.credits.expect("credits");
+    assert_range(&credits.range, 14.0, 19.96);
+}
+
+fn range(start: f64, end: f64) -> TimeRange {
+    TimeRange { start, end }
+}
+
+#[test]
+fn marker_heuristic() {
+    // Cold open until 60 s, a cut-heavy intro until 120 s, calm credits
+    // from 1200 s.
+    let mut scenes: Vec<SceneChange> = (0..30)
+        .map(|i| SceneChange {
+            time: 61.0 + i as f64 * 2.0,
+            score: 40.0,
+        })
+        .collect();
+    scenes.extend((0..100).map(|i| SceneChange {
+        time: 130.0 + i as f64 * 10.0,
+        score: 30.0,
+    }));
+    let analysis = MediaAnalysis {
+        black: vec![
+            range(59.5, 60.5),
+            range(120.0, 121.0),
+            range(600.0, 600.4),
+            range(1200.0, 1201.0),
+        ],
+        silence: vec![
+            range(59.4, 60.6),
+            range(120.2, 121.2),
+            range(900.0, 901.0),
+            range(1199.8, 1201.0),
+        ],
+        scenes,
+        duration: Some(1300.0),
+    };
+    let options = MarkerOptions::default();
+    let markers = EpisodeMarkers::from_analysis(analysis.clone(), &options);
+    let intro = markers.intro.expect("intro");
+    assert_eq!(intro.range, range(60.6, 121.2));
+    assert_eq!(intro.confidence, 1.0);
+    let credits = markers.credits.expect("credits");
+    assert_eq!(credits.range, range(1199.8, 1300.0));
+    assert_eq!(credits.confidence, 1.0);
+
+    // Silence alone is not enough to propose anything.
+    let quiet = MediaAnalysis {
+        black: Vec::new(),
+        ..analysis.clone()
+    };
+    let markers = EpisodeMarkers::from_analysis(quiet, &options);
+    assert_eq!(markers.intro, None);
+    assert_eq!(markers.credits, None);
+
+    // Credits need a known duration.
+    let unknown = MediaAnalysis {
+        duration: None,
+        ..analysis
+    };
+    let markers = EpisodeMarkers::from_analysis(unknown, &options);
+    assert!(markers.intro.is_some());
+    assert_eq!(markers.credits, None);
+}
+
+#[test]
+fn cancel_detection() {
+    let detection =
+        detect_scenes(FileSource::new(input_path()), &SceneOptions::default()).expect("start");
+    detection
+        .cancel_handle()
+        .cancel_with("user skipped", CancelMode::Abort);
+    let err = detection.wait().expect_err("cancelled detection");
+    assert!(err.contains("user skipped"), "{}", err);
+
+    let detection =
+        detect_black(FileSource::new(input_path()), &BlackOptions::default()).expect("start");
+    detection
+        .cancel_handle()
+        .cancel_with("graceful", CancelMode::Graceful);
+    let err = detection.wait().expect_err("cancelled detection");
+    assert!(err.contains("graceful"), "{}", err);
+}
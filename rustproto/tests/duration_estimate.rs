@@ -0,0 +1,205 @@
+use rsproto::{
+    ffprobe, run_ffmpeg, scan_packets, FfprobeOutput, FileSource, Provenance, RunOutput,
+};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn probe(format_extra: &str, streams: &[String]) -> FfprobeOutput {
+    let json = format!(
+        r#"{{
+            "streams": [{}],
+            "format": {{
+                "filename": "input", "nb_streams": 0, "nb_programs": 0,
+                "nb_stream_groups": 0, "format_name": "matroska,webm", "format_long_name": "",
+                "probe_score": 100{}
+            }}
+        }}"#,
+        streams.join(","),
+        format_extra
+    );
+    serde_json::from_str(&json).expect("parse sample")
+}
+
+fn stream(index: usize, codec_type: &str, extra: &str) -> String {
+    let typed = match codec_type {
+        "video" => r#", "width": 1920, "height": 1080, "pix_fmt": "yuv420p", "level": 40"#,
+        "audio" => {
+            r#", "sample_fmt": "fltp", "sample_rate": "48000", "channels": 2,
+            "channel_layout": "stereo", "bits_per_sample": 0"#
+        }
+        _ => "",
+    };
+    format!(
+        r#"{{"index": {}, "codec_type": "{}", "codec_name": "x", "codec_long_name": "",
+            "profile": "", "codec_tag_string": "[0][0][0][0]", "codec_tag": "0x0000",
+            "r_frame_rate": "0/0", "avg_frame_rate": "0/0", "time_base": "1/1000"{}{}}}"#,
+        index, codec_type, typed, extra
+    )
+}
+
+#[test]
+fn matroska_statistics_tags() {
+    let parsed = probe(
+        "",
+        &[
+            stream(
+                0,
+                "video",
+                r#", "tags": {"BPS-eng": "5000000", "DURATION-eng": "00:01:30.500000000"}"#,
+            ),
+            stream(
+                1,
+                "audio",
+                r#", "tags": {"NUMBER_OF_BYTES": "1200000", "DURATION": "00:01:00.000000000"}"#,
+            ),
+        ],
+    );
+
+    let duration = parsed.best_duration().expect("duration");
+    assert_eq!(duration.value, 90.5);
+    assert_eq!(duration.provenance, Provenance::Tag);
+    assert!(!duration.is_estimate());
+
+    let video = parsed.stream_bit_rate(0).expect("video bitrate");
+    assert_eq!(
+        (video.value, video.provenance),
+        (5_000_000, Provenance::Tag)
+    );
+    let audio = parsed.stream_bit_rate(1).expect("audio bitrate");
+    assert_eq!((audio.value, audio.provenance), (160_000, Provenance::Tag));
+    assert_eq!(parsed.stream_duration(1).unwrap().value, 60.0);
+    assert!(parsed.stream_bit_rate(7).is_none());
+}
+
+#[test]
+fn container_fallbacks() {
+    let parsed = probe(
+        r#", "duration": "100.000000", "bit_rate": "2128000""#,
+        &[
+            stream(0, "video", ""),
+            stream(1, "audio", r#", "bit_rate": "128000""#),
+        ],
+    );
+    let duration = parsed.best_duration().unwrap();
+    assert_eq!(
+        (duration.value, duration.provenance),
+        (100.0, Provenance::Format)
+    );
+
+    let stream_duration = parsed.stream_duration(0).unwrap();
+    assert_eq!(stream_duration.provenance, Provenance::Estimated);
+    assert!(stream_duration.is_estimate());
+
+    let video = parsed.stream_bit_rate(0).unwrap();
+    assert_eq!(
+        (video.value, video.provenance),
+        (2_000_000, Provenance::Estimated)
+    );
+    let audio = parsed.stream_bit_rate(1).unwrap();
+    assert_eq!(audio.provenance, Provenance::Stream);
+
+    // Subtitles without a bitrate don't block the estimate; known ones are
+    // subtracted.
+    let parsed = probe(
+        r#", "bit_rate": "2128000""#,
+        &[
+            stream(0, "video", ""),
+            stream(1, "audio", r#", "bit_rate": "128000""#),
+            stream(2, "subtitle", ""),
+            stream(3, "data", ""),
+            stream(4, "subtitle", r#", "bit_rate": "8000""#),
+        ],
+    );
+    assert_eq!(parsed.stream_bit_rate(0).unwrap().value, 1_992_000);
+
+    // With two unknown streams the remainder cannot be attributed.
+    let parsed = probe(
+        r#", "bit_rate": "2128000""#,
+        &[stream(0, "video", ""), stream(1, "audio", "")],
+    );
+    assert!(parsed.stream_bit_rate(0).is_none());
+    assert!(parsed.best_duration().is_none());
+}
+
+fn remux(input: &str) -> RunOutput {
+    let args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-i",
+        "{input}",
+        "-map",
+        "0:v:0",
+        "-c",
+        "copy",
+        "-t",
+        "4",
+        "{outdir}/clip.mkv",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    run_ffmpeg(FileSource::new(input), &args)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+#[test]
+fn matroska_clip_and_packet_scan() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    let out = remux(&input_path);
+    let clip = out.path().join("clip.mkv");
+    let parsed = block_on(ffprobe(FileSource::new(&clip))).expect("ffprobe run failed");
+    let stream = parsed.streams[0].common();
+    assert!(stream.duration.is_none());
+    let tagged = stream.best_duration().expect("DURATION tag");
+    assert_eq!(tagged.provenance, Provenance::Tag);
+
+    let scan = block_on(scan_packets(FileSource::new(&clip))).expect("packet scan");
+    let scanned = scan.stream_duration(0).expect("scanned duration");
+    assert_eq!(scanned.provenance, Provenance::PacketScan);
+    assert!(
+        (scanned.value - tagged.value).abs() < 0.5,
+        "{scanned:?} vs {tagged:?}"
+    );
+    assert!(scan.streams[&0].packets > 0);
+    let bytes = scan.streams[&0].bytes as f64;
+    let rate = scan.stream_bit_rate(0).expect("scanned bitrate");
+    assert_eq!(rate.value, (bytes * 8.0 / scanned.value).round() as i64);
+    assert!(scan.duration().is_some());
+}
@@ -1,4 +1,5 @@
-use rsproto::{ffprobe, FileSource, Stream};
+use rsproto::{ffprobe, FfprobeOutput, FileSource, Stream};
+use serde_json::{json, Value};
 use std::env;
 use std::path::Path;
 
@@ -46,3 +47,263 @@ fn parse_ffprobe_json_reference() {
         Stream::Video(_) | Stream::Audio(_) | Stream::Subtitle(_) | Stream::Other(_)
     ));
 }
+
+const SAMPLE_JSON: &str = r#"{
+    "streams": [
+        {
+            "index": 0,
+            "codec_name": "h264",
+            "codec_long_name": "H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10",
+            "profile": "High",
+            "codec_type": "video",
+            "codec_tag_string": "avc1",
+            "codec_tag": "0x31637661",
+            "width": 1280,
+            "height": 720,
+            "has_b_frames": 2,
+            "sample_aspect_ratio": "1:1",
+            "display_aspect_ratio": "16:9",
+            "pix_fmt": "yuv420p",
+            "level": 31,
+            "r_frame_rate": "24/1",
+            "avg_frame_rate": "24/1",
+            "time_base": "1/12288",
+            "start_time": "0.000000",
+            "duration": "596.458333",
+            "bit_rate": "2500000",
+            "disposition": {"default": 1, "attached_pic": 0},
+            "tags": {"language": "und", "handler_name": "VideoHandler"}
+        },
+        {
+            "index": 1,
+            "codec_name": "aac",
+            "codec_long_name": "AAC (Advanced Audio Coding)",
+            "profile": "LC",
+            "codec_type": "audio",
+            "codec_tag_string": "mp4a",
+            "codec_tag": "0x6134706d",
+            "sample_fmt": "fltp",
+            "sample_rate": "48000",
+            "channels": 2,
+            "channel_layout": "stereo",
+            "bits_per_sample": 0,
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0",
+            "time_base": "1/48000",
+            "start_time": "N/A",
+            "duration": "596.474667",
+            "bit_rate": "N/A",
+            "disposition": {"default": 1}
+        },
+        {
+            "index": 2,
+            "codec_name": "bin_data",
+            "codec_long_name": "binary data",
+            "profile": "unknown",
+            "codec_type": "data",
+            "codec_tag_string": "text",
+            "codec_tag": "0x74786574",
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0",
+            "time_base": "1/1000"
+        }
+    ],
+    "format": {
+        "filename": "input",
+        "nb_streams": 3,
+        "nb_programs": 0,
+        "nb_stream_groups": 0,
+        "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+        "format_long_name": "QuickTime / MOV",
+        "start_time": "0.000000",
+        "duration": "596.474667",
+        "size": "158008374",
+        "bit_rate": "2119234",
+        "probe_score": 100,
+        "tags": {"major_brand": "isom"}
+    },
+    "programs": []
+}"#;
+
+fn round_trip(parsed: &FfprobeOutput) {
+    let first = serde_json::to_value(parsed).expect("serialize");
+    let reparsed: FfprobeOutput = serde_json::from_value(first.clone()).expect("re-parse");
+    let second = serde_json::to_value(&reparsed).expect("serialize again");
+    assert_eq!(first, second);
+}
+
+#[test]
+fn serialize_round_trip_sample() {
+    let parsed: FfprobeOutput = serde_json::from_str(SAMPLE_JSON).expect("parse sample");
+    round_trip(&parsed);
+
+    let value = serde_json::to_value(&parsed).expect("serialize");
+    assert!(
+        value.get("programs").is_some(),
+        "unknown top-level keys are kept"
+    );
+    let video = &value["streams"][0];
+    assert_eq!(video["codec_type"], "video");
+    assert_eq!(video["time_base"], "1/12288");
+    assert_eq!(video["has_b_frames"], 2);
+    let audio = &value["streams"][1];
+    assert_eq!(audio["sample_fmt"], "fltp");
+    assert!(audio.get("bit_rate").is_none());
+    assert!(matches!(parsed.streams[2], Stream::Other(_)));
+    assert_eq!(value["streams"][2]["codec_type"], "data");
+}
+
+#[test]
+fn serialize_round_trip_probe() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    let parsed = block_on(ffprobe(FileSource::new(&input_path))).expect("ffprobe run failed");
+    round_trip(&parsed);
+}
+
+/// Required fields whose `N/A` ffprobe output cannot be represented, by
+/// JSON pointer and the path reported for them.
+const REQUIRED_NUMERIC: &[(&str, &str)] = &[
+    ("/format/nb_streams", "format.nb_streams"),
+    ("/format/nb_programs", "format.nb_programs"),
+    ("/format/nb_stream_groups", "format.nb_stream_groups"),
+    ("/format/probe_score", "format.probe_score"),
+    ("/streams/0/index", "streams[0].index"),
+    ("/streams/0/time_base", "streams[0].time_base"),
+    ("/streams/0/width", "streams[0].width"),
+    ("/streams/0/height", "streams[0].height"),
+    ("/streams/0/level", "streams[0].level"),
+    ("/streams/1/sample_rate", "streams[1].sample_rate"),
+    ("/streams/1/channels", "streams[1].channels"),
+    ("/streams/1/bits_per_sample", "streams[1].bits_per_sample"),
+    ("/streams/2/index", "streams[2].index"),
+    ("/streams/2/time_base", "streams[2].time_base"),
+];
+
+/// Required string fields, where `N/A` is a valid value.
+const REQUIRED_STRING: &[&str] = &[
+    "/format/filename",
+    "/format/format_name",
+    "/format/format_long_name",
+    "/streams/0/codec_tag",
+    "/streams/0/codec_tag_string",
+    "/streams/0/codec_name",
+    "/streams/0/codec_long_name",
+    "/streams/0/profile",
+    "/streams/0/pix_fmt",
+    "/streams/1/channel_layout",
+];
+
+fn sample_with(pointer: &str, value: Value) -> Vec<u8> {
+    let mut sample: Value = serde_json::from_str(SAMPLE_JSON).expect("parse sample");
+    *sample.pointer_mut(pointer).expect(pointer) = value;
+    serde_json::to_vec(&sample).expect("serialize sample")
+}
+
+#[test]
+fn strict_reports_path_of_na_in_required_fields() {
+    for (pointer, path) in REQUIRED_NUMERIC {
+        let err = FfprobeOutput::from_json_strict(&sample_with(pointer, json!("N/A")))
+            .expect_err(pointer);
+        assert_eq!(err.path, *path);
+        assert_eq!(err.raw, Some(json!("N/A")), "{path}");
+        assert!(err.to_string().starts_with(path), "{err}");
+    }
+    for pointer in REQUIRED_STRING {
+        FfprobeOutput::from_json_strict(&sample_with(pointer, json!("N/A"))).expect(pointer);
+    }
+}
+
+#[test]
+fn lenient_keeps_streams_with_na_in_required_fields() {
+    for (pointer, path) in REQUIRED_NUMERIC {
+        let parsed =
+            FfprobeOutput::from_json_lenient(&sample_with(pointer, json!("N/A"))).expect(pointer);
+        assert_eq!(parsed.output.streams.len(), 3, "{path}");
+        assert_eq!(parsed.diagnostics.len(), 1, "{path}");
+        assert_eq!(parsed.diagnostics[0].path, *path);
+        assert_eq!(parsed.diagnostics[0].raw, Some(json!("N/A")));
+    }
+
+    // A placeholder index is the stream's position.
+    let parsed =
+        FfprobeOutput::from_json_lenient(&sample_with("/streams/2/index", json!("N/A"))).unwrap();
+    assert_eq!(parsed.output.streams[2].common().index, 2);
+}
+
+#[test]
+fn missing_required_field() {
+    let mut sample: Value = serde_json::from_str(SAMPLE_JSON).unwrap();
+    sample["streams"][1]
+        .as_object_mut()
+        .unwrap()
+        .remove("channels");
+    let bytes = serde_json::to_vec(&sample).unwrap();
+
+    let err = FfprobeOutput::from_json_strict(&bytes).unwrap_err();
+    assert_eq!(err.path, "streams[1].channels");
+    assert_eq!(err.raw, None);
+    assert_eq!(err.reason, "missing required field");
+
+    let parsed = FfprobeOutput::from_json_lenient(&bytes).unwrap();
+    assert_eq!(parsed.diagnostics[0].path, "streams[1].channels");
+    assert!(matches!(&parsed.output.streams[1], Stream::Audio(a) if a.channels == 0));
+}
+
+#[test]
+fn malformed_optional_and_stream_values() {
+    let bytes = sample_with("/streams/0/bit_rate", json!("fast"));
+    let err = FfprobeOutput::from_json_strict(&bytes).unwrap_err();
+    assert_eq!(err.path, "streams[0].bit_rate");
+    assert_eq!(err.raw, Some(json!("fast")));
+
+    let parsed = FfprobeOutput::from_json_lenient(&bytes).unwrap();
+    assert_eq!(parsed.output.streams.len(), 3);
+    assert_eq!(parsed.output.streams[0].common().bit_rate, None);
+    assert_eq!(parsed.diagnostics[0].path, "streams[0].bit_rate");
+
+    let bytes = sample_with("/streams/1", json!("not a stream"));
+    let err = FfprobeOutput::from_json_strict(&bytes).unwrap_err();
+    assert_eq!(err.path, "streams[1]");
+    let parsed = FfprobeOutput::from_json_lenient(&bytes).unwrap();
+    let indexes: Vec<i64> = parsed
+        .output
+        .streams
+        .iter()
+        .map(|s| s.common().index)
+        .collect();
+    assert_eq!(indexes, [0, 2]);
+    assert_eq!(parsed.diagnostics.len(), 1);
+    assert!(parsed.diagnostics[0].reason.starts_with("skipped"));
+
+    assert!(FfprobeOutput::from_json_lenient(b"[]").is_err());
+    let clean = FfprobeOutput::from_json_lenient(SAMPLE_JSON.as_bytes()).unwrap();
+    assert!(clean.diagnostics.is_empty());
+}
+
+#[test]
+fn two_malformed_optional_fields_on_one_stream() {
+    let mut sample: Value = serde_json::from_str(SAMPLE_JSON).expect("parse sample");
+    sample["streams"][0]["bit_rate"] = json!("abc");
+    sample["streams"][0]["duration"] = json!("xyz");
+    let bytes = serde_json::to_vec(&sample).expect("serialize sample");
+
+    let err = FfprobeOutput::from_json_strict(&bytes).unwrap_err();
+    assert!(
+        err.path == "streams[0].bit_rate" || err.path == "streams[0].duration",
+        "{}",
+        err
+    );
+
+    let parsed = FfprobeOutput::from_json_lenient(&bytes).unwrap();
+    assert_eq!(parsed.output.streams.len(), 3);
+    let common = parsed.output.streams[0].common();
+    assert_eq!(common.bit_rate, None);
+    assert_eq!(common.duration, None);
+    let mut paths: Vec<&str> = parsed.diagnostics.iter().map(|d| d.path.as_str()).collect();
+    paths.sort();
+    assert_eq!(paths, ["streams[0].bit_rate", "streams[0].duration"]);
+}
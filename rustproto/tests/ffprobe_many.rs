@@ -0,0 +1,105 @@
+use rsproto::{ffprobe_many, FileSource, ReadSeek, Source};
+use std::collections::BTreeSet;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+/// A file source that takes `delay` to open, like a slow torrent piece.
+struct SlowSource {
+    inner: FileSource,
+    delay: Duration,
+}
+
+impl SlowSource {
+    fn new<P: Into<PathBuf>>(path: P, delay: Duration) -> Self {
+        Self {
+            inner: FileSource::new(path.into()),
+            delay,
+        }
+    }
+}
+
+impl Source for SlowSource {
+    fn open(&self) -> std::io::Result<Box<dyn ReadSeek>> {
+        thread::sleep(self.delay);
+        self.inner.open()
+    }
+
+    fn size(&self) -> std::io::Result<i64> {
+        self.inner.size()
+    }
+}
+
+#[test]
+fn probes_every_source_with_per_source_errors() {
+    let input = input_path();
+    let dir = tempfile::tempdir().expect("tempdir");
+    let bad = dir.path().join("bad.bin");
+    std::fs::write(&bad, vec![0u8; 2048]).expect("write");
+
+    let sources: Vec<SlowSource> = (0..12)
+        .map(|i| {
+            let path = if i % 4 == 3 {
+                bad.clone()
+            } else {
+                PathBuf::from(&input)
+            };
+            SlowSource::new(path, Duration::ZERO)
+        })
+        .collect();
+    let results: Vec<_> = ffprobe_many(sources, 3).collect();
+
+    assert_eq!(results.len(), 12);
+    let seen: BTreeSet<usize> = results.iter().map(|(i, _)| *i).collect();
+    assert_eq!(seen, (0..12).collect());
+    for (i, result) in &results {
+        if i % 4 == 3 {
+            let err = result.as_ref().expect_err("bad source");
+            assert!(!err.message.is_empty());
+        } else {
+            let parsed = result.as_ref().expect("good source");
+            assert!(!parsed.streams.is_empty());
+        }
+    }
+}
+
+#[test]
+fn results_arrive_in_completion_order() {
+    let input = input_path();
+    let sources = vec![
+        SlowSource::new(&input, Duration::from_millis(1500)),
+        SlowSource::new(&input, Duration::ZERO),
+        SlowSource::new(&input, Duration::ZERO),
+    ];
+    let order: Vec<usize> = ffprobe_many(sources, 2).map(|(i, _)| i).collect();
+    assert_eq!(order.len(), 3);
+    assert_eq!(order.last(), Some(&0), "{order:?}");
+}
+
+#[test]
+fn dropping_the_batch_stops_pulling_sources() {
+    let input = input_path();
+    let pulled = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = std::sync::Arc::clone(&pulled);
+    let sources = (0..1000).map(move |_| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        SlowSource::new(&input, Duration::ZERO)
+    });
+    let mut batch = ffprobe_many(sources, 2);
+    let (_, first) = batch.next().expect("one result");
+    assert!(first.is_ok());
+    drop(batch);
+    assert!(pulled.load(std::sync::atomic::Ordering::SeqCst) < 10);
+}
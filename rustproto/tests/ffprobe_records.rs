@@ -0,0 +1,120 @@
+use rsproto::{ffprobe_records, scan_packets, FileSource, ProbeRecord, RecordOptions};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+#[test]
+fn packets_match_packet_scan() {
+    let input_path = input_path();
+    let options = RecordOptions {
+        buffer: 4,
+        ..RecordOptions::default()
+    };
+    let mut counts: BTreeMap<i64, u64> = BTreeMap::new();
+    let mut first_video_key = None;
+    for record in ffprobe_records(FileSource::new(&input_path), &options).expect("start") {
+        let ProbeRecord::Packet(packet) = record.expect("packet record") else {
+            panic!("expected only packets");
+        };
+        assert!(packet.size > 0);
+        if packet.codec_type == "video" && first_video_key.is_none() {
+            first_video_key = Some(packet.is_keyframe());
+        }
+        *counts.entry(packet.stream_index).or_default() += 1;
+    }
+    assert_eq!(first_video_key, Some(true));
+
+    let scan = block_on(scan_packets(FileSource::new(&input_path))).expect("packet scan");
+    let expected: BTreeMap<i64, u64> = scan.streams.iter().map(|(i, s)| (*i, s.packets)).collect();
+    assert_eq!(counts, expected);
+}
+
+#[test]
+fn frames_of_selected_stream() {
+    let options = RecordOptions {
+        packets: false,
+        frames: true,
+        select_streams: Some("v:0".to_string()),
+        read_intervals: Some("%+1".to_string()),
+        ..RecordOptions::default()
+    };
+    let frames: Vec<_> = ffprobe_records(FileSource::new(input_path()), &options)
+        .expect("start")
+        .map(|r| match r.expect("frame record") {
+            ProbeRecord::Frame(frame) => frame,
+            other => panic!("expected only frames, got {:?}", other),
+        })
+        .collect();
+    assert!(!frames.is_empty());
+    assert!(frames
+        .iter()
+        .all(|f| f.media_type == "video" && f.width.is_some()));
+    assert_eq!(frames[0].key_frame, 1);
+    assert_eq!(frames[0].pict_type.as_deref(), Some("I"));
+}
+
+#[test]
+fn dropping_the_stream_stops_the_run() {
+    let options = RecordOptions {
+        packets: true,
+        frames: true,
+        buffer: 1,
+        ..RecordOptions::default()
+    };
+    let started = Instant::now();
+    let mut stream = ffprobe_records(FileSource::new(input_path()), &options).expect("start");
+    let first: Vec<_> = stream.by_ref().take(5).collect();
+    assert_eq!(first.len(), 5);
+    assert!(first.iter().all(|r| r.is_ok()));
+    drop(stream);
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn failed_run_ends_with_error() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("not_media.bin");
+    std::fs::write(&path, vec![0x5au8; 4096]).expect("write");
+    let records: Vec<_> = ffprobe_records(FileSource::new(&path), &RecordOptions::default())
+        .expect("start")
+        .collect();
+    let last = records.last().expect("an error item");
+    assert!(last.is_err(), "{:?}", last);
+}
@@ -0,0 +1,170 @@
+use rsproto::{
+    media_fingerprint, run_ffmpeg, FileSource, FingerprintOptions, MediaFingerprint, RunOutput,
+};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+fn fingerprint(path: &Path) -> MediaFingerprint {
+    media_fingerprint(
+        FileSource::new(path),
+        &FingerprintOptions {
+            frames_per_second: 2.0,
+            // Keeps fingerprinting a long input bounded.
+            duration: Some(30.0),
+            ..Default::default()
+        },
+    )
+    .expect("media_fingerprint")
+}
+
+#[test]
+fn reencoded_and_trimmed_release_matches() {
+    // 30 s of evolving picture and a tune whose note changes every half
+    // second. The input is only opened to satisfy `{input}`; the clip is
+    // made from the lavfi sources alone.
+    let original = ffmpeg(
+        &input_path(),
+        &[
+            "-i",
+            "{input}",
+            "-f",
+            "lavfi",
+            "-i",
+            "cellauto=s=27x24:r=25:rule=30:random_seed=7,scale=320x240:flags=neighbor,format=yuv420p",
+            "-f",
+            "lavfi",
+            "-i",
+            "aevalsrc=0.4*sin(2*PI*220*pow(2\\,mod(floor(1000*abs(sin(floor(2*t)*12.9898)))\\,24)/12)*t):s=44100:d=30",
+            "-map",
+            "1:v",
+            "-map",
+            "2:a",
+            "-t",
+            "30",
+            "-c:v",
+            "mpeg4",
+            "-q:v",
+            "2",
+            "-c:a",
+            "pcm_s16le",
+            "{outdir}/original.mkv",
+        ],
+    );
+    let original_path = original.path().join("original.mkv");
+
+    // Another release: smaller, lower quality, lossy audio, and missing the
+    // first 4 seconds.
+    let release = ffmpeg(
+        original_path.to_str().unwrap(),
+        &[
+            "-ss",
+            "4",
+            "-i",
+            "{input}",
+            "-vf",
+            "scale=240:180:flags=neighbor",
+            "-c:v",
+            "mpeg4",
+            "-q:v",
+            "8",
+            "-c:a",
+            "aac",
+            "-b:a",
+            "96k",
+            "-ar",
+            "48000",
+            "{outdir}/release.mp4",
+        ],
+    );
+    let release_path = release.path().join("release.mp4");
+
+    let a = fingerprint(&original_path);
+    let b = fingerprint(&release_path);
+    assert!((a.video.len() as i64 - 60).abs() <= 1, "{}", a.video.len());
+    assert!(!a.audio.is_empty());
+    assert_eq!(a.video_interval, 0.5);
+
+    let m = a.compare(&b, 10.0).expect("compare");
+    assert!((m.offset - 4.0).abs() <= 0.5, "{:?}", m);
+    assert!(m.similarity > 0.6, "{:?}", m);
+    assert!(m.video_similarity.unwrap() > 0.6, "{:?}", m);
+    assert!(m.audio_similarity.unwrap() > 0.4, "{:?}", m);
+
+    // Comparing the other way round flips the offset.
+    let back = b.compare(&a, 10.0).expect("compare");
+    assert!((back.offset + 4.0).abs() <= 0.5, "{:?}", back);
+
+    // Fingerprints round-trip through JSON for storage.
+    let json = serde_json::to_string(&a).unwrap();
+    let parsed: MediaFingerprint = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, a);
+    let same = a.compare(&parsed, 10.0).unwrap();
+    assert_eq!(same.similarity, 1.0);
+    assert_eq!(same.offset, 0.0);
+
+    // Unrelated content.
+    let other = fingerprint(Path::new(&input_path()));
+    let m = a.compare(&other, 10.0).expect("compare");
+    assert!(m.similarity < 0.3, "{:?}", m);
+}
+
+#[test]
+fn stream_selection() {
+    let input_path = input_path();
+    let video_only = media_fingerprint(
+        FileSource::new(&input_path),
+        &FingerprintOptions {
+            audio_stream: None,
+            duration: Some(5.0),
+            ..Default::default()
+        },
+    )
+    .expect("media_fingerprint");
+    assert!(video_only.audio.is_empty());
+    assert!((4..=6).contains(&video_only.video.len()));
+
+    let audio_only = media_fingerprint(
+        FileSource::new(&input_path),
+        &FingerprintOptions {
+            video_stream: None,
+            duration: Some(10.0),
+            ..Default::default()
+        },
+    )
+    .expect("media_fingerprint");
+    assert!(audio_only.video.is_empty());
+    assert!(!audio_only.audio.is_empty());
+    // No hash kind in common.
+    assert_eq!(video_only.compare(&audio_only, 5.0), None);
+
+    assert!(media_fingerprint(
+        FileSource::new(&input_path),
+        &FingerprintOptions {
+            video_stream: None,
+            audio_stream: None,
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
@@ -0,0 +1,184 @@
+use rsproto::{ffprobe, run_ffmpeg, CancelMode, Cancellation, FileSource, ReadSeek, Source};
+use std::env;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+fn args(extra: &[&str]) -> Vec<String> {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(extra);
+    full.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn graceful_stop_finalizes_output() {
+    let input_path = input_path();
+    let handle = run_ffmpeg(
+        FileSource::new(&input_path),
+        &args(&[
+            "-re",
+            "-i",
+            "{input}",
+            "-c:v",
+            "copy",
+            "-c:a",
+            "aac",
+            "{outdir}/out.mp4",
+        ]),
+    )
+    .expect("run_ffmpeg start");
+    thread::sleep(Duration::from_secs(2));
+    handle.cancel_with("viewer left", CancelMode::Graceful);
+    let out = handle.wait().expect("graceful stop");
+
+    assert_eq!(
+        out.cancelled,
+        Some(Cancellation {
+            mode: CancelMode::Graceful,
+            reason: "viewer left".to_string(),
+        })
+    );
+    // MP4 is only readable once the trailer (`moov`) has been written.
+    let probe =
+        block_on(ffprobe(FileSource::new(out.path().join("out.mp4")))).expect("ffprobe output");
+    let input = block_on(ffprobe(FileSource::new(&input_path))).expect("ffprobe input");
+    let duration = probe.format.duration.expect("output duration");
+    assert!(duration > 0.5, "{}", duration);
+    assert!(
+        duration < input.format.duration.unwrap() - 5.0,
+        "{}",
+        duration
+    );
+}
+
+/// Serves the input until half of it has been read, then blocks until the
+/// source is cancelled.
+struct StallingSource {
+    inner: FileSource,
+    limit: u64,
+    cancelled: Arc<(Mutex<bool>, Condvar)>,
+}
+
+struct StallingReader {
+    inner: Box<dyn ReadSeek>,
+    pos: u64,
+    limit: u64,
+    cancelled: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Source for StallingSource {
+    fn open(&self) -> std::io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(StallingReader {
+            inner: self.inner.open()?,
+            pos: 0,
+            limit: self.limit,
+            cancelled: Arc::clone(&self.cancelled),
+        }))
+    }
+
+    fn size(&self) -> std::io::Result<i64> {
+        self.inner.size()
+    }
+
+    fn cancel(&self) {
+        let (flag, cond) = &*self.cancelled;
+        *flag.lock().unwrap() = true;
+        cond.notify_all();
+    }
+}
+
+impl Read for StallingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.limit {
+            let (flag, cond) = &*self.cancelled;
+            let guard = cond
+                .wait_while(flag.lock().unwrap(), |cancelled| !*cancelled)
+                .unwrap();
+            drop(guard);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "source cancelled",
+            ));
+        }
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for StallingReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+#[test]
+fn graceful_stop_on_stalled_source_aborts() {
+    let inner = FileSource::new(input_path());
+    let limit = inner.size().expect("input size") as u64 / 2;
+    let source = StallingSource {
+        inner,
+        limit,
+        cancelled: Arc::new((Mutex::new(false), Condvar::new())),
+    };
+    let handle = run_ffmpeg(
+        source,
+        &args(&["-i", "{input}", "-c", "copy", "{outdir}/out.mkv"]),
+    )
+    .expect("run_ffmpeg start");
+    thread::sleep(Duration::from_millis(500));
+
+    let start = Instant::now();
+    let timeout = Duration::from_millis(500);
+    handle.cancel_graceful("viewer left", timeout);
+    // Later graceful stops keep the first deadline.
+    handle.cancel_with("viewer left", CancelMode::Graceful);
+    let err = handle.wait().expect_err("stalled run is aborted");
+    let elapsed = start.elapsed();
+    assert!(elapsed >= timeout, "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert!(
+        err.contains("viewer left (graceful stop timed out)"),
+        "{}",
+        err
+    );
+}
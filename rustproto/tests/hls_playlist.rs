@@ -0,0 +1,138 @@
+use rsproto::hls::{self, ByteRange, MasterPlaylist, MediaPlaylist, Playlist, PlaylistType};
+
+const FFMPEG_EVENT: &str = "#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:0
+#EXT-X-PLAYLIST-TYPE:EVENT
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:4.000000,
+seg_00000.m4s
+#EXTINF:4.000000,
+seg_00001.m4s
+#EXT-X-DISCONTINUITY
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:08.000+0000
+#EXTINF:2.500000,
+#EXT-X-BYTERANGE:1000@200
+seg_00002.m4s
+#EXT-X-ENDLIST
+";
+
+const LL_HLS: &str = "#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-VERSION:9
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.5
+#EXT-X-PART-INF:PART-TARGET=0.5
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXT-X-PART:DURATION=0.5,URI=\"seg10.0.m4s\",INDEPENDENT=YES
+#EXT-X-PART:DURATION=0.5,URI=\"seg10.1.m4s\"
+#EXTINF:1.000000,
+seg10.m4s
+#EXT-X-PART:DURATION=0.5,URI=\"seg11.0.m4s\",INDEPENDENT=YES
+#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"seg11.1.m4s\"
+";
+
+const MASTER: &str = "#EXTM3U
+#EXT-X-VERSION:6
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aud\",NAME=\"English\",LANGUAGE=\"en\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1920x1080,CODECS=\"hvc1.1.6.L120.90,mp4a.40.2\",AUDIO=\"aud\",HDCP-LEVEL=NONE
+video.m3u8
+";
+
+#[test]
+fn parse_ffmpeg_event_playlist() {
+    let playlist = MediaPlaylist::parse(FFMPEG_EVENT).expect("parse");
+    assert_eq!(playlist.version, Some(7));
+    assert_eq!(playlist.target_duration, 4);
+    assert_eq!(playlist.playlist_type, Some(PlaylistType::Event));
+    assert!(playlist.independent_segments);
+    assert!(playlist.end_list);
+    assert_eq!(playlist.segments.len(), 3);
+    assert_eq!(playlist.segments[0].uri, "seg_00000.m4s");
+    assert_eq!(
+        playlist.map_for(2).map(|m| m.uri.as_str()),
+        Some("init.mp4")
+    );
+    assert!(playlist.segments[2].discontinuity);
+    assert_eq!(
+        playlist.segments[2].byte_range,
+        Some(ByteRange {
+            length: 1000,
+            offset: Some(200)
+        })
+    );
+    assert!((playlist.total_duration() - 10.5).abs() < 1e-9);
+}
+
+#[test]
+fn parse_low_latency_playlist() {
+    let playlist = MediaPlaylist::parse(LL_HLS).expect("parse");
+    let control = playlist.server_control.as_ref().expect("server control");
+    assert!(control.can_block_reload);
+    assert_eq!(control.part_hold_back, Some(1.5));
+    assert_eq!(playlist.part_target, Some(0.5));
+    assert_eq!(playlist.segments.len(), 1);
+    assert_eq!(playlist.segments[0].parts.len(), 2);
+    assert!(playlist.segments[0].parts[0].independent);
+    assert_eq!(playlist.trailing_parts.len(), 1);
+    assert_eq!(playlist.preload_hints[0].uri, "seg11.1.m4s");
+}
+
+#[test]
+fn parse_master_playlist() {
+    let playlist = MasterPlaylist::parse(MASTER).expect("parse");
+    assert_eq!(playlist.renditions[0].language.as_deref(), Some("en"));
+    assert!(playlist.renditions[0].default);
+    let variant = &playlist.variants[0];
+    assert_eq!(variant.uri, "video.m3u8");
+    assert_eq!(variant.resolution, Some((1920, 1080)));
+    assert_eq!(
+        variant.codecs.as_deref(),
+        Some("hvc1.1.6.L120.90,mp4a.40.2")
+    );
+    assert_eq!(
+        variant.other_attributes,
+        vec![("HDCP-LEVEL".to_string(), "NONE".to_string())]
+    );
+}
+
+#[test]
+fn serialize_round_trip() {
+    for text in [FFMPEG_EVENT, LL_HLS, MASTER] {
+        let parsed = hls::parse(text).expect("parse");
+        let reparsed = hls::parse(&parsed.to_string()).expect("reparse");
+        assert_eq!(parsed, reparsed);
+    }
+}
+
+#[test]
+fn rewrite_uris_for_serving() {
+    let mut playlist = hls::parse(LL_HLS).expect("parse");
+    playlist.rewrite_uris(|uri| format!("https://cdn.example/run/{uri}"));
+    let Playlist::Media(media) = &playlist else {
+        panic!("expected media playlist");
+    };
+    assert_eq!(
+        media.map_for(0).unwrap().uri,
+        "https://cdn.example/run/init.mp4"
+    );
+    assert_eq!(
+        media.segments[0].parts[1].uri,
+        "https://cdn.example/run/seg10.1.m4s"
+    );
+    assert_eq!(
+        media.preload_hints[0].uri,
+        "https://cdn.example/run/seg11.1.m4s"
+    );
+    assert!(playlist
+        .to_string()
+        .contains("URI=\"https://cdn.example/run/seg11.0.m4s\""));
+}
+
+#[test]
+fn rejects_missing_header() {
+    let err = hls::parse("#EXTINF:4,\nseg.ts\n").unwrap_err();
+    assert_eq!(err.line, 1);
+}
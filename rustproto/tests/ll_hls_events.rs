@@ -0,0 +1,124 @@
+use rsproto::{hls, run_ll_hls, ArtifactRole, FileSource, LlHlsOptions, RunEvent};
+use std::env;
+use std::path::Path;
+use std::time::Duration;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+#[test]
+fn ll_hls_events() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    // Re-encode with a keyframe every second, so every segment boundary
+    // has an independent part to start on.
+    let options = LlHlsOptions {
+        part_target: 0.5,
+        segment_target: 2.0,
+        codec_args: [
+            "-c:v",
+            "mpeg4",
+            "-force_key_frames",
+            "expr:gte(t,n_forced*1)",
+            "-c:a",
+            "aac",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+    };
+    let mut handle = run_ll_hls(FileSource::new(&input_path), &options).expect("run_ll_hls start");
+    let events = handle.events().expect("events receiver");
+    let live = handle.ll_hls().expect("ll-hls playlist").clone();
+
+    // Blocks until the first part shows up.
+    let first = live
+        .blocking_reload(0, Some(0), Duration::from_secs(20))
+        .expect("blocking reload timed out");
+    assert!(!first.trailing_parts.is_empty() || !first.segments.is_empty());
+
+    let output = handle.wait().expect("ll-hls run failed");
+    let events: Vec<RunEvent> = events.try_iter().collect();
+    assert_eq!(events.last(), Some(&RunEvent::Finished));
+
+    let mut parts = 0;
+    let mut segments = Vec::new();
+    for event in &events {
+        match event {
+            RunEvent::PartComplete { msn, part, .. } => {
+                assert_eq!(*msn, segments.len() as u64);
+                assert_eq!(*part, parts);
+                parts += 1;
+            }
+            RunEvent::SegmentComplete { msn, uri, duration } => {
+                assert_eq!(*msn, segments.len() as u64);
+                assert!(*duration > 0.0);
+                segments.push(uri.clone());
+                parts = 0;
+            }
+            RunEvent::Error(err) => panic!("packaging failed: {}", err),
+            RunEvent::Finished => {}
+        }
+    }
+    assert!(!segments.is_empty(), "no segments completed");
+
+    let text = std::fs::read_to_string(output.path().join("index.m3u8")).expect("index.m3u8");
+    let playlist = hls::MediaPlaylist::parse(&text).expect("parse index.m3u8");
+    assert!(playlist.end_list);
+    assert_eq!(playlist.part_target, Some(0.5));
+    // Fixed from the options, and every segment stays within it.
+    assert_eq!(playlist.target_duration, 2);
+    assert!(playlist.segments.iter().all(|s| s.duration.round() <= 2.0));
+    assert!(playlist.preload_hints.is_empty());
+    let uris: Vec<String> = playlist.segments.iter().map(|s| s.uri.clone()).collect();
+    assert_eq!(uris, segments);
+    for segment in &playlist.segments {
+        assert!(
+            segment.parts[0].independent,
+            "{} starts mid-GOP",
+            segment.uri
+        );
+        let size: u64 = segment
+            .parts
+            .iter()
+            .map(|p| output.file(&p.uri).expect("part file").size)
+            .sum();
+        assert_eq!(output.file(&segment.uri).expect("segment file").size, size);
+    }
+    assert!(output.file("parts.m3u8").is_none());
+    assert_eq!(
+        output.file("init.mp4").map(|f| f.role),
+        Some(ArtifactRole::InitSegment)
+    );
+    assert!(output
+        .files_with_role(ArtifactRole::PartialSegment)
+        .next()
+        .is_some());
+    assert_eq!(live.playlist(), playlist);
+}
+
+#[test]
+fn invalid_targets() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    for (part_target, segment_target) in [
+        (0.0, 2.0),
+        (f64::NAN, 2.0),
+        (0.5, f64::NAN),
+        (0.5, f64::INFINITY),
+        (f64::INFINITY, f64::INFINITY),
+        (2.0, 1.0),
+    ] {
+        let options = LlHlsOptions {
+            part_target,
+            segment_target,
+            ..LlHlsOptions::default()
+        };
+        let err = run_ll_hls(FileSource::new(&input_path), &options)
+            .err()
+            .unwrap_or_else(|| panic!("accepted {} / {}", part_target, segment_target));
+        assert!(err.contains("invalid LL-HLS targets"), "{}", err);
+    }
+}
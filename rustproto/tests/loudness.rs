@@ -0,0 +1,109 @@
+use rsproto::{analyze_loudness, run_ffmpeg, FileSource, RunOutput};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+#[test]
+fn sine_loudness_and_replaygain() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    // A 1 kHz stereo sine at 1/8 of full scale: -18.06 dBFS peak, and
+    // -18.06 LUFS since K-weighting is ~0 dB at 1 kHz.
+    let wav = ffmpeg(
+        &input_path,
+        &[
+            "-i",
+            "{input}",
+            "-f",
+            "lavfi",
+            "-i",
+            "sine=frequency=1000:sample_rate=48000:duration=10",
+            "-map",
+            "1:a",
+            "-af",
+            "pan=stereo|c0=c0|c1=c0",
+            "-c:a",
+            "pcm_s16le",
+            "{outdir}/sine.wav",
+        ],
+    );
+    let wav_path = wav.path().join("sine.wav");
+
+    let loudness = analyze_loudness(FileSource::new(&wav_path), 0).expect("analyze_loudness");
+    let expected = 20.0 * (0.125f64).log10();
+    assert!(
+        (loudness.integrated - expected).abs() < 0.5,
+        "integrated {}",
+        loudness.integrated
+    );
+    assert!(
+        loudness.loudness_range < 1.0,
+        "lra {}",
+        loudness.loudness_range
+    );
+    assert!(
+        (loudness.sample_peak - expected).abs() < 0.2,
+        "sample peak {}",
+        loudness.sample_peak
+    );
+    assert!(
+        (loudness.true_peak - expected).abs() < 0.5,
+        "true peak {}",
+        loudness.true_peak
+    );
+    assert!(loudness.true_peak >= loudness.sample_peak - 0.01);
+
+    assert!(
+        (loudness.replaygain.track_peak - 0.125).abs() < 0.01,
+        "track peak {}",
+        loudness.replaygain.track_peak
+    );
+    assert!(
+        (-24.0..=64.0).contains(&loudness.replaygain.track_gain),
+        "track gain {}",
+        loudness.replaygain.track_gain
+    );
+
+    // One window per 100 ms.
+    assert!(
+        (95..=105).contains(&loudness.windows.len()),
+        "{} windows",
+        loudness.windows.len()
+    );
+    assert!(loudness.windows.windows(2).all(|w| w[0].time < w[1].time));
+    let last = loudness.windows.last().unwrap();
+    assert_eq!(last.integrated, loudness.integrated);
+    let middle = &loudness.windows[loudness.windows.len() / 2];
+    assert!((middle.momentary - expected).abs() < 0.5, "{:?}", middle);
+    assert!((middle.short_term - expected).abs() < 0.5, "{:?}", middle);
+}
+
+#[test]
+fn input_audio_stream_and_missing_stream() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    let loudness = analyze_loudness(FileSource::new(&input_path), 0).expect("analyze_loudness");
+    assert!(loudness.integrated.is_finite());
+    assert!(loudness.integrated < 0.0);
+    assert!(!loudness.windows.is_empty());
+
+    assert!(analyze_loudness(FileSource::new(&input_path), 7).is_err());
+}
@@ -0,0 +1,168 @@
+use rsproto::{ffprobe_cached, FfprobeOutput, FileSource, ProbeCache, ReadSeek, Source};
+use std::env;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn sample(filename: &str) -> FfprobeOutput {
+    let json = format!(
+        r#"{{"format": {{"filename": "{}", "nb_streams": 0, "nb_programs": 0,
+            "nb_stream_groups": 0, "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+            "format_long_name": "QuickTime / MOV", "probe_score": 100}}}}"#,
+        filename
+    );
+    serde_json::from_str(&json).expect("parse sample")
+}
+
+#[test]
+fn lru_eviction() {
+    let cache = ProbeCache::new(2);
+    cache.insert("a", sample("a"));
+    cache.insert("b", sample("b"));
+    assert!(cache.get("a").is_some());
+    cache.insert("c", sample("c"));
+
+    assert_eq!(cache.len(), 2);
+    assert!(
+        cache.get("b").is_none(),
+        "least recently used entry is evicted"
+    );
+    assert_eq!(cache.get("a").unwrap().format.filename, "a");
+    assert_eq!(cache.get("c").unwrap().format.filename, "c");
+}
+
+#[test]
+fn persistence_round_trip() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("probe-cache.json");
+    {
+        let cache = ProbeCache::with_persistence(2, &path).expect("open cache");
+        assert!(cache.is_empty());
+        cache.insert("a", sample("a"));
+        cache.insert("b", sample("b"));
+        let _ = cache.get("a");
+        cache.save().expect("save");
+    }
+
+    let cache = ProbeCache::with_persistence(2, &path).expect("reopen cache");
+    assert_eq!(cache.len(), 2);
+    // Recency survives the reload: "b" is still the eviction candidate.
+    cache.insert("c", sample("c"));
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("a").is_some());
+}
+
+#[test]
+fn concurrent_saves() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("probe-cache.json");
+    let cache = Arc::new(ProbeCache::with_persistence(64, &path).expect("open cache"));
+
+    // Inserts alone don't touch the file.
+    cache.insert("first", sample("first"));
+    assert!(!path.exists());
+
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let cache = Arc::clone(&cache);
+            std::thread::spawn(move || {
+                for i in 0..4 {
+                    let key = format!("{}-{}", t, i);
+                    cache.insert(key.clone(), sample(&key));
+                    cache.save().expect("save");
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    cache.save().expect("save");
+
+    let reloaded = ProbeCache::with_persistence(64, &path).expect("reopen cache");
+    assert_eq!(reloaded.len(), 33);
+    assert_eq!(reloaded.get("7-3").unwrap().format.filename, "7-3");
+    let leftovers = std::fs::read_dir(dir.path()).unwrap().count();
+    assert_eq!(leftovers, 1, "temporary files left behind");
+
+    // Dropping the cache writes pending changes.
+    cache.remove("first");
+    drop(cache);
+    let reloaded = ProbeCache::with_persistence(64, &path).expect("reopen cache");
+    assert_eq!(reloaded.len(), 32);
+}
+
+struct CountingSource {
+    inner: FileSource,
+    opens: Arc<AtomicUsize>,
+}
+
+impl Source for CountingSource {
+    fn open(&self) -> std::io::Result<Box<dyn ReadSeek>> {
+        self.opens.fetch_add(1, Ordering::SeqCst);
+        self.inner.open()
+    }
+
+    fn size(&self) -> std::io::Result<i64> {
+        self.inner.size()
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+        self.inner.fingerprint()
+    }
+}
+
+#[test]
+fn ffprobe_cached_skips_second_probe() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+
+    let cache = ProbeCache::new(8);
+    let opens = Arc::new(AtomicUsize::new(0));
+    let source = || CountingSource {
+        inner: FileSource::new(&input_path),
+        opens: Arc::clone(&opens),
+    };
+
+    let first = block_on(ffprobe_cached(source(), &cache)).expect("first probe");
+    let after_first = opens.load(Ordering::SeqCst);
+    assert!(after_first > 0);
+    let second = block_on(ffprobe_cached(source(), &cache)).expect("cached probe");
+    assert_eq!(
+        opens.load(Ordering::SeqCst),
+        after_first,
+        "cache hit reopened source"
+    );
+    assert_eq!(first.format.nb_streams, second.format.nb_streams);
+    assert_eq!(cache.len(), 1);
+}
@@ -0,0 +1,142 @@
+use rsproto::{
+    ffprobe_with_options, run_ffmpeg, FileSource, ProbeOptions, ReadSeek, Source, Stream,
+};
+use std::env;
+use std::io::Cursor;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+/// Serves only the first `len` bytes of a file, like a partially downloaded torrent.
+struct PrefixSource {
+    data: Vec<u8>,
+}
+
+impl Source for PrefixSource {
+    fn open(&self) -> std::io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(Cursor::new(self.data.clone())))
+    }
+
+    fn size(&self) -> std::io::Result<i64> {
+        Ok(self.data.len() as i64)
+    }
+}
+
+#[test]
+fn fast_probe_finds_streams() {
+    let parsed = block_on(ffprobe_with_options(
+        FileSource::new(input_path()),
+        &ProbeOptions::fast(),
+    ))
+    .expect("fast probe failed");
+    assert!(parsed
+        .streams
+        .iter()
+        .any(|s| matches!(s, Stream::Video(_) | Stream::Audio(_))));
+    assert_eq!(parsed.format.nb_streams as usize, parsed.streams.len());
+}
+
+#[test]
+fn probe_options_reach_ffprobe() {
+    let options = ProbeOptions {
+        probesize: Some(32),
+        analyzeduration: Some(0),
+        fpsprobesize: Some(0),
+        skip_estimate_duration_from_pts: true,
+        best_effort: false,
+    };
+    let err = block_on(ffprobe_with_options(
+        PrefixSource { data: Vec::new() },
+        &options,
+    ))
+    .expect_err("empty input should not probe");
+    let input_at = err.args.iter().position(|a| a == "-i").expect("-i");
+    for flag in [
+        "-probesize",
+        "-analyzeduration",
+        "-fpsprobesize",
+        "-skip_estimate_duration_from_pts",
+    ] {
+        let at = err.args.iter().position(|a| a == flag).expect(flag);
+        assert!(at < input_at, "{} must precede -i", flag);
+    }
+}
+
+#[test]
+fn best_effort_on_partial_download() {
+    // Matroska writes its track headers first, so a prefix still opens.
+    let args: Vec<String> = [
+        "ffmpeg",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-i",
+        "{input}",
+        "-t",
+        "10",
+        "-c:v",
+        "mpeg4",
+        "-c:a",
+        "aac",
+        "{outdir}/clip.mkv",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let out = run_ffmpeg(FileSource::new(input_path()), &args)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed");
+    let data = std::fs::read(out.path().join("clip.mkv")).expect("read clip");
+    let prefix = data[..data.len() / 2].to_vec();
+    let options = ProbeOptions {
+        best_effort: true,
+        ..ProbeOptions::default()
+    };
+    let parsed = block_on(ffprobe_with_options(
+        PrefixSource { data: prefix },
+        &options,
+    ))
+    .expect("probe of a partial download");
+    assert!(parsed.streams.iter().any(|s| matches!(s, Stream::Video(_))));
+    assert!(parsed.streams.iter().any(|s| matches!(s, Stream::Audio(_))));
+    assert_eq!(parsed.format.nb_streams as usize, parsed.streams.len());
+    // The segment header carries the full duration, not the downloaded part.
+    let duration = parsed.format.duration.expect("duration from the header");
+    assert!((duration - 10.0).abs() < 0.5, "{}", duration);
+}
@@ -0,0 +1,120 @@
+use rsproto::{rescale, FfprobeOutput, Rational, Stream};
+use std::time::Duration;
+
+#[test]
+fn arithmetic_and_ordering() {
+    assert_eq!(Rational::new(6, -8).reduce(), Rational::new(-3, 4));
+    let r = Rational::new(-6, 8).reduce();
+    assert_eq!((r.num, r.den), (-3, 4));
+    assert_eq!(Rational::new(1, 2), Rational::new(2, 4));
+    assert_eq!(
+        Rational::new(24000, 1001).checked_mul(Rational::new(1001, 1000)),
+        Some(Rational::from(24))
+    );
+    assert_eq!(
+        Rational::new(1, 2).checked_div(Rational::new(1, 4)),
+        Some(Rational::from(2))
+    );
+    assert_eq!(Rational::new(1, 2).checked_div(Rational::new(0, 1)), None);
+    assert_eq!(
+        Rational::new(i64::MAX, 1).checked_mul(Rational::new(3, 1)),
+        None
+    );
+    assert!(Rational::new(30000, 1001) < Rational::new(30, 1));
+    assert!(Rational::new(1, 3) > Rational::new(-1, 2));
+    assert_eq!(Rational::new(0, 0), Rational::new(0, 0));
+    assert_eq!(Rational::new(0, 0).partial_cmp(&Rational::new(1, 1)), None);
+    assert!((Rational::new(24000, 1001).to_f64() - 23.976).abs() < 1e-3);
+    assert!(Rational::new(0, 0).to_f64().is_nan());
+}
+
+#[test]
+fn extreme_values_keep_their_sign() {
+    // Lowest terms do not fit in i64, so the value is kept as is.
+    let r = Rational::new(i64::MIN, -1).reduce();
+    assert_eq!((r.num, r.den), (i64::MIN, -1));
+    assert!(Rational::new(i64::MIN, -1) > Rational::new(i64::MAX, 1));
+    assert_eq!(
+        Rational::new(i64::MIN, -1).to_duration(),
+        Some(Duration::from_secs(1 << 63))
+    );
+
+    let r = Rational::new(1, i64::MIN).reduce();
+    assert_eq!((r.num, r.den), (1, i64::MIN));
+    assert!(Rational::new(1, i64::MIN) < Rational::new(0, 1));
+    assert!(Rational::new(1, i64::MIN) > Rational::new(-1, i64::MAX));
+    assert_eq!(Rational::new(1, i64::MIN).to_duration(), None);
+    assert_eq!(
+        Rational::new(-1, i64::MIN).to_duration(),
+        Some(Duration::ZERO)
+    );
+    let r = Rational::new(2, i64::MIN).reduce();
+    assert_eq!((r.num, r.den), (-1, 1 << 62));
+}
+
+#[test]
+fn durations_and_rescaling() {
+    assert_eq!(
+        Rational::new(3, 2).to_duration(),
+        Some(Duration::from_millis(1500))
+    );
+    assert_eq!(Rational::new(1, 0).to_duration(), None);
+    assert_eq!(Rational::new(-1, 2).to_duration(), None);
+    assert_eq!(
+        Rational::new(1, 90000).timestamp_to_duration(180_000),
+        Some(Duration::from_secs(2))
+    );
+
+    let ms = Rational::new(1, 1000);
+    let mpeg = Rational::new(1, 90000);
+    assert_eq!(rescale(2000, ms, mpeg), Some(180_000));
+    assert_eq!(rescale(180_045, mpeg, ms), Some(2001));
+    assert_eq!(rescale(180_044, mpeg, ms), Some(2000));
+    assert_eq!(rescale(-180_045, mpeg, ms), Some(-2001));
+    assert_eq!(rescale(1, ms, Rational::new(0, 1)), None);
+    assert_eq!(rescale(i64::MAX, Rational::new(2, 1), ms), None);
+}
+
+#[test]
+fn display_and_parse() {
+    assert_eq!(Rational::new(30000, 1001).to_string(), "30000/1001");
+    assert_eq!("30000/1001".parse(), Ok(Rational::new(30000, 1001)));
+    let aspect: Rational = "16:9".parse().unwrap();
+    assert_eq!((aspect.num, aspect.den), (16, 9));
+    assert_eq!("25".parse(), Ok(Rational::from(25)));
+    assert!("16x9".parse::<Rational>().is_err());
+    assert!("".parse::<Rational>().is_err());
+}
+
+#[test]
+fn probe_fields() {
+    let json = r#"{
+        "format": {"filename": "x.mp4", "nb_streams": 2, "nb_programs": 0,
+            "nb_stream_groups": 0, "format_name": "mov", "format_long_name": "QuickTime / MOV",
+            "probe_score": 100},
+        "streams": [
+            {"index": 0, "codec_name": "h264", "codec_long_name": "H.264", "profile": "High",
+             "codec_type": "video", "codec_tag_string": "avc1", "codec_tag": "0x31637661",
+             "width": 720, "height": 576, "pix_fmt": "yuv420p", "level": 30,
+             "sample_aspect_ratio": "64:45", "display_aspect_ratio": "16:9",
+             "r_frame_rate": "25/1", "avg_frame_rate": "25/1", "time_base": "1/12800"},
+            {"index": 1, "codec_name": "aac", "codec_long_name": "AAC", "profile": "LC",
+             "codec_type": "audio", "codec_tag_string": "mp4a", "codec_tag": "0x6134706d",
+             "sample_fmt": "fltp", "sample_rate": "48000", "channels": 2,
+             "channel_layout": "stereo", "bits_per_sample": 0,
+             "r_frame_rate": "0/0", "avg_frame_rate": "0/0", "time_base": "1/48000"}
+        ]
+    }"#;
+    let parsed: FfprobeOutput = serde_json::from_str(json).expect("parse");
+    let Stream::Video(video) = &parsed.streams[0] else {
+        panic!("expected video");
+    };
+    assert_eq!(video.sample_aspect_ratio, Some(Rational::new(64, 45)));
+    assert_eq!(video.display_aspect_ratio, Some(Rational::new(16, 9)));
+    assert_eq!(video.common.avg_frame_rate, Some(Rational::from(25)));
+    assert_eq!(parsed.streams[1].common().avg_frame_rate, None);
+
+    let value = serde_json::to_value(&parsed).expect("serialize");
+    assert_eq!(value["streams"][0]["display_aspect_ratio"], "16:9");
+    assert_eq!(value["streams"][1]["avg_frame_rate"], "0/0");
+}
@@ -0,0 +1,283 @@
+use rsproto::{
+    ffprobe, remux, run_ffmpeg, FfprobeOutput, FileSource, NewChapter, RemuxFormat, RemuxOptions,
+    RunOutput, StreamEdit,
+};
+use std::env;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+fn probe(path: &Path) -> FfprobeOutput {
+    block_on(ffprobe(FileSource::new(path))).expect("ffprobe")
+}
+
+/// 4 s of video, a stereo and a commentary track, English subtitles and two
+/// chapters.
+fn fixture(work: &Path) -> (RunOutput, PathBuf) {
+    let srt = work.join("subs.srt");
+    std::fs::write(
+        &srt,
+        "1\n00:00:00,500 --> 00:00:01,500\nHello\n\n2\n00:00:02,000 --> 00:00:03,000\nBye\n",
+    )
+    .expect("write srt");
+    let meta = work.join("chapters.txt");
+    std::fs::write(
+        &meta,
+        ";FFMETADATA1\ntitle=Original\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=2000\n\
+         title=One\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=2000\nEND=4000\ntitle=Two\n",
+    )
+    .expect("write chapters");
+
+    let out = ffmpeg(
+        &input_path(),
+        &[
+            "-i",
+            "{input}",
+            "-f",
+            "lavfi",
+            "-i",
+            "sine=f=440:r=48000:d=4",
+            "-i",
+            srt.to_str().unwrap(),
+            "-i",
+            meta.to_str().unwrap(),
+            "-map",
+            "0:v:0",
+            "-map",
+            "0:a:0",
+            "-map",
+            "1:a",
+            "-map",
+            "2:s",
+            "-map_metadata",
+            "3",
+            "-map_chapters",
+            "3",
+            "-t",
+            "4",
+            "-c:v",
+            "copy",
+            "-c:a",
+            "aac",
+            "-c:s",
+            "srt",
+            "-metadata:s:a:0",
+            "language=eng",
+            "-metadata:s:a:1",
+            "language=eng",
+            "-metadata:s:a:1",
+            "title=Commentary",
+            "-metadata:s:s:0",
+            "language=eng",
+            "-disposition:a:0",
+            "default",
+            "-disposition:a:1",
+            "0",
+            "{outdir}/fixture.mkv",
+        ],
+    );
+    let path = out.path().join("fixture.mkv");
+    (out, path)
+}
+
+#[test]
+fn rewrite_tags_dispositions_and_chapters() {
+    let work = tempfile::tempdir().expect("tempdir");
+    let (_fixture, path) = fixture(work.path());
+    let before = probe(&path);
+    assert_eq!(before.streams.len(), 4);
+    assert_eq!(before.chapters.len(), 2);
+
+    let options = RemuxOptions {
+        tags: vec![("title".to_string(), "Remuxed".to_string())],
+        streams: vec![
+            StreamEdit {
+                index: 1,
+                title: Some("Stereo".to_string()),
+                language: Some("de".to_string()),
+                default: Some(false),
+                ..Default::default()
+            },
+            StreamEdit {
+                index: 3,
+                title: Some("Signs".to_string()),
+                default: Some(true),
+                forced: Some(true),
+                tags: vec![("comment".to_string(), "edited".to_string())],
+                ..Default::default()
+            },
+        ],
+        // The commentary track.
+        drop: vec![2],
+        chapters: Some(vec![
+            NewChapter {
+                start: 0.0,
+                end: 1.5,
+                title: Some("Cold open; a=b".to_string()),
+            },
+            NewChapter {
+                start: 1.5,
+                end: 4.0,
+                title: None,
+            },
+        ]),
+        ..Default::default()
+    };
+    let out = remux(FileSource::new(&path), &before, &options).expect("remux");
+    let after = probe(&out.path().join("remux.mkv"));
+
+    assert_eq!(after.format.format_name, "matroska,webm");
+    assert_eq!(
+        after.format.tags.get("title").map(String::as_str),
+        Some("Remuxed")
+    );
+    assert_eq!(after.streams.len(), 3);
+    let codecs: Vec<&str> = after
+        .streams
+        .iter()
+        .map(|s| s.common().codec_name.as_str())
+        .collect();
+    assert_eq!(codecs, ["mpeg4", "aac", "subrip"]);
+
+    let audio = after.streams[1].common();
+    assert_eq!(audio.title(), Some("Stereo"));
+    assert_eq!(audio.language().as_deref(), Some("ger"));
+    assert!(!audio.disposition_flags().default);
+
+    let subs = after.streams[2].common();
+    assert_eq!(subs.title(), Some("Signs"));
+    // Untouched tags are copied.
+    assert_eq!(subs.language().as_deref(), Some("eng"));
+    assert_eq!(subs.tag("comment"), Some("edited"));
+    let flags = subs.disposition_flags();
+    assert!(flags.default && flags.forced);
+
+    assert_eq!(after.chapters.len(), 2);
+    assert_eq!(after.chapters[0].title(), Some("Cold open; a=b"));
+    assert!((after.chapters[0].end_time.unwrap() - 1.5).abs() < 0.001);
+    assert_eq!(after.chapters[1].title(), None);
+}
+
+#[test]
+fn mp4_without_chapters() {
+    let work = tempfile::tempdir().expect("tempdir");
+    let (_fixture, path) = fixture(work.path());
+    let before = probe(&path);
+
+    let options = RemuxOptions {
+        format: RemuxFormat::Mp4,
+        // Clear the title and keep video and the first audio track.
+        tags: vec![("title".to_string(), String::new())],
+        streams: vec![StreamEdit {
+            index: 1,
+            language: Some("fra".to_string()),
+            ..Default::default()
+        }],
+        drop: vec![2, 3],
+        chapters: Some(Vec::new()),
+    };
+    let out = remux(FileSource::new(&path), &before, &options).expect("remux");
+    let after = probe(&out.path().join("remux.mp4"));
+    assert!(after.format.format_name.contains("mp4"));
+    assert_eq!(after.streams.len(), 2);
+    assert!(after.chapters.is_empty());
+    assert!(!after.format.tags.contains_key("title"));
+    assert_eq!(after.streams[1].common().language().as_deref(), Some("fre"));
+}
+
+#[test]
+fn invalid_edits() {
+    let work = tempfile::tempdir().expect("tempdir");
+    let (_fixture, path) = fixture(work.path());
+    let before = probe(&path);
+    let err = |options: RemuxOptions| {
+        remux(FileSource::new(&path), &before, &options).expect_err("invalid options")
+    };
+
+    let e = err(RemuxOptions {
+        drop: vec![9],
+        ..Default::default()
+    });
+    assert!(e.contains("no input stream 9"), "{}", e);
+
+    let e = err(RemuxOptions {
+        drop: vec![0, 1, 2, 3],
+        ..Default::default()
+    });
+    assert!(e.contains("every stream"), "{}", e);
+
+    let e = err(RemuxOptions {
+        streams: vec![StreamEdit {
+            index: 1,
+            language: Some("english".to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    assert!(e.contains("language"), "{}", e);
+
+    let e = err(RemuxOptions {
+        streams: vec![StreamEdit {
+            index: 2,
+            default: Some(true),
+            ..Default::default()
+        }],
+        drop: vec![2],
+        ..Default::default()
+    });
+    assert!(e.contains("dropped"), "{}", e);
+
+    let e = err(RemuxOptions {
+        chapters: Some(vec![NewChapter {
+            start: 3.0,
+            end: 1.0,
+            title: None,
+        }]),
+        ..Default::default()
+    });
+    assert!(e.contains("invalid chapter"), "{}", e);
+}
@@ -0,0 +1,178 @@
+use rsproto::{ffprobe, run_ffmpeg, FfprobeOutput, FileSource, StreamPreferences, StreamSelection};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn block_on<F: std::future::Future>(mut fut: F) -> F::Output {
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn raw_waker() -> RawWaker {
+        fn no_op(_: *const ()) {}
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    // Safety: we never move the future after pinning.
+    let mut fut = unsafe { Pin::new_unchecked(&mut fut) };
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => std::thread::yield_now(),
+        }
+    }
+}
+
+fn stream(index: i64, kind: &str, extra: &str, disposition: &str, tags: &str) -> String {
+    let codec = match kind {
+        "video" => "h264",
+        "audio" => "aac",
+        _ => "subrip",
+    };
+    format!(
+        r#"{{"index": {}, "codec_name": "{}", "codec_long_name": "", "profile": "",
+            "codec_type": "{}", "codec_tag_string": "", "codec_tag": "0x0000",
+            "time_base": "1/1000", {} "disposition": {{{}}}, "tags": {{{}}}}}"#,
+        index, codec, kind, extra, disposition, tags
+    )
+}
+
+fn sample() -> FfprobeOutput {
+    let video = r#""width": 1920, "height": 1080, "pix_fmt": "yuv420p", "level": 40,"#;
+    let stereo =
+        r#""sample_rate": 48000, "channels": 2, "channel_layout": "stereo", "bits_per_sample": 0,"#;
+    let surround =
+        r#""sample_rate": 48000, "channels": 6, "channel_layout": "5.1", "bits_per_sample": 0,"#;
+    let streams = [
+        stream(0, "video", video, r#""attached_pic": 1"#, ""),
+        stream(1, "video", video, r#""default": 1"#, ""),
+        stream(
+            2,
+            "audio",
+            stereo,
+            "",
+            r#""language": "eng", "title": "Director's Commentary""#,
+        ),
+        stream(
+            3,
+            "audio",
+            surround,
+            r#""default": 1"#,
+            r#""language": "eng""#,
+        ),
+        stream(4, "audio", stereo, "", r#""language": "en""#),
+        stream(5, "audio", stereo, "", r#""LANGUAGE": "jpn""#),
+        stream(6, "subtitle", "", r#""forced": 1"#, r#""language": "eng""#),
+        stream(7, "subtitle", "", "", r#""language": "eng""#),
+        stream(8, "subtitle", "", "", r#""language": "fre""#),
+    ];
+    let json = format!(
+        r#"{{"streams": [{}], "format": {{"filename": "input", "nb_streams": 9,
+            "nb_programs": 0, "nb_stream_groups": 0, "format_name": "matroska,webm",
+            "format_long_name": "Matroska / WebM", "probe_score": 100}}}}"#,
+        streams.join(",")
+    );
+    serde_json::from_str(&json).expect("parse sample")
+}
+
+fn selection(video: i64, audio: i64, subtitle: Option<i64>) -> StreamSelection {
+    StreamSelection {
+        video: Some(video),
+        audio: Some(audio),
+        subtitle,
+    }
+}
+
+#[test]
+fn preferences() {
+    let probe = sample();
+
+    // Cover art is never the video stream; the default audio wins.
+    let chosen = probe.select_streams(&StreamPreferences::default());
+    assert_eq!(chosen, selection(1, 3, None));
+    assert_eq!(chosen.ffmpeg_args(), ["-map", "0:1", "-map", "0:3"]);
+
+    let japanese = probe.select_streams(&StreamPreferences {
+        audio_languages: vec!["ja".to_string(), "en".to_string()],
+        ..Default::default()
+    });
+    assert_eq!(japanese.audio, Some(5));
+
+    // Stereo English without the commentary.
+    let stereo = StreamPreferences {
+        audio_languages: vec!["eng".to_string()],
+        channels: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(probe.select_streams(&stereo).audio, Some(4));
+    // Without avoiding commentary, input order breaks the tie.
+    let any = StreamPreferences {
+        avoid_commentary: false,
+        ..stereo.clone()
+    };
+    assert_eq!(probe.select_streams(&any).audio, Some(2));
+    // Closest channel count, not the largest.
+    let five = StreamPreferences {
+        channels: Some(5),
+        ..stereo
+    };
+    assert_eq!(probe.select_streams(&five).audio, Some(3));
+
+    // Full subtitles before forced ones, in language order.
+    let subs = probe.select_streams(&StreamPreferences {
+        subtitle_languages: vec!["en".to_string(), "fr".to_string()],
+        ..Default::default()
+    });
+    assert_eq!(subs, selection(1, 3, Some(7)));
+    assert_eq!(
+        subs.ffmpeg_args(),
+        ["-map", "0:1", "-map", "0:3", "-map", "0:7"]
+    );
+    let french = probe.select_streams(&StreamPreferences {
+        subtitle_languages: vec!["fra".to_string(), "eng".to_string()],
+        ..Default::default()
+    });
+    assert_eq!(french.subtitle, Some(8));
+    let none = probe.select_streams(&StreamPreferences {
+        subtitle_languages: vec!["spa".to_string()],
+        ..Default::default()
+    });
+    assert_eq!(none.subtitle, None);
+}
+
+#[test]
+fn selection_maps_real_input() {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    let probe = block_on(ffprobe(FileSource::new(&input_path))).expect("ffprobe");
+    let chosen = probe.select_streams(&StreamPreferences::default());
+    assert!(chosen.video.is_some() && chosen.audio.is_some());
+
+    let mut args: Vec<String> = ["ffmpeg", "-hide_banner", "-loglevel", "error", "-y", "-i"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.push("{input}".to_string());
+    args.extend(chosen.ffmpeg_args());
+    args.extend(
+        ["-t", "1", "-c", "copy", "{outdir}/selected.mkv"]
+            .iter()
+            .map(|s| s.to_string()),
+    );
+    let out = run_ffmpeg(FileSource::new(&input_path), &args)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed");
+    let selected = block_on(ffprobe(FileSource::new(out.path().join("selected.mkv"))))
+        .expect("ffprobe selected");
+    assert_eq!(selected.streams.len(), 2);
+}
@@ -0,0 +1,72 @@
+use rsproto::{normalize_language, Disposition, FfprobeOutput, Stream};
+
+const SAMPLE_JSON: &str = r#"{
+    "streams": [
+        {
+            "index": 0, "codec_name": "hevc", "codec_long_name": "H.265 / HEVC",
+            "profile": "Main 10", "codec_type": "video", "codec_tag_string": "hvc1",
+            "codec_tag": "0x31637668", "width": 3840, "height": 2160, "pix_fmt": "yuv420p10le",
+            "level": 153, "r_frame_rate": "24000/1001", "avg_frame_rate": "24000/1001",
+            "time_base": "1/24000",
+            "disposition": {"default": 1, "attached_pic": 0, "forced": 0, "some_new_flag": 1},
+            "tags": {"language": "en", "handler_name": "VideoHandler", "rotate": "90"},
+            "side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]
+        },
+        {
+            "index": 1, "codec_name": "ass", "codec_long_name": "ASS (Advanced SSA) subtitle",
+            "profile": "unknown", "codec_type": "subtitle", "codec_tag_string": "[0][0][0][0]",
+            "codec_tag": "0x0000", "r_frame_rate": "0/0", "avg_frame_rate": "0/0",
+            "time_base": "1/1000",
+            "disposition": {"default": 0, "forced": 1, "hearing_impaired": 1},
+            "tags": {"LANGUAGE": "deu", "TITLE": "Signs & Songs", "ENCODER": "libass"}
+        }
+    ],
+    "format": {
+        "filename": "input", "nb_streams": 2, "nb_programs": 0, "nb_stream_groups": 0,
+        "format_name": "matroska,webm", "format_long_name": "Matroska / WebM",
+        "probe_score": 100
+    }
+}"#;
+
+#[test]
+fn typed_disposition_and_tags() {
+    let parsed: FfprobeOutput = serde_json::from_str(SAMPLE_JSON).expect("parse sample");
+    let (video, subtitle) = match (&parsed.streams[0], &parsed.streams[1]) {
+        (Stream::Video(v), Stream::Subtitle(s)) => (v, s),
+        other => panic!("unexpected streams: {:?}", other),
+    };
+
+    let flags = video.common.disposition_flags();
+    assert!(flags.default);
+    assert!(!flags.attached_pic);
+    assert_eq!(video.common.disposition.get("some_new_flag"), Some(&1));
+    assert_eq!(video.common.language().as_deref(), Some("eng"));
+    assert_eq!(video.common.handler_name(), Some("VideoHandler"));
+    assert_eq!(video.common.rotation(), Some(90));
+    assert_eq!(video.rotation(), Some(90));
+
+    assert_eq!(
+        subtitle.common.disposition_flags(),
+        Disposition {
+            forced: true,
+            hearing_impaired: true,
+            ..Disposition::default()
+        }
+    );
+    assert_eq!(subtitle.common.language().as_deref(), Some("ger"));
+    assert_eq!(subtitle.common.title(), Some("Signs & Songs"));
+    assert_eq!(subtitle.common.encoder(), Some("libass"));
+    assert_eq!(subtitle.common.rotation(), None);
+}
+
+#[test]
+fn language_normalization() {
+    assert_eq!(normalize_language("EN").as_deref(), Some("eng"));
+    assert_eq!(normalize_language("pt-BR").as_deref(), Some("por"));
+    assert_eq!(normalize_language("fra").as_deref(), Some("fre"));
+    assert_eq!(normalize_language("zho").as_deref(), Some("chi"));
+    assert_eq!(normalize_language("jpn").as_deref(), Some("jpn"));
+    assert_eq!(normalize_language("und"), None);
+    assert_eq!(normalize_language(""), None);
+    assert_eq!(normalize_language("xx"), None);
+}
@@ -0,0 +1,51 @@
+use rsproto::{FfprobeOutput, Stream, SubtitleKind, SubtitleStream};
+
+fn subtitle(codec_name: &str) -> SubtitleStream {
+    let json = format!(
+        r#"{{
+            "streams": [{{"index": 0, "codec_type": "subtitle", "codec_name": "{}",
+                "codec_long_name": "", "profile": "", "codec_tag_string": "[0][0][0][0]",
+                "codec_tag": "0x0000", "r_frame_rate": "0/0", "avg_frame_rate": "0/0",
+                "time_base": "1/1000"}}],
+            "format": {{
+                "filename": "input", "nb_streams": 1, "nb_programs": 0,
+                "nb_stream_groups": 0, "format_name": "matroska,webm", "format_long_name": "",
+                "probe_score": 100
+            }}
+        }}"#,
+        codec_name
+    );
+    let parsed: FfprobeOutput = serde_json::from_str(&json).expect("parse sample");
+    match parsed.streams.into_iter().next() {
+        Some(Stream::Subtitle(s)) => s,
+        other => panic!("expected subtitle stream, got {:?}", other),
+    }
+}
+
+#[test]
+fn text_codecs_convert_to_webvtt() {
+    for codec in ["subrip", "ass", "webvtt", "mov_text"] {
+        let s = subtitle(codec);
+        assert_eq!(s.kind(), SubtitleKind::Text, "{codec}");
+        assert!(s.can_convert_to_webvtt(), "{codec}");
+        assert!(!s.requires_burn_in(), "{codec}");
+    }
+}
+
+#[test]
+fn bitmap_codecs_need_burn_in() {
+    for codec in ["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle"] {
+        let s = subtitle(codec);
+        assert_eq!(s.kind(), SubtitleKind::Bitmap, "{codec}");
+        assert!(s.requires_burn_in(), "{codec}");
+        assert!(!s.can_convert_to_webvtt(), "{codec}");
+    }
+}
+
+#[test]
+fn undecodable_codecs_are_unknown() {
+    let s = subtitle("dvb_teletext");
+    assert_eq!(s.kind(), SubtitleKind::Unknown);
+    assert!(!s.can_convert_to_webvtt());
+    assert!(!s.requires_burn_in());
+}
@@ -0,0 +1,134 @@
+use rsproto::{FfprobeOutput, HdrFormat, Rational, SideData, Stream, VideoStream};
+
+fn video(extra: &str) -> VideoStream {
+    let json = format!(
+        r#"{{
+            "streams": [{{
+                "index": 0, "codec_name": "hevc", "codec_long_name": "H.265 / HEVC",
+                "profile": "Main 10", "codec_type": "video", "codec_tag_string": "hvc1",
+                "codec_tag": "0x31637668", "width": 3840, "height": 2160,
+                "pix_fmt": "yuv420p10le", "level": 153, "r_frame_rate": "24/1",
+                "avg_frame_rate": "24/1", "time_base": "1/24"{}
+            }}],
+            "format": {{
+                "filename": "input", "nb_streams": 1, "nb_programs": 0,
+                "nb_stream_groups": 0, "format_name": "matroska,webm",
+                "format_long_name": "Matroska / WebM", "probe_score": 100
+            }}
+        }}"#,
+        extra
+    );
+    let parsed: FfprobeOutput = serde_json::from_str(&json).expect("parse sample");
+    let value = serde_json::to_value(&parsed).expect("serialize");
+    let reparsed: FfprobeOutput = serde_json::from_value(value.clone()).expect("re-parse");
+    assert_eq!(
+        serde_json::to_value(&reparsed).expect("serialize again"),
+        value
+    );
+    match parsed.streams.into_iter().next() {
+        Some(Stream::Video(v)) => v,
+        other => panic!("expected a video stream, got {:?}", other),
+    }
+}
+
+#[test]
+fn hdr10_with_static_metadata() {
+    let v = video(
+        r#", "color_range": "tv", "color_space": "bt2020nc", "color_transfer": "smpte2084",
+        "color_primaries": "bt2020", "chroma_location": "topleft", "field_order": "progressive",
+        "side_data_list": [
+            {"side_data_type": "Mastering display metadata",
+             "red_x": "34000/50000", "red_y": "16000/50000", "green_x": "13250/50000",
+             "green_y": "34500/50000", "blue_x": "7500/50000", "blue_y": "3000/50000",
+             "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+             "min_luminance": "50/10000", "max_luminance": "40000000/10000"},
+            {"side_data_type": "Content light level metadata", "max_content": 1000,
+             "max_average": 400}
+        ]"#,
+    );
+    assert_eq!(v.color_range.as_deref(), Some("tv"));
+    assert_eq!(v.color_space.as_deref(), Some("bt2020nc"));
+    assert_eq!(v.color_primaries.as_deref(), Some("bt2020"));
+    assert_eq!(v.chroma_location.as_deref(), Some("topleft"));
+    assert_eq!(v.field_order.as_deref(), Some("progressive"));
+    assert_eq!(v.hdr_format(), Some(HdrFormat::Hdr10));
+    assert!(v.needs_tone_mapping());
+
+    let mastering = v.mastering_display().expect("mastering display");
+    let primaries = mastering.primaries.expect("primaries");
+    assert_eq!(
+        primaries.red_x,
+        Rational {
+            num: 34000,
+            den: 50000
+        }
+    );
+    let luminance = mastering.luminance.expect("luminance");
+    assert_eq!(
+        luminance.max_luminance.num / luminance.max_luminance.den,
+        4000
+    );
+    let cll = v.content_light_level().expect("content light level");
+    assert_eq!((cll.max_content, cll.max_average), (1000, 400));
+}
+
+#[test]
+fn dolby_vision_and_hlg() {
+    let dv = video(
+        r#", "color_transfer": "smpte2084",
+        "side_data_list": [
+            {"side_data_type": "DOVI configuration record", "dv_version_major": 1,
+             "dv_version_minor": 0, "dv_profile": 8, "dv_level": 6, "rpu_present_flag": 1,
+             "el_present_flag": 0, "bl_present_flag": 1, "dv_bl_signal_compatibility_id": 1,
+             "dv_md_compression": "none"}
+        ]"#,
+    );
+    assert_eq!(
+        dv.hdr_format(),
+        Some(HdrFormat::DolbyVision {
+            profile: 8,
+            compatibility_id: 1
+        })
+    );
+    assert_eq!(
+        dv.dolby_vision()
+            .expect("dovi")
+            .unknown
+            .get("dv_md_compression"),
+        Some(&serde_json::json!("none"))
+    );
+
+    let hlg = video(r#", "color_transfer": "arib-std-b67""#);
+    assert_eq!(hlg.hdr_format(), Some(HdrFormat::Hlg));
+}
+
+#[test]
+fn sdr_and_unknown_side_data() {
+    let v = video(
+        r#", "color_range": "unknown", "color_transfer": "bt709",
+        "side_data_list": [
+            {"side_data_type": "Display Matrix", "displaymatrix": "\n00000000: 0 65536 0\n",
+             "rotation": -90},
+            {"side_data_type": "Stereo 3D", "type": "2D", "inverted": 0},
+            {"side_data_type": "Content light level metadata", "max_content": "n/a"}
+        ]"#,
+    );
+    assert_eq!(v.color_range, None);
+    assert_eq!(v.hdr_format(), None);
+    assert!(!v.needs_tone_mapping());
+    assert_eq!(v.rotation(), Some(90));
+    match &v.side_data_list[1] {
+        SideData::Other(map) => assert_eq!(map["type"], "2D"),
+        other => panic!("expected untyped side data, got {:?}", other),
+    }
+    assert_eq!(v.side_data_list[1].side_data_type(), "Stereo 3D");
+    // A known type that doesn't parse is kept untyped.
+    match &v.side_data_list[2] {
+        SideData::Other(map) => assert_eq!(map["max_content"], "n/a"),
+        other => panic!("expected untyped side data, got {:?}", other),
+    }
+    assert_eq!(
+        v.side_data_list[2].side_data_type(),
+        "Content light level metadata"
+    );
+}
@@ -0,0 +1,116 @@
+use rsproto::{audio_waveform, run_ffmpeg, FileSource, RunOutput, Waveform, WaveformOptions};
+use std::env;
+use std::path::Path;
+
+const DEFAULT_INPUT: &str =
+    "/Users/michelbartels/Documents/personal-projects/backend-torrent/ffmpeg/Big_Buck_Bunny.mp4";
+
+fn input_path() -> String {
+    let input_path = env::var("INPUT_FILE").unwrap_or_else(|_| DEFAULT_INPUT.to_string());
+    if !Path::new(&input_path).exists() {
+        panic!("Input file not found: {}", input_path);
+    }
+    input_path
+}
+
+fn ffmpeg(input: &str, args: &[&str]) -> RunOutput {
+    let mut full = vec!["ffmpeg", "-hide_banner", "-loglevel", "error", "-y"];
+    full.extend_from_slice(args);
+    let full: Vec<String> = full.iter().map(|s| s.to_string()).collect();
+    run_ffmpeg(FileSource::new(input), &full)
+        .expect("run_ffmpeg start")
+        .wait()
+        .expect("ffmpeg run failed")
+}
+
+#[test]
+fn tone_then_silence() {
+    // One second of a half-scale 440 Hz tone, then one second of silence.
+    let wav = ffmpeg(
+        &input_path(),
+        &[
+            "-i",
+            "{input}",
+            "-f",
+            "lavfi",
+            "-i",
+            "aevalsrc=0.5*sin(2*PI*440*t)*lt(t\\,1):s=44100:d=2",
+            "-map",
+            "1:a",
+            "-c:a",
+            "pcm_f32le",
+            "{outdir}/tone.wav",
+        ],
+    );
+    let path = wav.path().join("tone.wav");
+
+    let waveform = audio_waveform(
+        FileSource::new(&path),
+        &WaveformOptions {
+            buckets_per_second: 10,
+            ..Default::default()
+        },
+    )
+    .expect("audio_waveform");
+    assert_eq!(waveform.samples_per_bucket, 4800);
+    assert_eq!(waveform.sample_rate, 48000);
+    assert_eq!(waveform.buckets_per_second(), 10.0);
+    assert_eq!(waveform.peaks.len(), 20, "{:?}", waveform.peaks);
+    for &(min, max) in &waveform.peaks[..9] {
+        assert!(
+            (min + 0.5).abs() < 0.02 && (max - 0.5).abs() < 0.02,
+            "{} {}",
+            min,
+            max
+        );
+    }
+    for &(min, max) in &waveform.peaks[11..] {
+        assert!(min.abs() < 0.01 && max.abs() < 0.01, "{} {}", min, max);
+    }
+
+    let dat = waveform.to_dat();
+    assert_eq!(dat.len(), 20 + 20 * 4);
+    let word = |i: usize| i32::from_le_bytes(dat[i * 4..i * 4 + 4].try_into().unwrap());
+    assert_eq!(
+        [word(0), word(1), word(2), word(3), word(4)],
+        [1, 0, 48000, 4800, 20]
+    );
+    let min = i16::from_le_bytes([dat[20], dat[21]]);
+    let max = i16::from_le_bytes([dat[22], dat[23]]);
+    assert!((min as i32 + 16384).abs() < 700, "{}", min);
+    assert!((max as i32 - 16384).abs() < 700, "{}", max);
+}
+
+#[test]
+fn input_audio_and_bucket_rates() {
+    let input_path = input_path();
+    let fine = audio_waveform(FileSource::new(&input_path), &WaveformOptions::default())
+        .expect("audio_waveform");
+    assert_eq!(fine.samples_per_bucket, 480);
+    assert!(fine.peaks.len() > 100);
+    assert!(fine.peaks.iter().all(|(min, max)| min <= max));
+    assert!(fine.peaks.iter().any(|(_, max)| *max > 0.05));
+
+    // Rates that don't divide 48 kHz are resampled to a nearby multiple.
+    let odd = audio_waveform(
+        FileSource::new(&input_path),
+        &WaveformOptions {
+            buckets_per_second: 256,
+            ..Default::default()
+        },
+    )
+    .expect("audio_waveform");
+    assert_eq!(odd.samples_per_bucket, 188);
+    assert_eq!(odd.sample_rate, 188 * 256);
+    let seconds = |w: &Waveform| w.peaks.len() as f64 / w.buckets_per_second();
+    assert!((seconds(&odd) - seconds(&fine)).abs() < 0.05);
+
+    assert!(audio_waveform(
+        FileSource::new(&input_path),
+        &WaveformOptions {
+            buckets_per_second: 0,
+            ..Default::default()
+        },
+    )
+    .is_err());
+}
@@ -0,0 +1,233 @@
+//! Checks the hand-written ffprobe types against `doc/ffprobe.xsd`, which
+//! defines the fields ffprobe can print. A field added to the schema has to
+//! be either modelled or listed as left in `unknown` before this passes.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rsproto::{FfprobeOutput, Stream};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+const FORMAT_MODELLED: &[&str] = &[
+    "filename",
+    "nb_streams",
+    "nb_programs",
+    "nb_stream_groups",
+    "format_name",
+    "format_long_name",
+    "start_time",
+    "duration",
+    "size",
+    "bit_rate",
+    "probe_score",
+    "tags",
+];
+
+const FORMAT_UNMODELLED: &[&str] = &[];
+
+/// `StreamCommon`, shared by every stream type.
+const STREAM_COMMON: &[&str] = &[
+    "index",
+    "codec_name",
+    "codec_long_name",
+    "profile",
+    "codec_type",
+    "codec_tag",
+    "codec_tag_string",
+    "r_frame_rate",
+    "avg_frame_rate",
+    "time_base",
+    "start_time",
+    "duration",
+    "bit_rate",
+    "disposition",
+    "tags",
+];
+
+const VIDEO_MODELLED: &[&str] = &[
+    "width",
+    "height",
+    "pix_fmt",
+    "level",
+    "sample_aspect_ratio",
+    "display_aspect_ratio",
+    "field_order",
+    "color_range",
+    "color_space",
+    "color_transfer",
+    "color_primaries",
+    "chroma_location",
+    "side_data_list",
+];
+
+const AUDIO_MODELLED: &[&str] = &[
+    "sample_rate",
+    "channels",
+    "channel_layout",
+    "bits_per_sample",
+];
+
+const SUBTITLE_MODELLED: &[&str] = &["width", "height"];
+
+const ATTACHMENT_MODELLED: &[&str] = &["extradata_size"];
+
+/// Stream fields that are deliberately kept in `unknown`.
+const STREAM_UNMODELLED: &[&str] = &[
+    "extradata",
+    "extradata_hash",
+    "mime_codec_string",
+    "coded_width",
+    "coded_height",
+    "closed_captions",
+    "film_grain",
+    "has_b_frames",
+    "refs",
+    "sample_fmt",
+    "initial_padding",
+    "id",
+    "start_pts",
+    "duration_ts",
+    "max_bit_rate",
+    "bits_per_raw_sample",
+    "nb_frames",
+    "nb_read_frames",
+    "nb_read_packets",
+];
+
+/// Attribute and child element names of each named complex type, with the
+/// XSD type of attributes (`None` for elements).
+fn xsd_types() -> BTreeMap<String, BTreeMap<String, Option<String>>> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/doc/ffprobe.xsd");
+    let mut reader = Reader::from_file(path).expect("open ffprobe.xsd");
+    let mut buf = Vec::new();
+    let mut types = BTreeMap::new();
+    let mut current: Option<String> = None;
+    let attr = |e: &quick_xml::events::BytesStart<'_>, name: &str| {
+        e.try_get_attribute(name)
+            .expect("attribute")
+            .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+    };
+    loop {
+        match reader.read_event_into(&mut buf).expect("read ffprobe.xsd") {
+            Event::Start(e) if e.name().as_ref() == b"xsd:complexType" => {
+                current = attr(&e, "name");
+            }
+            Event::End(e) if e.name().as_ref() == b"xsd:complexType" => current = None,
+            Event::Start(e) | Event::Empty(e) => {
+                let Some(ty) = &current else {
+                    continue;
+                };
+                let field_type = match e.name().as_ref() {
+                    b"xsd:attribute" => Some(attr(&e, "type")),
+                    b"xsd:element" => Some(None),
+                    _ => None,
+                };
+                if let (Some(field_type), Some(name)) = (field_type, attr(&e, "name")) {
+                    types
+                        .entry(ty.clone())
+                        .or_insert_with(BTreeMap::new)
+                        .insert(name, field_type);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    types
+}
+
+fn set(lists: &[&[&str]]) -> BTreeSet<String> {
+    lists
+        .iter()
+        .flat_map(|l| l.iter().map(|s| s.to_string()))
+        .collect()
+}
+
+/// A plausible JSON value for every field, in the shape ffprobe prints.
+fn sample_object(fields: &BTreeMap<String, Option<String>>, codec_type: &str) -> Value {
+    let mut obj = Map::new();
+    for (name, ty) in fields {
+        let value = match (name.as_str(), ty.as_deref()) {
+            ("codec_type", _) => json!(codec_type),
+            ("r_frame_rate" | "avg_frame_rate" | "time_base", _) => json!("1/25"),
+            ("sample_aspect_ratio" | "display_aspect_ratio", _) => json!("1:1"),
+            ("tags", _) => json!({"title": "x"}),
+            ("disposition", _) => json!({"default": 1}),
+            ("side_data_list", _) => json!([]),
+            (_, Some("xsd:int" | "xsd:long" | "xsd:boolean")) => json!(1),
+            (_, Some("xsd:float")) => json!("1.000000"),
+            _ => json!("x"),
+        };
+        obj.insert(name.clone(), value);
+    }
+    Value::Object(obj)
+}
+
+#[test]
+fn format_fields_are_mapped() {
+    let types = xsd_types();
+    let format = &types["formatType"];
+    let xsd: BTreeSet<String> = format.keys().cloned().collect();
+    assert_eq!(xsd, set(&[FORMAT_MODELLED, FORMAT_UNMODELLED]));
+
+    let json = json!({"format": sample_object(format, ""), "streams": []});
+    let parsed: FfprobeOutput = serde_json::from_value(json).expect("parse format");
+    assert_eq!(
+        parsed.format.unknown.into_keys().collect::<BTreeSet<_>>(),
+        set(&[FORMAT_UNMODELLED])
+    );
+}
+
+#[test]
+fn stream_fields_are_mapped() {
+    let types = xsd_types();
+    let stream = &types["streamType"];
+    let xsd: BTreeSet<String> = stream.keys().cloned().collect();
+    let mapped = set(&[
+        STREAM_COMMON,
+        VIDEO_MODELLED,
+        AUDIO_MODELLED,
+        SUBTITLE_MODELLED,
+        ATTACHMENT_MODELLED,
+        STREAM_UNMODELLED,
+    ]);
+    let added: Vec<_> = xsd.difference(&mapped).collect();
+    assert!(
+        added.is_empty(),
+        "unmapped stream fields in the XSD: {added:?}"
+    );
+    let stale: Vec<_> = mapped.difference(&xsd).collect();
+    assert!(
+        stale.is_empty(),
+        "mapped fields missing from the XSD: {stale:?}"
+    );
+
+    let format = sample_object(&types["formatType"], "");
+    for (codec_type, modelled) in [
+        ("video", VIDEO_MODELLED),
+        ("audio", AUDIO_MODELLED),
+        ("subtitle", SUBTITLE_MODELLED),
+        ("attachment", ATTACHMENT_MODELLED),
+        ("data", &[][..]),
+    ] {
+        let json = json!({"format": format, "streams": [sample_object(stream, codec_type)]});
+        let parsed: FfprobeOutput = serde_json::from_value(json).expect(codec_type);
+        let unknown = match parsed.streams.into_iter().next().expect("stream") {
+            Stream::Video(s) => s.unknown,
+            Stream::Audio(s) => s.unknown,
+            Stream::Subtitle(s) => s.unknown,
+            Stream::Attachment(s) => s.unknown,
+            Stream::Other(s) => s.unknown,
+        };
+        let expected: BTreeSet<String> = xsd
+            .difference(&set(&[STREAM_COMMON, modelled]))
+            .cloned()
+            .collect();
+        assert_eq!(
+            unknown.into_keys().collect::<BTreeSet<_>>(),
+            expected,
+            "{codec_type} stream"
+        );
+    }
+}